@@ -0,0 +1,40 @@
+use criterion::{black_box,criterion_group,criterion_main,Criterion,Throughput};
+use lil::lexer::{Lexer,TokenType};
+
+#[path = "../tests/generator/mod.rs"]
+mod generator;
+
+/// Number of method declarations in the generated program.
+const METHODS : usize = 1000;
+
+/// Count the tokens in a given input.
+fn lex(input: &str) -> u64 {
+    let mut lexer = Lexer::new(input);
+    let mut count = 0;
+    while lexer.next().kind != TokenType::EOF {
+	count += 1;
+    }
+    count
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let input = generator::generate_program(METHODS);
+    let mut group = c.benchmark_group("lexer");
+    // Report throughput in tokens/sec
+    group.throughput(Throughput::Elements(lex(&input)));
+    group.bench_function("lex",|b| b.iter(|| lex(black_box(&input))));
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let input = generator::generate_program(METHODS);
+    let (ast,_) = lil::parse(&input).unwrap();
+    let mut group = c.benchmark_group("parser");
+    // Report throughput in nodes/sec
+    group.throughput(Throughput::Elements(ast.len() as u64));
+    group.bench_function("parse",|b| b.iter(|| lil::parse(black_box(&input)).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches,bench_lexer,bench_parser);
+criterion_main!(benches);
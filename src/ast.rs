@@ -1,58 +1,400 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::fmt;
-use std::convert::From;
+use std::hash::{Hash,Hasher};
+use std::convert::{From,TryFrom};
+use serde::{Deserialize,Serialize};
 use syntactic_heap::SyntacticHeap;
-use syntactic_heap::Ref;
 
 // =============================================================================
 // Abstract Syntax Tree
 // =============================================================================
 
-pub type AbstractSyntaxTree = SyntacticHeap<Node>;
+/// The heap of nodes making up an abstract syntax tree, where nodes
+/// refer to each other by index.  This wraps the underlying heap
+/// implementation, such that it is not exposed to users and can be
+/// changed without breaking them.
+pub struct Ast {
+    heap: SyntacticHeap<Node>,
+    /// Indices of the top-level declarations held in this heap, in
+    /// the order they were declared.
+    roots: Vec<usize>,
+    /// Slots which have been reserved (see `reserve`), along with the
+    /// node they were filled with (if any).
+    reserved: HashMap<usize,Option<Node>>
+}
+
+/// Retained for compatibility, since this was the original name for
+/// the heap.
+pub type AbstractSyntaxTree = Ast;
+
+impl Ast {
+    pub fn new() -> Self {
+	Ast{heap: SyntacticHeap::new(), roots: Vec::new(), reserved: HashMap::new()}
+    }
+
+    /// Construct an empty heap expected to hold around `n` nodes.
+    /// Only the node storage is pre-sized, since there are typically
+    /// far fewer roots (or reserved slots) than nodes.  The resulting
+    /// heap is otherwise identical to one from `new`.
+    pub fn with_capacity(n: usize) -> Self {
+	Ast{heap: SyntacticHeap::with_capacity(n), roots: Vec::new(), reserved: HashMap::new()}
+    }
+
+    /// Determine the number of nodes in this heap.
+    pub fn len(&self) -> usize {
+	self.heap.len()
+    }
+
+    /// Determine whether this heap contains no nodes.
+    pub fn is_empty(&self) -> bool {
+	self.len() == 0
+    }
+
+    /// Get the node at a given index.  Reading a slot which has been
+    /// reserved but not yet filled is a bug, and is caught by a debug
+    /// assertion.
+    pub fn get(&self, index: usize) -> &Node {
+	// Avoid the lookup in the (common) case nothing was reserved
+	if self.reserved.is_empty() {
+	    return self.heap.get(index);
+	}
+	match self.reserved.get(&index) {
+	    Some(Some(node)) => node,
+	    Some(None) => {
+		debug_assert!(false,"reserved node {} read before being filled",index);
+		self.heap.get(index)
+	    }
+	    None => self.heap.get(index)
+	}
+    }
+
+    /// Get the indices of the top-level declarations in this heap (as
+    /// recorded by `Parser::parse_program`).  These are the entry
+    /// points from which the rest of the tree can be reached.
+    pub fn roots(&self) -> &[usize] {
+	&self.roots
+    }
+
+    /// Record that the node at a given index is a top-level
+    /// declaration.
+    pub fn add_root(&mut self, index: usize) {
+	self.roots.push(index);
+    }
+
+    /// Push a new node onto the heap, returning a reference to it.
+    pub fn push(&mut self, node: Node) -> AstRef<'_> {
+	let index = self.heap.push(node).raw_index();
+	AstRef{ast: self, index}
+    }
+
+    /// Reserve a slot on the heap, returning its index.  This allows
+    /// a node to be referred to before it has been constructed (e.g.
+    /// when its children need to know its index).  The slot must be
+    /// completed with `fill` before it is read.
+    pub fn reserve(&mut self) -> usize {
+	let index = self.heap.push(Node::Error).raw_index();
+	self.reserved.insert(index,None);
+	index
+    }
+
+    /// Fill a slot previously obtained from `reserve` with a given
+    /// node.  This panics if the slot was not reserved, or has
+    /// already been filled.
+    pub fn fill(&mut self, index: usize, node: Node) {
+	match self.reserved.get_mut(&index) {
+	    Some(slot @ None) => { *slot = Some(node); }
+	    Some(Some(_)) => panic!("reserved node {} filled twice",index),
+	    None => panic!("node {} was not reserved",index)
+	}
+    }
+}
+
+/// The serialised form of a heap, which is simply its nodes (in
+/// order) along with its roots.
+#[derive(Serialize)]
+struct AstRepr<'a> {
+    nodes: Vec<&'a Node>,
+    roots: &'a [usize]
+}
+
+/// As for `AstRepr`, but owning its contents such that it can be
+/// deserialised.
+#[derive(Deserialize)]
+struct AstData {
+    nodes: Vec<Node>,
+    roots: Vec<usize>
+}
+
+impl Serialize for Ast {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok,S::Error> {
+	let nodes = (0..self.len()).map(|i| self.get(i)).collect();
+	AstRepr{nodes, roots: &self.roots}.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ast {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self,D::Error> {
+	let data = AstData::deserialize(deserializer)?;
+	let mut ast = Ast::new();
+	for n in data.nodes {
+	    ast.push(n);
+	}
+	ast.roots = data.roots;
+	Ok(ast)
+    }
+}
+
+impl Ast {
+    /// Serialise this heap as JSON.  Each node is written as an
+    /// object keyed by its kind (e.g. `{"TypeDecl":[0,1]}`), with
+    /// children given by index.
+    pub fn to_json(&self) -> String {
+	// NOTE: serialising nodes and indices cannot fail
+	serde_json::to_string(self).unwrap()
+    }
+
+    /// Deserialise a heap from JSON (as written by `to_json`).
+    pub fn from_json(json: &str) -> serde_json::Result<Ast> {
+	serde_json::from_str(json)
+    }
+}
+
+impl Default for Ast {
+    fn default() -> Self {
+	Ast::new()
+    }
+}
+
+/// Nodes are printed in order, each prefixed with its index (e.g.
+/// `#0:skip`).  Reserved slots are printed as they were filled.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	for i in 0..self.len() {
+	    write!(f,"#{}:{} ",i,self.get(i))?;
+	}
+	Ok(())
+    }
+}
+
+/// A reference to a node held within a given heap.
+#[derive(Clone,Copy)]
+pub struct AstRef<'a> {
+    ast: &'a Ast,
+    index: usize
+}
+
+impl<'a> AstRef<'a> {
+    /// Get the index of the node being referred to.
+    pub fn raw_index(&self) -> usize {
+	self.index
+    }
+
+    /// Get the node being referred to.
+    pub fn get(&self) -> &'a Node {
+	self.ast.get(self.index)
+    }
+}
 
 // =============================================================================
 // Terms
 // =============================================================================
 
-#[derive(Clone,Debug,PartialEq)]
+#[derive(Clone,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
 pub enum Node {
     // Base
     Utf8(String),
+    // Placeholder for a subterm which could not be parsed
+    Error,
     // Declarations
+    AnnotatedDecl(Vec<Annotation>,Decl),
+    ConstDecl(Type,Name,Expr),
     TypeDecl(Name,Type),
     MethodDecl(Name,Type,Vec<Parameter>,Stmt),
     // Statements
     AssertStmt(Expr),
+    AssignStmt(Expr,Expr),
     BlockStmt(Vec<Stmt>),
+    BreakStmt,
+    ContinueStmt,
+    ForStmt(Stmt,Expr,Stmt,Stmt),
+    IfStmt(Expr,Stmt,Option<Stmt>),
+    PrintStmt(Expr),
+    ReturnStmt(Option<Expr>),
     SkipStmt,
+    SwitchStmt(Expr,Vec<(Option<Expr>,Stmt)>),
+    TypeAliasStmt(Name,Type),
+    VarDeclStmt(Type,Name,Expr),
+    WhileStmt(Expr,Stmt),
     // Expressions
+    AddExpr(Expr,Expr),
+    AndExpr(Expr,Expr),
+    ArrayAccessExpr(Expr,Expr),
     BoolExpr(bool),
+    CallExpr(Name,Vec<Expr>),
     EqualsExpr(Expr,Expr),
+    FieldAccessExpr(Expr,Name),
     NotEqualsExpr(Expr,Expr),
+    NotExpr(Expr),
+    NullExpr,
+    OrExpr(Expr,Expr),
     LessThanExpr(Expr,Expr),
-    IntExpr(i32),
+    /// An integer literal, given by its sign (`true` if negative) and
+    /// magnitude.  This can hold every value of both `i128` and `u128`.
+    IntExpr(bool,u128),
+    LenExpr(Expr),
+    SizeofExpr(Type),
+    SubtractExpr(Expr,Expr),
     VarExpr(Name),
     // Types
     ArrayType(Type),
     BoolType,
     IntType(bool,u8),
+    /// Marks a method return type (i.e. `var`) which is to be inferred
+    /// from the method's body.
+    InferredType,
     NullType,
     RecordType(Vec<(Type,Name)>),
     ReferenceType(Type),
+    SizedArrayType(Type,Expr),
     VoidType
 }
 
+impl Node {
+    /// Determine the indices of all nodes referenced by this node, in
+    /// the order they appear.  This allows generic passes to traverse
+    /// the tree without knowing the layout of each variant.
+    pub fn children(&self) -> Vec<usize> {
+	match self {
+	    Node::Utf8(_) => vec![],
+	    Node::Error => vec![],
+	    // Declarations
+	    Node::AnnotatedDecl(anns,d) => {
+		let mut cs = vec![];
+		for a in anns {
+		    cs.push(a.name.0);
+		    cs.extend(a.args.iter().map(|e| e.0));
+		}
+		cs.push(d.index);
+		cs
+	    }
+	    Node::ConstDecl(t,n,e) => vec![t.0,n.0,e.0],
+	    Node::TypeDecl(n,t) => vec![n.0,t.0],
+	    Node::MethodDecl(n,t,ps,b) => {
+		let mut cs = vec![n.0,t.0];
+		for p in ps {
+		    cs.push(p.declared.0);
+		    cs.push(p.name.0);
+		    cs.push(p.site.0);
+		}
+		cs.push(b.0);
+		cs
+	    }
+	    // Statements
+	    Node::AssertStmt(e) => vec![e.0],
+	    Node::AssignStmt(l,r) => vec![l.0,r.0],
+	    Node::BlockStmt(ss) => ss.iter().map(|s| s.0).collect(),
+	    Node::IfStmt(c,t,f) => {
+		let mut cs = vec![c.0,t.0];
+		cs.extend(f.iter().map(|s| s.0));
+		cs
+	    }
+	    Node::PrintStmt(e) => vec![e.0],
+	    Node::ReturnStmt(e) => e.iter().map(|e| e.0).collect(),
+	    Node::ForStmt(i,c,u,b) => vec![i.0,c.0,u.0,b.0],
+	    Node::BreakStmt|Node::ContinueStmt|Node::SkipStmt => vec![],
+	    Node::SwitchStmt(e,cs) => {
+		let mut ns = vec![e.0];
+		for (l,s) in cs {
+		    if let Some(l) = l { ns.push(l.0); }
+		    ns.push(s.0);
+		}
+		ns
+	    }
+	    Node::TypeAliasStmt(n,t) => vec![n.0,t.0],
+	    Node::VarDeclStmt(t,n,e) => vec![t.0,n.0,e.0],
+	    Node::WhileStmt(c,b) => vec![c.0,b.0],
+	    // Expressions
+	    Node::AddExpr(l,r)|Node::AndExpr(l,r)|Node::EqualsExpr(l,r)|Node::NotEqualsExpr(l,r)
+		|Node::LessThanExpr(l,r)|Node::OrExpr(l,r)|Node::SubtractExpr(l,r) => vec![l.0,r.0],
+	    Node::ArrayAccessExpr(e,i) => vec![e.0,i.0],
+	    Node::BoolExpr(_) => vec![],
+	    Node::CallExpr(n,args) => {
+		let mut cs = vec![n.0];
+		cs.extend(args.iter().map(|a| a.0));
+		cs
+	    }
+	    Node::FieldAccessExpr(e,n) => vec![e.0,n.0],
+	    Node::IntExpr(_,_) => vec![],
+	    Node::NullExpr => vec![],
+	    Node::LenExpr(e) => vec![e.0],
+	    Node::NotExpr(e) => vec![e.0],
+	    Node::SizeofExpr(t) => vec![t.0],
+	    Node::VarExpr(n) => vec![n.0],
+	    // Types
+	    Node::ArrayType(t) => vec![t.0],
+	    Node::BoolType => vec![],
+	    Node::IntType(_,_) => vec![],
+	    Node::NullType => vec![],
+	    Node::RecordType(fs) => {
+		let mut cs = vec![];
+		for (t,n) in fs {
+		    cs.push(t.0);
+		    cs.push(n.0);
+		}
+		cs
+	    }
+	    Node::ReferenceType(t) => vec![t.0],
+	    Node::SizedArrayType(t,e) => vec![t.0,e.0],
+	    Node::InferredType => vec![],
+	    Node::VoidType => vec![]
+	}
+    }
+
+    /// Determine whether this node references no other nodes.
+    pub fn is_leaf(&self) -> bool {
+	self.children().is_empty()
+    }
+
+    /// Determine the broad category of this node (e.g. "statement"),
+    /// which is useful when reporting unexpected nodes.
+    pub fn kind_name(&self) -> &'static str {
+	match self {
+	    Node::Utf8(_) => "name",
+	    Node::Error => "error",
+	    n if Decl::is(n) => "declaration",
+	    n if Stmt::is(n) => "statement",
+	    n if Expr::is(n) => "expression",
+	    _ => "type"
+	}
+    }
+}
+
 // =============================================================================
 // Declarations
 // =============================================================================
 
-#[derive(Clone,Copy,Debug,PartialEq)]
+#[derive(Clone,Copy,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
 pub struct Decl { pub index: usize }
 
 /// Represents a parameter declaration in the source of a given method.
-#[derive(Clone,Debug,PartialEq)]
+/// Since every use of a name shares one node, the parameter also
+/// records a separate node for this occurrence of its name (its
+/// `site`), such that errors can be reported against it.
+#[derive(Clone,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
 pub struct Parameter {
     pub declared : Type,
-    pub name : Name
+    pub name : Name,
+    pub site : Name
+}
+
+/// Represents an annotation attached to a declaration, such as
+/// `@inline` or `@since(1)`.  Annotations have no meaning within the
+/// language itself, but provide metadata for external tools.
+#[derive(Clone,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Annotation {
+    pub name : Name,
+    pub args : Vec<Expr>
 }
 
 impl Decl {
@@ -68,18 +410,51 @@ impl Decl {
     /// Determine whether a given term is a declaration or not.
     pub fn is(t: &Node) -> bool {
         match t {
+	    Node::AnnotatedDecl(_,_) => true,
+	    Node::ConstDecl(_,_,_) => true,
 	    Node::MethodDecl(_,_,_,_) => true,
             Node::TypeDecl(_,_) => true,
             _ => false
         }
     }
+
+    /// Strip away any annotations from this declaration, returning
+    /// the underlying declaration.
+    pub fn unannotated(self, ast: &AbstractSyntaxTree) -> Decl {
+	match ast.get(self.index) {
+	    Node::AnnotatedDecl(_,d) => d.unannotated(ast),
+	    _ => self
+	}
+    }
+
+    /// Iterate all declarations amongst a given set of roots whose
+    /// (unannotated) node satisfies a given predicate.  For example,
+    /// `Decl::of_kind(ast,roots,|n| matches!(n,Node::ConstDecl(..)))`
+    /// yields all constant declarations.  Annotated declarations are
+    /// yielded without their annotations.
+    pub fn of_kind<'a,F>(ast: &'a AbstractSyntaxTree, roots: &'a [Decl], pred: F) -> impl Iterator<Item=Decl> + 'a
+    where F : Fn(&Node) -> bool + 'a {
+	roots.iter()
+	    .map(move |d| d.unannotated(ast))
+	    .filter(move |d| pred(ast.get(d.index)))
+    }
+
+    /// Iterate all method declarations amongst a given set of roots.
+    pub fn methods<'a>(ast: &'a AbstractSyntaxTree, roots: &'a [Decl]) -> impl Iterator<Item=Decl> + 'a {
+	Decl::of_kind(ast,roots,|n| matches!(n,Node::MethodDecl(..)))
+    }
+
+    /// Iterate all type declarations amongst a given set of roots.
+    pub fn types<'a>(ast: &'a AbstractSyntaxTree, roots: &'a [Decl]) -> impl Iterator<Item=Decl> + 'a {
+	Decl::of_kind(ast,roots,|n| matches!(n,Node::TypeDecl(..)))
+    }
 }
 
 // =============================================================================
 // Statements
 // =============================================================================
 
-#[derive(Clone,Copy,Debug,PartialEq)]
+#[derive(Clone,Copy,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
 pub struct Stmt(pub usize);
 
 impl Stmt {
@@ -95,9 +470,21 @@ impl Stmt {
     /// Determine whether a given term is a declaration or not.
     pub fn is(t: &Node) -> bool {
         match t {
+	    Node::Error => true,
 	    Node::AssertStmt(_) => true,
+	    Node::AssignStmt(_,_) => true,
 	    Node::BlockStmt(_) => true,
+	    Node::BreakStmt => true,
+	    Node::ContinueStmt => true,
+	    Node::IfStmt(_,_,_) => true,
+	    Node::PrintStmt(_) => true,
+	    Node::ReturnStmt(_) => true,
+	    Node::ForStmt(_,_,_,_) => true,
 	    Node::SkipStmt => true,
+	    Node::SwitchStmt(_,_) => true,
+	    Node::TypeAliasStmt(_,_) => true,
+	    Node::VarDeclStmt(_,_,_) => true,
+	    Node::WhileStmt(_,_) => true,
             _ => false
         }
     }
@@ -107,7 +494,7 @@ impl Stmt {
 // Expressions
 // =============================================================================
 
-#[derive(Clone,Copy,Debug,PartialEq)]
+#[derive(Clone,Copy,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
 pub struct Expr(pub usize);
 
 impl Expr {
@@ -123,22 +510,62 @@ impl Expr {
     /// Determine whether a given term is a declaration or not.
     pub fn is(t: &Node) -> bool {
         match t {
+	    Node::Error => true,
+	    Node::AddExpr(_,_) => true,
+	    Node::AndExpr(_,_) => true,
+	    Node::ArrayAccessExpr(_,_) => true,
 	    Node::BoolExpr(_) => true,
+	    Node::CallExpr(_,_) => true,
 	    Node::EqualsExpr(_,_) => true,
+	    Node::FieldAccessExpr(_,_) => true,
 	    Node::LessThanExpr(_,_) => true,
 	    Node::NotEqualsExpr(_,_) => true,
-	    Node::IntExpr(_) => true,
+	    Node::NotExpr(_) => true,
+	    Node::NullExpr => true,
+	    Node::OrExpr(_,_) => true,
+	    Node::IntExpr(_,_) => true,
+	    Node::LenExpr(_) => true,
+	    Node::SizeofExpr(_) => true,
+	    Node::SubtractExpr(_,_) => true,
 	    Node::VarExpr(_) => true,
             _ => false
         }
     }
+
+    /// Determine the value of an integer literal with a given sign
+    /// and magnitude, provided this fits within an `i128`.  Literals
+    /// in the upper half of `u128` (e.g. `u128::MAX`) do not.
+    pub fn int_value(negative: bool, magnitude: u128) -> Option<i128> {
+	if negative {
+	    0i128.checked_sub_unsigned(magnitude)
+	} else {
+	    i128::try_from(magnitude).ok()
+	}
+    }
+
+    /// Evaluate this expression at compile time, producing an integer
+    /// value.  This fails if the expression is not constructed purely
+    /// from integer literals (and `sizeof`).
+    pub fn as_constant(&self, ast: &AbstractSyntaxTree) -> Option<i64> {
+	match ast.get(self.0) {
+	    Node::IntExpr(n,m) => i64::try_from(Expr::int_value(*n,*m)?).ok(),
+	    Node::SizeofExpr(t) => i64::try_from(t.size_of(ast)?).ok(),
+	    Node::AddExpr(l,r) => {
+		l.as_constant(ast)?.checked_add(r.as_constant(ast)?)
+	    }
+	    Node::SubtractExpr(l,r) => {
+		l.as_constant(ast)?.checked_sub(r.as_constant(ast)?)
+	    }
+	    _ => None
+	}
+    }
 }
 
 // =============================================================================
 // Types
 // =============================================================================
 
-#[derive(Clone,Copy,Debug,PartialEq)]
+#[derive(Clone,Copy,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
 pub struct Type(pub usize);
 
 impl Type {
@@ -154,12 +581,17 @@ impl Type {
     /// Determine whether a given term is a type (or not).
     pub fn is(ast: &AbstractSyntaxTree, t: &Node) -> bool {
         match t {
+            Node::Error => true,
             Node::BoolType => true,
             Node::IntType(_,_) => true,
+            Node::InferredType => true,
             Node::NullType => true,
             Node::VoidType => true,
             Node::ArrayType(t) => Type::is(ast,ast.get(t.0)),
             Node::ReferenceType(t) => Type::is(ast,ast.get(t.0)),
+            Node::SizedArrayType(t,e) => {
+                Type::is(ast,ast.get(t.0)) && Expr::is(ast.get(e.0))
+            }
             Node::RecordType(fs) => {
                 for (t,_) in fs {
                     if !Type::is(ast,ast.get(t.0)) {
@@ -171,13 +603,88 @@ impl Type {
             _ => false
         }
     }
+
+    /// Determine the (inclusive) range of values of an integer type
+    /// with a given signedness and width.  For example, `u8` gives
+    /// `(0,255)` and `i8` gives `(-128,127)`.
+    pub fn int_range(signed: bool, width: u8) -> (i128,u128) {
+	let width = width.min(128) as u32;
+	if width == 0 {
+	    (0,0)
+	} else if signed {
+	    let max = (1u128 << (width - 1)) - 1;
+	    (-(max as i128) - 1,max)
+	} else {
+	    (0,u128::MAX >> (128 - width))
+	}
+    }
+
+    /// Determine whether two types have identical structure.  Nesting
+    /// matters, so an array of references (e.g. `(&i32)[]`) is never
+    /// equal to a reference to an array (e.g. `&(i32[])`).  Likewise,
+    /// records are equal only when they have the same fields in the
+    /// same order.
+    pub fn structurally_eq(ast: &AbstractSyntaxTree, a: Type, b: Type) -> bool {
+	match (ast.get(a.0),ast.get(b.0)) {
+	    // Primitives
+	    (Node::BoolType, Node::BoolType) => true,
+	    (Node::NullType, Node::NullType) => true,
+	    (Node::IntType(s1,w1), Node::IntType(s2,w2)) => s1 == s2 && w1 == w2,
+	    (Node::VoidType, Node::VoidType) => true,
+	    // Compounds depend on elements
+	    (Node::ArrayType(e1), Node::ArrayType(e2)) => {
+		Type::structurally_eq(ast,*e1,*e2)
+	    }
+	    (Node::SizedArrayType(e1,s1), Node::SizedArrayType(e2,s2)) => {
+		let n1 = s1.as_constant(ast);
+		n1.is_some() && n1 == s2.as_constant(ast) && Type::structurally_eq(ast,*e1,*e2)
+	    }
+	    (Node::ReferenceType(e1), Node::ReferenceType(e2)) => {
+		Type::structurally_eq(ast,*e1,*e2)
+	    }
+	    (Node::RecordType(fs1), Node::RecordType(fs2)) => {
+		fs1.len() == fs2.len() && fs1.iter().zip(fs2).all(|((t1,n1),(t2,n2))| {
+		    n1.as_str(ast) == n2.as_str(ast) && Type::structurally_eq(ast,*t1,*t2)
+		})
+	    }
+	    _ => false
+	}
+    }
+
+    /// Determine the size (in bytes) of this type.  Integers occupy
+    /// their declared width, whilst arrays and references are
+    /// represented as pointers (i.e. eight bytes).  Records are
+    /// packed, meaning their size is simply the sum of their fields
+    /// (i.e. no padding is inserted for alignment).  Sized arrays
+    /// hold their elements inline and, hence, their size is that of
+    /// their elements multiplied by their length.  There is no size
+    /// for something which is not a (sizeable) type, such as `var`,
+    /// or for an array whose length is not a valid constant.
+    pub fn size_of(&self, ast: &AbstractSyntaxTree) -> Option<u64> {
+	match ast.get(self.0) {
+	    Node::BoolType => Some(1),
+	    Node::IntType(_,w) => Some((*w as u64) / 8),
+	    Node::NullType => Some(8),
+	    Node::VoidType => Some(0),
+	    Node::ArrayType(_) => Some(8),
+	    Node::ReferenceType(_) => Some(8),
+	    Node::SizedArrayType(t,e) => {
+		let n = u64::try_from(e.as_constant(ast)?).ok()?;
+		t.size_of(ast)?.checked_mul(n)
+	    }
+	    Node::RecordType(fields) => {
+		fields.iter().try_fold(0u64,|n,(t,_)| n.checked_add(t.size_of(ast)?))
+	    }
+	    _ => None
+	}
+    }
 }
 
 // =============================================================================
 // Names
 // =============================================================================
 
-#[derive(Clone,Copy,Debug,Hash,PartialEq,Eq)]
+#[derive(Clone,Copy,Debug,Hash,PartialEq,Eq,Serialize,Deserialize)]
 pub struct Name(pub usize);
 
 impl Name {
@@ -188,32 +695,202 @@ impl Name {
         // Done
         Name(index)
     }
+
+    /// Resolve this name into the string it represents.
+    pub fn as_str<'a>(&self, ast: &'a AbstractSyntaxTree) -> &'a str {
+	match ast.get(self.0) {
+	    Node::Utf8(s) => s,
+	    _ => panic!("invalid name")
+	}
+    }
+
+    /// Get a key for this name which compares (and hashes) by the
+    /// string it represents, rather than by its index.  A parser gives
+    /// every use of a name the same node, but names parsed separately
+    /// (e.g. by different parsers into the same heap) do not share
+    /// nodes.  Keys allow such names to be compared regardless.
+    pub fn key<'a>(&self, ast: &'a AbstractSyntaxTree) -> NameKey<'a> {
+	NameKey{name: *self, text: self.as_str(ast)}
+    }
+}
+
+/// A name paired with the string it represents (see `Name::key`).
+/// Two keys are equal when their strings are, regardless of whether
+/// they refer to the same node.  Keys can also be looked up by plain
+/// string (e.g. `map.get("x")`).
+#[derive(Clone,Copy,Debug)]
+pub struct NameKey<'a> {
+    name: Name,
+    text: &'a str
+}
+
+impl<'a> NameKey<'a> {
+    /// Get the name node from which this key was created.
+    pub fn name(&self) -> Name {
+	self.name
+    }
+
+    /// Get the string this key represents.
+    pub fn as_str(&self) -> &'a str {
+	self.text
+    }
+}
+
+impl<'a> PartialEq for NameKey<'a> {
+    fn eq(&self, other: &Self) -> bool {
+	self.text == other.text
+    }
+}
+
+impl<'a> Eq for NameKey<'a> {}
+
+impl<'a> Hash for NameKey<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+	self.text.hash(state)
+    }
+}
+
+impl<'a> Borrow<str> for NameKey<'a> {
+    fn borrow(&self) -> &str {
+	self.text
+    }
+}
+
+// =============================================================================
+// Cloning
+// =============================================================================
+
+/// Deep copy the subtree rooted at a given node into fresh nodes on
+/// the heap, returning the index of the new root.  The copy shares no
+/// nodes with the original and, hence, can be rewritten without
+/// affecting it.  The only exception is names, which are shared
+/// since each name has exactly one node (see `Parser`).
+pub fn clone_subtree(ast: &mut AbstractSyntaxTree, root: usize) -> usize {
+    let node = ast.get(root).clone();
+    // Copy children first, so they appear before their parent
+    let node = match node {
+	Node::Utf8(_) => node,
+	Node::Error => node,
+	Node::AnnotatedDecl(anns,d) => {
+	    let anns = anns.into_iter().map(|a| Annotation{name: a.name,args: a.args.into_iter().map(|e| clone_expr(ast,e)).collect()}).collect();
+	    Node::AnnotatedDecl(anns,Decl{index: clone_subtree(ast,d.index)})
+	}
+	Node::ConstDecl(t,n,e) => {
+	    Node::ConstDecl(clone_type(ast,t),n,clone_expr(ast,e))
+	}
+	Node::TypeDecl(n,t) => {
+	    Node::TypeDecl(n,clone_type(ast,t))
+	}
+	Node::MethodDecl(n,t,ps,b) => {
+	    let t = clone_type(ast,t);
+	    let ps = ps.into_iter().map(|p| Parameter{declared: clone_type(ast,p.declared),name: p.name,site: p.site}).collect();
+	    let b = Stmt(clone_subtree(ast,b.0));
+	    Node::MethodDecl(n,t,ps,b)
+	}
+	Node::AssertStmt(e) => Node::AssertStmt(clone_expr(ast,e)),
+	Node::AssignStmt(l,r) => Node::AssignStmt(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::BlockStmt(ss) => {
+	    Node::BlockStmt(ss.into_iter().map(|s| Stmt(clone_subtree(ast,s.0))).collect())
+	}
+	Node::IfStmt(c,t,f) => {
+	    let c = clone_expr(ast,c);
+	    let t = Stmt(clone_subtree(ast,t.0));
+	    let f = f.map(|f| Stmt(clone_subtree(ast,f.0)));
+	    Node::IfStmt(c,t,f)
+	}
+	Node::PrintStmt(e) => Node::PrintStmt(clone_expr(ast,e)),
+	Node::ReturnStmt(e) => Node::ReturnStmt(e.map(|e| clone_expr(ast,e))),
+	Node::ForStmt(i,c,u,b) => {
+	    let i = Stmt(clone_subtree(ast,i.0));
+	    let c = clone_expr(ast,c);
+	    let u = Stmt(clone_subtree(ast,u.0));
+	    Node::ForStmt(i,c,u,Stmt(clone_subtree(ast,b.0)))
+	}
+	Node::BreakStmt|Node::ContinueStmt|Node::SkipStmt => node,
+	Node::SwitchStmt(e,cs) => {
+	    let e = clone_expr(ast,e);
+	    let cs = cs.into_iter().map(|(l,s)| {
+		let l = l.map(|l| clone_expr(ast,l));
+		(l,Stmt(clone_subtree(ast,s.0)))
+	    }).collect();
+	    Node::SwitchStmt(e,cs)
+	}
+	Node::TypeAliasStmt(n,t) => {
+	    Node::TypeAliasStmt(n,clone_type(ast,t))
+	}
+	Node::VarDeclStmt(t,n,e) => {
+	    Node::VarDeclStmt(clone_type(ast,t),n,clone_expr(ast,e))
+	}
+	Node::WhileStmt(c,b) => {
+	    let c = clone_expr(ast,c);
+	    Node::WhileStmt(c,Stmt(clone_subtree(ast,b.0)))
+	}
+	Node::AddExpr(l,r) => Node::AddExpr(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::AndExpr(l,r) => Node::AndExpr(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::ArrayAccessExpr(e,i) => Node::ArrayAccessExpr(clone_expr(ast,e),clone_expr(ast,i)),
+	Node::BoolExpr(_) => node,
+	Node::CallExpr(n,args) => {
+	    Node::CallExpr(n,args.into_iter().map(|a| clone_expr(ast,a)).collect())
+	}
+	Node::EqualsExpr(l,r) => Node::EqualsExpr(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::FieldAccessExpr(e,n) => Node::FieldAccessExpr(clone_expr(ast,e),n),
+	Node::NotEqualsExpr(l,r) => Node::NotEqualsExpr(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::LessThanExpr(l,r) => Node::LessThanExpr(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::IntExpr(_,_) => node,
+	Node::NullExpr => node,
+	Node::OrExpr(l,r) => Node::OrExpr(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::LenExpr(e) => Node::LenExpr(clone_expr(ast,e)),
+	Node::NotExpr(e) => Node::NotExpr(clone_expr(ast,e)),
+	Node::SizeofExpr(t) => Node::SizeofExpr(clone_type(ast,t)),
+	Node::SubtractExpr(l,r) => Node::SubtractExpr(clone_expr(ast,l),clone_expr(ast,r)),
+	Node::VarExpr(n) => Node::VarExpr(n),
+	Node::ArrayType(t) => Node::ArrayType(clone_type(ast,t)),
+	Node::BoolType => node,
+	Node::IntType(_,_) => node,
+	Node::NullType => node,
+	Node::RecordType(fs) => {
+	    Node::RecordType(fs.into_iter().map(|(t,n)| (clone_type(ast,t),n)).collect())
+	}
+	Node::ReferenceType(t) => Node::ReferenceType(clone_type(ast,t)),
+	Node::SizedArrayType(t,e) => Node::SizedArrayType(clone_type(ast,t),clone_expr(ast,e)),
+	Node::InferredType => node,
+	Node::VoidType => node
+    };
+    ast.push(node).raw_index()
+}
+
+fn clone_type(ast: &mut AbstractSyntaxTree, t: Type) -> Type {
+    Type(clone_subtree(ast,t.0))
+}
+
+fn clone_expr(ast: &mut AbstractSyntaxTree, e: Expr) -> Expr {
+    Expr(clone_subtree(ast,e.0))
 }
 
 // =============================================================================
 // Conversions
 // =============================================================================
 
-impl From<Ref<'_,Node>> for Decl {
-    fn from(r: Ref<'_,Node>) -> Decl {
+impl From<AstRef<'_>> for Decl {
+    fn from(r: AstRef<'_>) -> Decl {
 	Decl{index:r.raw_index()}
     }
 }
 
-impl From<Ref<'_,Node>> for Stmt {
-    fn from(r: Ref<'_,Node>) -> Stmt {
+impl From<AstRef<'_>> for Stmt {
+    fn from(r: AstRef<'_>) -> Stmt {
 	Stmt(r.raw_index())
     }
 }
 
-impl From<Ref<'_,Node>> for Type {
-    fn from(r: Ref<'_,Node>) -> Type {
+impl From<AstRef<'_>> for Type {
+    fn from(r: AstRef<'_>) -> Type {
 	Type(r.raw_index())
     }
 }
 
-impl From<Ref<'_,Node>> for Name {
-    fn from(r: Ref<'_,Node>) -> Name {
+impl From<AstRef<'_>> for Name {
+    fn from(r: AstRef<'_>) -> Name {
 	Name(r.raw_index())
     }
 }
@@ -0,0 +1,92 @@
+use crate::ast::*;
+
+// =================================================================
+// Desugaring
+// =================================================================
+
+/// Lower a given declaration into the core language, returning the
+/// lowered declaration.  Currently, this only rewrites `for` loops,
+/// since compound assignments (e.g. `x++`) are already lowered by the
+/// parser and `else if` is simply a nested `if`.  For example, `for
+/// (i32 i=0; i<n; i++) { ... }` becomes:
+///
+/// ```text
+/// {
+///     i32 i = 0;
+///     while i < n {
+///         ...
+///         i = i + 1;
+///     }
+/// }
+/// ```
+///
+/// Since the tree is append-only, the lowered declaration is added as
+/// new nodes.  However, expressions, types and simple statements are
+/// shared with the original declaration.
+pub fn desugar(ast: &mut AbstractSyntaxTree, d: Decl) -> Decl {
+    match ast.get(d.index).clone() {
+	Node::AnnotatedDecl(anns,inner) => {
+	    let inner = desugar(ast,inner);
+	    Decl::new(ast,Node::AnnotatedDecl(anns,inner))
+	}
+	Node::MethodDecl(name,ret,params,body) => {
+	    let body = desugar_stmt(ast,body);
+	    Decl::new(ast,Node::MethodDecl(name,ret,params,body))
+	}
+	_ => d
+    }
+}
+
+fn desugar_stmt(ast: &mut AbstractSyntaxTree, s: Stmt) -> Stmt {
+    match ast.get(s.0).clone() {
+	Node::BlockStmt(stmts) => {
+	    let stmts = stmts.into_iter().map(|s| desugar_stmt(ast,s)).collect();
+	    Stmt::new(ast,Node::BlockStmt(stmts))
+	}
+	Node::ForStmt(init,cond,update,body) if !has_continue(ast,body) => {
+	    let init = desugar_stmt(ast,init);
+	    let update = desugar_stmt(ast,update);
+	    // Update is executed at the end of each iteration
+	    let stmts = match ast.get(body.0).clone() {
+		Node::BlockStmt(stmts) => stmts,
+		_ => vec![body]
+	    };
+	    let mut stmts : Vec<Stmt> = stmts.into_iter().map(|s| desugar_stmt(ast,s)).collect();
+	    stmts.push(update);
+	    let body = Stmt::new(ast,Node::BlockStmt(stmts));
+	    let stmt = Stmt::new(ast,Node::WhileStmt(cond,body));
+	    // Block ensures init is only in scope for the loop
+	    Stmt::new(ast,Node::BlockStmt(vec![init,stmt]))
+	}
+	Node::IfStmt(cond,tt,ff) => {
+	    let tt = desugar_stmt(ast,tt);
+	    let ff = ff.map(|ff| desugar_stmt(ast,ff));
+	    Stmt::new(ast,Node::IfStmt(cond,tt,ff))
+	}
+	Node::SwitchStmt(expr,cases) => {
+	    let cases = cases.into_iter().map(|(l,b)| (l,desugar_stmt(ast,b))).collect();
+	    Stmt::new(ast,Node::SwitchStmt(expr,cases))
+	}
+	Node::WhileStmt(cond,body) => {
+	    let body = desugar_stmt(ast,body);
+	    Stmt::new(ast,Node::WhileStmt(cond,body))
+	}
+	_ => s
+    }
+}
+
+/// Determine whether a given loop body contains a `continue` which
+/// applies to that loop (i.e. which is not within a nested loop).
+/// Such `for` loops are not lowered, since the `continue` would skip
+/// the update placed at the end of the body.
+fn has_continue(ast: &AbstractSyntaxTree, s: Stmt) -> bool {
+    match ast.get(s.0) {
+	Node::ContinueStmt => true,
+	Node::BlockStmt(stmts) => stmts.iter().any(|s| has_continue(ast,*s)),
+	Node::IfStmt(_,tt,ff) => {
+	    has_continue(ast,*tt) || ff.is_some_and(|ff| has_continue(ast,ff))
+	}
+	Node::SwitchStmt(_,cases) => cases.iter().any(|(_,b)| has_continue(ast,*b)),
+	_ => false
+    }
+}
@@ -1,13 +1,171 @@
 use std::fmt;
+use crate::ast::Type;
+use crate::parser;
+use crate::source_map::{Highlight,DEFAULT_TAB_WIDTH};
 
 /// Identifiers a particular kind of syntax error.
 #[derive(Clone,Debug)]
 pub enum ErrorCode {
     InternalFailure(String),
-    /// Expected lhs type, got rhs type
-    ExpectedSubtype,
+    /// Expected one type, found another (both given in source form)
+    ExpectedSubtype { expected: String, found: String },
     /// Access unknown variable
-    VariableNotFound
+    VariableNotFound,
+    /// Call to unknown method
+    MethodNotFound,
+    /// Call with the wrong number of arguments
+    ArityMismatch { expected: usize, actual: usize },
+    /// Call to overloaded method where no overload accepts the given
+    /// number of arguments
+    NoMatchingMethod { actual: usize },
+    /// Call to overloaded method where several overloads match
+    AmbiguousCall,
+    /// Type is structurally malformed (e.g. an array of void)
+    InvalidType { reason: String },
+    /// Expression is not a compile-time constant
+    ExpectedConstant,
+    /// Name already declared by an earlier declaration
+    DuplicateDeclaration,
+    /// Parameter has the same name as an earlier parameter
+    DuplicateParameter,
+    /// Access unknown field of a record
+    FieldNotFound,
+    /// Case label already used by an earlier case
+    DuplicateCase,
+    /// Switch does not cover every possible value
+    NonExhaustiveSwitch,
+    /// Statement can never be executed
+    UnreachableCode,
+    /// Method can reach its end without returning a value
+    MissingReturn,
+    /// Local variable has the same name as an enclosing variable
+    ShadowedVariable,
+    /// Checking stopped early because too many errors were found
+    TooManyErrors,
+    /// Break statement which is not within a loop
+    BreakOutsideLoop,
+    /// Continue statement which is not within a loop
+    ContinueOutsideLoop,
+    /// Integer literal (given by its sign and magnitude) which does
+    /// not fit in the integer type (given by its signedness and
+    /// width) expected of it
+    LiteralOutOfRange { negative: bool, magnitude: u128, signed: bool, width: u8 }
+}
+
+/// Identifies how serious a given error is.  Errors prevent a program
+/// from being accepted, whilst warnings only flag code which is legal
+/// but likely to be a mistake.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+impl ErrorCode {
+    /// Determine the severity of this error code.
+    pub fn severity(&self) -> Severity {
+	match self {
+	    ErrorCode::ShadowedVariable => Severity::Warning,
+	    _ => Severity::Error
+	}
+    }
+
+    /// Determine the unique code identifying this kind of error (e.g.
+    /// `E0002`).  This can be given to `--explain` for a longer
+    /// description of the error.
+    pub fn code(&self) -> &'static str {
+	match self {
+	    ErrorCode::InternalFailure(_) => "E0001",
+	    ErrorCode::ExpectedSubtype{..} => "E0002",
+	    ErrorCode::VariableNotFound => "E0003",
+	    ErrorCode::MethodNotFound => "E0004",
+	    ErrorCode::ArityMismatch{..} => "E0005",
+	    ErrorCode::NoMatchingMethod{..} => "E0006",
+	    ErrorCode::AmbiguousCall => "E0007",
+	    ErrorCode::InvalidType{..} => "E0008",
+	    ErrorCode::ExpectedConstant => "E0009",
+	    ErrorCode::DuplicateDeclaration => "E0010",
+	    ErrorCode::DuplicateParameter => "E0011",
+	    ErrorCode::FieldNotFound => "E0012",
+	    ErrorCode::DuplicateCase => "E0013",
+	    ErrorCode::NonExhaustiveSwitch => "E0014",
+	    ErrorCode::UnreachableCode => "E0015",
+	    ErrorCode::MissingReturn => "E0016",
+	    ErrorCode::ShadowedVariable => "E0017",
+	    ErrorCode::TooManyErrors => "E0018",
+	    ErrorCode::BreakOutsideLoop => "E0019",
+	    ErrorCode::ContinueOutsideLoop => "E0020",
+	    ErrorCode::LiteralOutOfRange{..} => "E0021"
+	}
+    }
+}
+
+/// Longer descriptions of each error code, along with a minimal
+/// example of the error.  These are printed by `--explain`.
+static EXPLANATIONS : &[(&str,&str)] = &[
+    ("E0001", "The compiler failed internally.  This is a bug in the compiler,\n\
+               rather than in the program being compiled."),
+    ("E0002", "An expression does not have the type required by its context.\n\n\
+               \x20   i32 f() { return true; }\n\n\
+               Here, `true` has type `bool` but `f` must return an `i32`."),
+    ("E0003", "A variable is used which has not been declared in scope.\n\n\
+               \x20   i32 f() { return x; }"),
+    ("E0004", "A method is called which has not been declared.\n\n\
+               \x20   i32 f() { return g(1); }"),
+    ("E0005", "A method is called with the wrong number of arguments.\n\n\
+               \x20   i32 f(i32 x) { return x; }\n\
+               \x20   i32 g() { return f(); }"),
+    ("E0006", "An overloaded method is called with a number of arguments which\n\
+               none of its declarations accept.\n\n\
+               \x20   i32 f() { return 0; }\n\
+               \x20   i32 f(i32 x) { return x; }\n\
+               \x20   i32 g() { return f(1,2); }"),
+    ("E0007", "A method call matches more than one declaration of an overloaded\n\
+               method and, hence, it is unclear which should be called."),
+    ("E0008", "A type is malformed, such as an array whose elements are void.\n\n\
+               \x20   type t = void[];"),
+    ("E0009", "An expression which must be known at compile time (e.g. the size\n\
+               of an array type) is not a constant.\n\n\
+               \x20   void f(i32 n) { type t = i32[n]; }"),
+    ("E0010", "Two declarations share the same name.  Types, methods and\n\
+               constants share a single namespace, though methods with\n\
+               different numbers of parameters may share a name.\n\n\
+               \x20   type t = i32;\n\
+               \x20   type t = bool;"),
+    ("E0011", "Two parameters of a method share the same name.\n\n\
+               \x20   void f(i32 x, bool x) {}"),
+    ("E0012", "A record is accessed with a field it does not have.\n\n\
+               \x20   i32 f({i32 x} r) { return r.y; }"),
+    ("E0013", "Two cases of a switch share the same label.\n\n\
+               \x20   switch x { case 1: { skip; } case 1: { skip; } }"),
+    ("E0014", "A switch does not cover every possible value of its operand.\n\
+               Add the missing cases, or a `default` case."),
+    ("E0015", "A statement can never be executed, since every path to it has\n\
+               already returned.\n\n\
+               \x20   i32 f() { return 1; skip; }"),
+    ("E0016", "A method which returns a value can reach the end of its body\n\
+               without returning.\n\n\
+               \x20   i32 f(bool b) { if b { return 1; } }"),
+    ("E0017", "A local variable has the same name as a variable in an enclosing\n\
+               scope, which hides the outer variable.  This is a warning.\n\n\
+               \x20   void f(i32 x) { { i32 x = 1; } }"),
+    ("E0018", "Checking stopped early since too many errors were found.  Fix the\n\
+               errors reported so far, and then check again."),
+    ("E0019", "A `break` statement is used outside of any loop and, hence, there\n\
+               is no loop for it to exit.\n\n\
+               \x20   void f(bool b) { if b { break; } }"),
+    ("E0020", "A `continue` statement is used outside of any loop and, hence,\n\
+               there is no loop for it to continue.\n\n\
+               \x20   void f() { continue; }"),
+    ("E0021", "An integer literal is given where a value of some integer type is\n\
+               expected, but lies outside the range of that type.\n\n\
+               \x20   void f() { u8 x = 256; }")
+];
+
+/// Determine the longer description of a given error code (e.g.
+/// `E0002`), or `None` if there is no such code.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter().find(|(c,_)| *c == code).map(|(_,text)| *text)
 }
 
 /// Identifies some form of syntax error on a given Abstract Syntax
@@ -28,10 +186,12 @@ pub fn internal_failure(node: usize, msg: &str) -> SyntaxError {
 }
 
 /// Construct a syntax error representing a subtype error of some kind
-/// (e.g. expected int, found bool).
+/// (e.g. expected int, found bool).  The expected and found types are
+/// given in source form.
 #[allow(dead_code)]
-pub fn expected_subtype(node: usize) -> SyntaxError {
-    SyntaxError{node, errno: ErrorCode::ExpectedSubtype}
+pub fn expected_subtype(node: usize, expected: &str, found: &str) -> SyntaxError {
+    let errno = ErrorCode::ExpectedSubtype{expected: expected.to_string(), found: found.to_string()};
+    SyntaxError{node, errno}
 }
 
 /// Construct a syntax error representing a variable not found error.
@@ -40,9 +200,296 @@ pub fn variable_not_found(node: usize) -> SyntaxError {
    SyntaxError{node, errno: ErrorCode::VariableNotFound}
 }
 
+/// Construct a syntax error representing a call to a method which
+/// does not exist.
+#[allow(dead_code)]
+pub fn method_not_found(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::MethodNotFound}
+}
+
+/// Construct a syntax error representing a call with a different
+/// number of arguments from the number of parameters declared.
+#[allow(dead_code)]
+pub fn arity_mismatch(node: usize, expected: usize, actual: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::ArityMismatch{expected,actual}}
+}
+
+/// Construct a syntax error representing a call to an overloaded
+/// method, where no overload accepts the given number of arguments.
+#[allow(dead_code)]
+pub fn no_matching_method(node: usize, actual: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::NoMatchingMethod{actual}}
+}
+
+/// Construct a syntax error representing a call to an overloaded
+/// method, where more than one overload matches.
+#[allow(dead_code)]
+pub fn ambiguous_call(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::AmbiguousCall}
+}
+
+/// Construct a syntax error representing a malformed type, such as
+/// an array of `void`.
+#[allow(dead_code)]
+pub fn invalid_type(node: usize, reason: &str) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::InvalidType{reason: reason.to_string()}}
+}
+
+/// Construct a syntax error representing an expression which was
+/// required to be a compile-time constant, but is not.
+#[allow(dead_code)]
+pub fn expected_constant(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::ExpectedConstant}
+}
+
+/// Construct a syntax error representing a declaration whose name
+/// was already used by an earlier declaration.
+#[allow(dead_code)]
+pub fn duplicate_declaration(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::DuplicateDeclaration}
+}
+
+/// Construct a syntax error representing a parameter whose name was
+/// already used by an earlier parameter of the same method.
+#[allow(dead_code)]
+pub fn duplicate_parameter(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::DuplicateParameter}
+}
+
+/// Construct a syntax error representing an access to a field which
+/// does not exist in the given record type.
+#[allow(dead_code)]
+pub fn field_not_found(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::FieldNotFound}
+}
+
+/// Construct a syntax error representing a case label which was
+/// already used by an earlier case of the same switch.
+#[allow(dead_code)]
+pub fn duplicate_case(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::DuplicateCase}
+}
+
+/// Construct a syntax error representing a switch statement which
+/// does not cover every possible value of its discriminant.
+#[allow(dead_code)]
+pub fn non_exhaustive_switch(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::NonExhaustiveSwitch}
+}
+
+/// Construct a syntax error representing a statement which can never
+/// be executed (e.g. because it follows a `return`).
+#[allow(dead_code)]
+pub fn unreachable_code(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::UnreachableCode}
+}
+
+/// Construct a syntax error representing a non-void method with some
+/// path through its body which does not return a value.
+#[allow(dead_code)]
+pub fn missing_return(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::MissingReturn}
+}
+
+/// Construct a warning representing a local variable declaration
+/// which shadows an enclosing parameter or local variable.
+#[allow(dead_code)]
+pub fn shadowed_variable(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::ShadowedVariable}
+}
+
+/// Construct a note indicating that checking stopped at a given node
+/// because the maximum number of errors was reached.
+#[allow(dead_code)]
+pub fn too_many_errors(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::TooManyErrors}
+}
+
+/// Construct a syntax error representing a `break` statement which
+/// is not enclosed by any loop.
+#[allow(dead_code)]
+pub fn break_outside_loop(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::BreakOutsideLoop}
+}
+
+/// Construct a syntax error representing a `continue` statement
+/// which is not enclosed by any loop.
+#[allow(dead_code)]
+pub fn continue_outside_loop(node: usize) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::ContinueOutsideLoop}
+}
+
+/// Construct a syntax error representing an integer literal which
+/// lies outside the (inclusive) range of the type expected of it.
+#[allow(dead_code)]
+pub fn literal_out_of_range(node: usize, negative: bool, magnitude: u128, signed: bool, width: u8) -> SyntaxError {
+   SyntaxError{node, errno: ErrorCode::LiteralOutOfRange{negative,magnitude,signed,width}}
+}
+
 /// Simple mechanism for printing an error code
 impl fmt::Display for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-	write!(f,"expected a type, found another type")
+	match self {
+	    ErrorCode::InternalFailure(msg) => write!(f,"internal failure ({})",msg),
+	    ErrorCode::ExpectedSubtype{expected,found} => write!(f,"expected {}, found {}",expected,found),
+	    ErrorCode::VariableNotFound => write!(f,"variable not found"),
+	    ErrorCode::MethodNotFound => write!(f,"method not found"),
+	    ErrorCode::ArityMismatch{expected,actual} => {
+		let plural = if *expected == 1 { "" } else { "s" };
+		write!(f,"expected {} argument{}, found {}",expected,plural,actual)
+	    }
+	    ErrorCode::NoMatchingMethod{actual} => {
+		let plural = if *actual == 1 { "" } else { "s" };
+		write!(f,"no method accepts {} argument{}",actual,plural)
+	    }
+	    ErrorCode::AmbiguousCall => write!(f,"ambiguous method call"),
+	    ErrorCode::InvalidType{reason} => write!(f,"invalid type ({})",reason),
+	    ErrorCode::ExpectedConstant => write!(f,"expected a constant expression"),
+	    ErrorCode::DuplicateDeclaration => write!(f,"name already declared"),
+	    ErrorCode::DuplicateParameter => write!(f,"parameter already declared"),
+	    ErrorCode::FieldNotFound => write!(f,"field not found"),
+	    ErrorCode::DuplicateCase => write!(f,"duplicate case label"),
+	    ErrorCode::NonExhaustiveSwitch => write!(f,"switch is not exhaustive"),
+	    ErrorCode::UnreachableCode => write!(f,"unreachable code"),
+	    ErrorCode::MissingReturn => write!(f,"missing return statement"),
+	    ErrorCode::ShadowedVariable => write!(f,"variable shadows an enclosing variable"),
+	    ErrorCode::TooManyErrors => write!(f,"too many errors; stopping"),
+	    ErrorCode::BreakOutsideLoop => write!(f,"break outside of loop"),
+	    ErrorCode::ContinueOutsideLoop => write!(f,"continue outside of loop"),
+	    ErrorCode::LiteralOutOfRange{negative,magnitude,signed,width} => {
+		let (min,max) = Type::int_range(*signed,*width);
+		let sign = if *negative { "-" } else { "" };
+		let prefix = if *signed { "i" } else { "u" };
+		write!(f,"{}{} is out of range for {}{} ({}..={})",sign,magnitude,prefix,width,min,max)
+	    }
+	}
+    }
+}
+
+/// Errors are printed as their severity and code followed by the
+/// message (e.g. `error[E0003]: variable not found`).  The node is
+/// omitted, since it is only meaningful with the source at hand.
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	let severity = match self.errno.severity() {
+	    Severity::Error => "error",
+	    Severity::Warning => "warning"
+	};
+	write!(f,"{}[{}]: {}",severity,self.errno.code(),self.errno)
     }
 }
+
+impl std::error::Error for SyntaxError {}
+
+// =================================================================
+// Compile Errors
+// =================================================================
+
+/// Identifies an error arising from any stage of compilation.  That
+/// is, either the source failed to parse, or it failed to type check.
+#[derive(Debug)]
+pub enum CompileError {
+    Parse(parser::Error),
+    Syntax(SyntaxError)
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    CompileError::Parse(err) => fmt::Display::fmt(err,f),
+	    CompileError::Syntax(err) => fmt::Display::fmt(err,f)
+	}
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    CompileError::Parse(err) => Some(err),
+	    CompileError::Syntax(err) => Some(err)
+	}
+    }
+}
+
+impl From<parser::Error> for CompileError {
+    fn from(err: parser::Error) -> Self {
+	CompileError::Parse(err)
+    }
+}
+
+impl From<SyntaxError> for CompileError {
+    fn from(err: SyntaxError) -> Self {
+	CompileError::Syntax(err)
+    }
+}
+
+// =================================================================
+// Diagnostics
+// =================================================================
+
+/// A message attached to a region of the input, which can be rendered
+/// for display to the user.  This is independent of where the message
+/// arose (e.g. parsing) and of how it is ultimately displayed.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Diagnostic {
+    /// Offset of the first character of the region.
+    pub start: usize,
+    /// Offset immediately following the last character of the region.
+    pub end: usize,
+    /// Describes the problem.
+    pub message: String
+}
+
+impl Diagnostic {
+    pub fn new(start: usize, end: usize, message: &str) -> Self {
+	Diagnostic{start,end,message: message.to_string()}
+    }
+
+    /// Render this diagnostic against the input it refers to.  This
+    /// gives the message, followed by the line enclosing the start of
+    /// the region with that region underlined.
+    pub fn render(&self, input: &str) -> String {
+	let start = self.start.min(input.len());
+	// Determine enclosing line
+	let line_start = input[..start].rfind('\n').map_or(0,|i| i + 1);
+	let line_end = input[start..].find('\n').map_or(input.len(),|i| start + i);
+	let line = &input[line_start..line_end];
+	// Determine region within line (which is never empty, so that
+	// errors at the end of the input are still visible).
+	let end = self.end.min(line_end).max(start + 1);
+	let hl = Highlight{line, start: start - line_start, end: end - line_start};
+	format!("error: {}\n{}",self.message,hl.render(DEFAULT_TAB_WIDTH))
+    }
+}
+
+impl From<&parser::Error> for Diagnostic {
+    fn from(err: &parser::Error) -> Self {
+	Diagnostic::new(err.start,err.end,&err.message)
+    }
+}
+
+// ======================================================
+// Tests
+// ======================================================
+
+#[test]
+fn test_01() {
+    let d = Diagnostic::new(9,10,"unknown token encountered");
+    assert_eq!(d.render("type t = ;"),"error: unknown token encountered\ntype t = ;\n         ^\n");
+}
+
+#[test]
+fn test_02() {
+    let input = "void f() {\n  skip\n}";
+    let d = Diagnostic::new(18,19,"expected one thing, found another");
+    assert_eq!(d.render(input),"error: expected one thing, found another\n}\n^\n");
+}
+
+#[test]
+fn test_03() {
+    let input = "type t = i32";
+    let mut ast = crate::ast::AbstractSyntaxTree::new();
+    let err = parser::Parser::new(input,&mut ast,|_,_| {}).parse_decl().err().unwrap();
+    let d = Diagnostic::from(&err);
+    assert_eq!(d.render(input),"error: expected one thing, found another\ntype t = i32\n            ^\n");
+}
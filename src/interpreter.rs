@@ -0,0 +1,396 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::result;
+use std::collections::HashMap;
+use crate::ast::*;
+use crate::source_map::SourceMap;
+use crate::source_map::DEFAULT_TAB_WIDTH;
+
+// =================================================================
+// Error
+// =================================================================
+
+/// Identifies possible errors arising during execution.  Every error
+/// is reported against the AST node responsible for it.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Error {
+    /// Identifies an AST node in the source file.
+    pub node: usize,
+    /// Describes what went wrong.
+    pub message: &'static str
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    pub fn new(node: usize, message: &'static str) -> Error {
+	Error{node,message}
+    }
+
+    /// Produce a human-readable report for this error, including the
+    /// line on which it arose and the offending region within it.
+    pub fn report(&self, map: &SourceMap) -> String {
+	let hl = map.get_highlight(self.node);
+	format!("error: {}\n{}",self.message,hl.render(DEFAULT_TAB_WIDTH))
+    }
+}
+
+// =================================================================
+// Values
+// =================================================================
+
+/// Represents a value which can be produced by evaluating an
+/// expression.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i128),
+    Null
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    Value::Bool(b) => write!(f,"{}",b),
+	    Value::Int(i) => write!(f,"{}",i),
+	    Value::Null => write!(f,"null")
+	}
+    }
+}
+
+/// Identifies how the execution of a statement completed.  Either
+/// execution continues with the next statement, the enclosing loop
+/// is exited (or continued), or the enclosing method returns (with an
+/// optional value).
+#[derive(Clone,Copy,Debug)]
+pub enum Outcome {
+    Next,
+    Break,
+    Continue,
+    Return(Option<Value>)
+}
+
+/// Maps variable names to their current values.  As for the type
+/// checker, variables are keyed by name directly.
+pub type Frame = HashMap<Name, Value>;
+
+// =================================================================
+// Interpreter
+// =================================================================
+
+/// Responsible for executing statements and evaluating expressions
+/// within a given AST.  This assumes the AST has already been type
+/// checked.
+pub struct Interpreter<'a> {
+    ast: &'a AbstractSyntaxTree,
+    /// Destination for values output by `print` statements.
+    out: RefCell<Box<dyn Write + 'a>>
+}
+
+impl<'a> Interpreter<'a> {
+
+    pub fn new(ast: &'a AbstractSyntaxTree) -> Self {
+	let out : RefCell<Box<dyn Write + 'a>> = RefCell::new(Box::new(io::stdout()));
+	Interpreter{ast,out}
+    }
+
+    /// Set the destination for values output by `print` statements,
+    /// which is `stdout` by default.
+    pub fn with_output<W: Write + 'a>(mut self, out: W) -> Self {
+	self.out = RefCell::new(Box::new(out));
+	self
+    }
+
+    // Declarations
+    // -----------------------------------------------------------------
+
+    /// Invoke a given method declaration with a given set of
+    /// arguments, producing its return value (if any).
+    pub fn invoke(&self, d: Decl, args: &[Value]) -> Result<Option<Value>> {
+	match self.ast.get(d.index) {
+	    Node::AnnotatedDecl(_,d) => self.invoke(*d,args),
+	    Node::MethodDecl(_,_,params,body) => {
+		if params.len() != args.len() {
+		    return Err(Error::new(d.index,"incorrect number of arguments"));
+		}
+		let mut frame = Frame::new();
+		// Allocate parameters into frame
+		for (p,v) in params.iter().zip(args) {
+		    frame.insert(p.name,*v);
+		}
+		match self.execute(&mut frame,*body)? {
+		    Outcome::Return(v) => Ok(v),
+		    Outcome::Next => Ok(None),
+		    Outcome::Break|Outcome::Continue => {
+			Err(Error::new(d.index,"loop control outside of loop"))
+		    }
+		}
+	    }
+	    _ => Err(Error::new(d.index,"expected method"))
+	}
+    }
+
+    /// Find the method declaration with a given name and number of
+    /// parameters.  Since the interpreter is not given the program's
+    /// declarations, this searches the entire tree.
+    fn lookup_method(&self, name: Name, arity: usize) -> Option<Decl> {
+	(0..self.ast.len()).find(|i| {
+	    matches!(self.ast.get(*i),Node::MethodDecl(n,_,ps,_) if *n == name && ps.len() == arity)
+	}).map(|index| Decl{index})
+    }
+
+    // Statements
+    // -----------------------------------------------------------------
+
+    pub fn execute(&self, frame: &mut Frame, stmt: Stmt) -> Result<Outcome> {
+	match self.ast.get(stmt.0) {
+	    Node::AssertStmt(cond) => {
+		self.execute_assert(frame,*cond)
+	    }
+	    Node::AssignStmt(lhs,rhs) => {
+		self.execute_assign(frame,*lhs,*rhs)
+	    }
+	    Node::BlockStmt(stmts) => {
+		self.execute_block(frame,stmts)
+	    }
+	    Node::BreakStmt => Ok(Outcome::Break),
+	    Node::ContinueStmt => Ok(Outcome::Continue),
+	    Node::ForStmt(init,cond,update,body) => {
+		self.execute_for(frame,*init,*cond,*update,*body)
+	    }
+	    Node::IfStmt(cond,tt,ff) => {
+		self.execute_if(frame,*cond,*tt,*ff)
+	    }
+	    Node::PrintStmt(expr) => {
+		self.execute_print(frame,stmt,*expr)
+	    }
+	    Node::ReturnStmt(expr) => {
+		self.execute_return(frame,*expr)
+	    }
+	    Node::SkipStmt => Ok(Outcome::Next),
+	    Node::SwitchStmt(expr,cases) => {
+		self.execute_switch(frame,*expr,cases)
+	    }
+	    Node::TypeAliasStmt(_,_) => Ok(Outcome::Next),
+	    Node::VarDeclStmt(_,name,init) => {
+		let v = self.evaluate(frame,*init)?;
+		frame.insert(*name,v);
+		Ok(Outcome::Next)
+	    }
+	    Node::WhileStmt(cond,body) => {
+		self.execute_while(frame,*cond,*body)
+	    }
+	    _ => Err(Error::new(stmt.0,"unknown statement"))
+	}
+    }
+
+    /// Execute an assertion.  If the condition does not hold, then an
+    /// error is reported against the condition itself.
+    pub fn execute_assert(&self, frame: &mut Frame, cond: Expr) -> Result<Outcome> {
+	match self.evaluate(frame,cond)? {
+	    Value::Bool(true) => Ok(Outcome::Next),
+	    _ => Err(Error::new(cond.0,"assertion failed"))
+	}
+    }
+
+    pub fn execute_assign(&self, frame: &mut Frame, lhs: Expr, rhs: Expr) -> Result<Outcome> {
+	let v = self.evaluate(frame,rhs)?;
+	//
+	match self.ast.get(lhs.0) {
+	    Node::VarExpr(name) => {
+		frame.insert(*name,v);
+		Ok(Outcome::Next)
+	    }
+	    _ => Err(Error::new(lhs.0,"invalid lval"))
+	}
+    }
+
+    pub fn execute_block(&self, frame: &mut Frame, stmts: &[Stmt]) -> Result<Outcome> {
+	for stmt in stmts {
+	    match self.execute(frame,*stmt)? {
+		Outcome::Next => {}
+		r => { return Ok(r); }
+	    }
+	}
+	Ok(Outcome::Next)
+    }
+
+    pub fn execute_for(&self, frame: &mut Frame, init: Stmt, cond: Expr, update: Stmt, body: Stmt) -> Result<Outcome> {
+	self.execute(frame,init)?;
+	//
+	while self.evaluate_bool(frame,cond)? {
+	    match self.execute(frame,body)? {
+		Outcome::Next|Outcome::Continue => {}
+		Outcome::Break => { break; }
+		r => { return Ok(r); }
+	    }
+	    self.execute(frame,update)?;
+	}
+	Ok(Outcome::Next)
+    }
+
+    pub fn execute_if(&self, frame: &mut Frame, cond: Expr, tt: Stmt, ff: Option<Stmt>) -> Result<Outcome> {
+	if self.evaluate_bool(frame,cond)? {
+	    self.execute(frame,tt)
+	} else if let Some(ff) = ff {
+	    self.execute(frame,ff)
+	} else {
+	    Ok(Outcome::Next)
+	}
+    }
+
+    pub fn execute_print(&self, frame: &mut Frame, stmt: Stmt, expr: Expr) -> Result<Outcome> {
+	let v = self.evaluate(frame,expr)?;
+	//
+	match writeln!(self.out.borrow_mut(),"{}",v) {
+	    Ok(_) => Ok(Outcome::Next),
+	    Err(_) => Err(Error::new(stmt.0,"failed to write output"))
+	}
+    }
+
+    pub fn execute_return(&self, frame: &mut Frame, expr: Option<Expr>) -> Result<Outcome> {
+	match expr {
+	    Some(e) => Ok(Outcome::Return(Some(self.evaluate(frame,e)?))),
+	    None => Ok(Outcome::Return(None))
+	}
+    }
+
+    /// Execute a switch statement.  The first case whose label matches
+    /// the discriminant is executed or, failing that, the default (if
+    /// there is one).
+    pub fn execute_switch(&self, frame: &mut Frame, expr: Expr, cases: &[(Option<Expr>,Stmt)]) -> Result<Outcome> {
+	let v = self.evaluate(frame,expr)?;
+	let mut default = None;
+	//
+	for (label,body) in cases {
+	    match label {
+		Some(l) => {
+		    let matched = match (v,self.evaluate(frame,*l)?) {
+			(Value::Bool(b1),Value::Bool(b2)) => b1 == b2,
+			(Value::Int(i1),Value::Int(i2)) => i1 == i2,
+			_ => false
+		    };
+		    if matched {
+			return self.execute(frame,*body);
+		    }
+		}
+		None => { default = Some(*body); }
+	    }
+	}
+	match default {
+	    Some(body) => self.execute(frame,body),
+	    None => Ok(Outcome::Next)
+	}
+    }
+
+    pub fn execute_while(&self, frame: &mut Frame, cond: Expr, body: Stmt) -> Result<Outcome> {
+	while self.evaluate_bool(frame,cond)? {
+	    match self.execute(frame,body)? {
+		Outcome::Next|Outcome::Continue => {}
+		Outcome::Break => { break; }
+		r => { return Ok(r); }
+	    }
+	}
+	Ok(Outcome::Next)
+    }
+
+    // Expressions
+    // -----------------------------------------------------------------
+
+    pub fn evaluate(&self, frame: &Frame, expr: Expr) -> Result<Value> {
+	match self.ast.get(expr.0) {
+	    Node::AddExpr(lhs,rhs) => {
+		let l = self.evaluate_int(frame,*lhs)?;
+		let r = self.evaluate_int(frame,*rhs)?;
+		Ok(Value::Int(l.wrapping_add(r)))
+	    }
+	    Node::AndExpr(lhs,rhs) => {
+		// NOTE: rhs is only evaluated when lhs holds
+		let b = self.evaluate_bool(frame,*lhs)? && self.evaluate_bool(frame,*rhs)?;
+		Ok(Value::Bool(b))
+	    }
+	    Node::BoolExpr(b) => Ok(Value::Bool(*b)),
+	    Node::CallExpr(name,args) => {
+		let mut vs = Vec::new();
+		for a in args {
+		    vs.push(self.evaluate(frame,*a)?);
+		}
+		let d = match self.lookup_method(*name,vs.len()) {
+		    Some(d) => d,
+		    None => { return Err(Error::new(expr.0,"method not found")); }
+		};
+		match self.invoke(d,&vs)? {
+		    Some(v) => Ok(v),
+		    None => Err(Error::new(expr.0,"expected value"))
+		}
+	    }
+	    Node::EqualsExpr(lhs,rhs) => {
+		let l = self.evaluate(frame,*lhs)?;
+		let r = self.evaluate(frame,*rhs)?;
+		Ok(Value::Bool(l == r))
+	    }
+	    Node::IntExpr(n,m) => {
+		match Expr::int_value(*n,*m) {
+		    Some(i) => Ok(Value::Int(i)),
+		    None => Err(Error::new(expr.0,"integer out of range"))
+		}
+	    }
+	    Node::LessThanExpr(lhs,rhs) => {
+		let l = self.evaluate_int(frame,*lhs)?;
+		let r = self.evaluate_int(frame,*rhs)?;
+		Ok(Value::Bool(l < r))
+	    }
+	    Node::NotEqualsExpr(lhs,rhs) => {
+		let l = self.evaluate(frame,*lhs)?;
+		let r = self.evaluate(frame,*rhs)?;
+		Ok(Value::Bool(l != r))
+	    }
+	    Node::NotExpr(e) => {
+		let b = self.evaluate_bool(frame,*e)?;
+		Ok(Value::Bool(!b))
+	    }
+	    Node::NullExpr => Ok(Value::Null),
+	    Node::OrExpr(lhs,rhs) => {
+		// NOTE: rhs is only evaluated when lhs does not hold
+		let b = self.evaluate_bool(frame,*lhs)? || self.evaluate_bool(frame,*rhs)?;
+		Ok(Value::Bool(b))
+	    }
+	    Node::SizeofExpr(t) => {
+		match t.size_of(self.ast) {
+		    Some(n) => Ok(Value::Int(i128::from(n))),
+		    None => Err(Error::new(expr.0,"invalid type"))
+		}
+	    }
+	    Node::SubtractExpr(lhs,rhs) => {
+		let l = self.evaluate_int(frame,*lhs)?;
+		let r = self.evaluate_int(frame,*rhs)?;
+		Ok(Value::Int(l.wrapping_sub(r)))
+	    }
+	    Node::VarExpr(name) => {
+		match frame.get(name) {
+		    Some(v) => Ok(*v),
+		    None => Err(Error::new(expr.0,"variable not found"))
+		}
+	    }
+	    _ => Err(Error::new(expr.0,"unknown expression"))
+	}
+    }
+
+    /// Evaluate an expression which is expected to produce a boolean.
+    fn evaluate_bool(&self, frame: &Frame, expr: Expr) -> Result<bool> {
+	match self.evaluate(frame,expr)? {
+	    Value::Bool(b) => Ok(b),
+	    _ => Err(Error::new(expr.0,"expected boolean"))
+	}
+    }
+
+    /// Evaluate an expression which is expected to produce an integer.
+    fn evaluate_int(&self, frame: &Frame, expr: Expr) -> Result<i128> {
+	match self.evaluate(frame,expr)? {
+	    Value::Int(i) => Ok(i),
+	    _ => Err(Error::new(expr.0,"expected integer"))
+	}
+    }
+}
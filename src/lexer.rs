@@ -1,3 +1,5 @@
+use std::io;
+use std::io::Read;
 use std::iter::Peekable;
 use std::str::CharIndices;
 
@@ -5,11 +7,12 @@ use std::str::CharIndices;
 // Token
 // =================================================================
 
-#[derive(Clone,Copy,PartialEq)]
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub enum TokenType {
     Ampersand,
     AmpersandAmpersand,
     Assert,
+    At,
     Bar,
     BarBar,
     Bool,
@@ -17,6 +20,7 @@ pub enum TokenType {
     Case,
     Colon,
     Comma,
+    Const,
     Continue,
     Default,
     Do,
@@ -34,18 +38,23 @@ pub enum TokenType {
     I16,
     I32,
     I64,
+    I128,
     Integer,
     LeftAngle,
     LeftAngleEquals,    
     LeftBrace,
     LeftCurly,
     LeftSquare,
+    Len,
     Minus,
     MinusGreater,
+    MinusMinus,
     New,
     Null,
     Percent,
     Plus,
+    PlusPlus,
+    Print,
     Return,
     RightAngle,
     RightAndleEquals,
@@ -57,22 +66,128 @@ pub enum TokenType {
     Shreak,    
     ShreakEquals,
     SemiColon,
+    Sizeof,
     Skip,
     Switch,
     Star,
     True,
     Type,
+    Var,
     While,
     U8,
     U16,
     U32,
     U64,
+    U128,
     Void
 }
 
+/// Identifies the broad class of a token, which is useful (for
+/// example) when highlighting source code.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum TokenClass {
+    Identifier,
+    Keyword,
+    Literal,
+    Operator,
+    EOF
+}
+
+impl TokenType {
+    /// Determine the class of this token type.  This deliberately
+    /// matches every variant, so adding a token type forces it to be
+    /// classified.
+    pub fn class(&self) -> TokenClass {
+	use TokenType::*;
+	match self {
+	    // Identifiers
+	    Identifier => TokenClass::Identifier,
+	    // Keywords
+	    Assert|Bool|Break|Case|Const|Continue|Default|Do|Delete|Else
+		|For|If|I8|I16|I32|I64|I128|Len|New|Print|Return|Sizeof|Skip|Switch
+		|Type|Var|While|U8|U16|U32|U64|U128|Void => TokenClass::Keyword,
+	    // Literals
+	    False|Integer|Null|True => TokenClass::Literal,
+	    // Operators (including punctuation)
+	    Ampersand|AmpersandAmpersand|At|Bar|BarBar|Colon|Comma|Dot
+		|Equal|EqualEqual|LeftAngle|LeftAngleEquals|LeftBrace
+		|LeftCurly|LeftSquare|Minus|MinusGreater|MinusMinus
+		|Percent|Plus|PlusPlus|RightAngle|RightAndleEquals
+		|RightBrace|RightCurly|RightSlash|RightSlashSlash
+		|RightSquare|Shreak|ShreakEquals|SemiColon|Star => TokenClass::Operator,
+	    // End of file
+	    EOF => TokenClass::EOF
+	}
+    }
+
+    /// Determine the token type corresponding to a given keyword
+    /// string (e.g. `"while"`), or `None` if the string is not a
+    /// keyword.  Note that this includes literals spelled as words,
+    /// such as `true` and `null`.
+    pub fn from_keyword(s: &str) -> Option<TokenType> {
+	use TokenType::*;
+	let kind = match s {
+	    "assert" => Assert,
+	    "bool" => Bool,
+	    "break" => Break,
+	    "case" => Case,
+	    "const" => Const,
+	    "continue" => Continue,
+	    "default" => Default,
+	    "do" => Do,
+	    "delete" => Delete,
+	    "else" => Else,
+	    "false" => False,
+	    "for" => For,
+	    "if" => If,
+	    "i8" => I8,
+	    "i16" => I16,
+	    "i32" => I32,
+	    "i64" => I64,
+	    "i128" => I128,
+	    "len" => Len,
+	    "new" => New,
+	    "null" => Null,
+	    "print" => Print,
+	    "return" => Return,
+	    "sizeof" => Sizeof,
+	    "skip" => Skip,
+	    "switch" => Switch,
+	    "true" => True,
+	    "type" => Type,
+	    "var" => Var,
+	    "while" => While,
+	    "u8" => U8,
+	    "u16" => U16,
+	    "u32" => U32,
+	    "u64" => U64,
+	    "u128" => U128,
+	    "void" => Void,
+	    _ => { return None; }
+	};
+	Some(kind)
+    }
+
+    /// Determine whether this is a keyword (e.g. `while` or `i32`).
+    pub fn is_keyword(&self) -> bool {
+	self.class() == TokenClass::Keyword
+    }
+
+    /// Determine whether this is an operator or punctuation (e.g. `<`
+    /// or `;`).
+    pub fn is_operator(&self) -> bool {
+	self.class() == TokenClass::Operator
+    }
+
+    /// Determine whether this is a literal (e.g. `true` or `123`).
+    pub fn is_literal(&self) -> bool {
+	self.class() == TokenClass::Literal
+    }
+}
+
 /// Represents a single token generated from a string slice.  This
 /// identifies where the token starts and ends in the original slice.
-#[derive(Clone,Copy,PartialEq)]
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub struct Token<'a> {
     /// Type of the token
     pub kind : TokenType,
@@ -87,12 +202,13 @@ pub struct Token<'a> {
 
 impl<'a> Token<'a> {
     /// Get the integer payload associated with this token, assuming
-    /// it has Integer kind.
-    pub fn as_int(&self) -> i32 {
+    /// it has Integer kind.  This is `None` if the payload is too
+    /// large to be represented.
+    pub fn as_int(&self) -> Option<u128> {
 	// Can only call this method on integer tokens.
 	assert!(self.kind == TokenType::Integer);
 	// Parse conents (expecting integer)
-	return self.content.parse().unwrap();
+	return self.content.parse().ok();
     }
 
     /// Get the string payload associated with this token.
@@ -125,6 +241,20 @@ pub struct Lexer<'a> {
     lookahead: Option<Token<'a>>
 }
 
+/// Read an entire source into memory, such that it can then be
+/// tokenized using `Lexer::new`.  Lexing genuinely incrementally from
+/// a reader is not supported, since every token borrows its content
+/// directly from the input slice (which avoids allocating for each
+/// token).  Supporting this would require tokens to own their content
+/// and the lexer to refill a buffer whenever a token straddled its
+/// end.  In practice, source files are small enough that reading them
+/// in full is not a concern.
+pub fn read_source<R: Read>(mut reader: R) -> io::Result<String> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    Ok(input)
+}
+
 /// An acceptor determines whether or not a character is part of a
 /// given token.
 type Acceptor = fn(char)->bool;
@@ -140,6 +270,25 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Get the slice of input which has not yet been consumed.  If a
+    /// token has been peeked (but not consumed), then this is included
+    /// in the remaining input.
+    pub fn remaining(&self) -> &'a str {
+	let offset = match self.lookahead {
+	    Some(t) if t.kind == TokenType::EOF => self.input.len(),
+	    Some(t) => t.start,
+	    None => {
+		// NOTE: cloning the iterator is cheap, and avoids needing
+		// mutable access for peeking.
+		match self.chars.clone().next() {
+		    Some((o,_)) => o,
+		    None => self.input.len()
+		}
+	    }
+	};
+	&self.input[offset..]
+    }
+
     /// Peek at the next token in the sequence, or none if we have
     /// reached the end.
     pub fn peek(&mut self) -> Token<'a> {
@@ -204,6 +353,29 @@ impl<'a> Lexer<'a> {
         self.next()
     }
 
+    /// Scan the remainder of a line comment (i.e. after the leading
+    /// `//`), then recursively scan an actual token.
+    fn scan_line_comment(&mut self) -> Token<'a> {
+        // Drop everything upto end of line
+        self.scan_whilst(|c| c != '\n');
+        // Scan an actual token
+        self.next()
+    }
+
+    /// Scan the remainder of a block comment (i.e. after the leading
+    /// `/*`), then recursively scan an actual token.  An unterminated
+    /// block comment simply runs to the end of the input.
+    fn scan_block_comment(&mut self) -> Token<'a> {
+        // Drop everything upto (and including) the closing "*/"
+        while let Some((_,c)) = self.chars.next() {
+            if c == '*' && self.scan_following('/') {
+                break;
+            }
+        }
+        // Scan an actual token
+        self.next()
+    }
+
     /// Scan all digits from a given starting point.
     fn scan_integer(&mut self, start: usize) -> Token<'a> {
         let kind = TokenType::Integer;
@@ -216,114 +388,35 @@ impl<'a> Lexer<'a> {
     fn scan_identifier_or_keyword(&mut self, start: usize) -> Token<'a> {
         let end = self.scan_whilst(is_identifier_middle);
         let content = &self.input[start..end];
-        let kind = match content {
-	    "assert" => {
-                TokenType::Assert
-            }
-	    "bool" => {
-                TokenType::Bool
-            }
-	    "break" => {
-                TokenType::Break
-            }
-	    "case" => {
-                TokenType::Case
-            }
-	    "continue" => {
-                TokenType::Continue
-            }
-	    "default" => {
-                TokenType::Default
-            }
-	    "Do" => {
-                TokenType::Do
-            }
-	    "delete" => {
-                TokenType::Delete
-            }
-	    "else" => {
-                TokenType::Else
-            }
-	    "false" => {
-                TokenType::False
-            }
-	    "for" => {
-                TokenType::For
-            }
-            "if" => {
-                TokenType::If
-            }
-	    "i8" => {
-                TokenType::I8
-            }
-	    "i16" => {
-                TokenType::I16
-            }
-	    "i32" => {
-                TokenType::I32
-            }
-	    "i64" => {
-                TokenType::I64
-            }   
-	    "new" => {
-                TokenType::New
-            }
-	    "null" => {
-                TokenType::Null
-            }
-	    "return" => {
-                TokenType::Return
-            }
-	    "skip" => {
-                TokenType::Skip
-            }
-	    "switch" => {
-                TokenType::Switch
-            }
-	    "true" => {
-                TokenType::True
-            }
-	    "type" => {
-                TokenType::Type
-            }
-            "while" => {
-                TokenType::While
-            }
-	    "u8" => {
-                TokenType::U8
-            }
-	    "u16" => {
-                TokenType::U16
-            }
-	    "u32" => {
-                TokenType::U32
-            }
-	    "u64" => {
-                TokenType::U64
-            }	    	    
-	    "void" => {
-                TokenType::Void
-            }
-            _ => {
-                TokenType::Identifier
-            }
-        };
+        let kind = TokenType::from_keyword(content).unwrap_or(TokenType::Identifier);
         Token{kind,start,content}
     }
 
     /// Scan an operator from a given starting point.
-    fn scan_operator(&self, start: usize, ch: char) -> Token<'a> {
+    fn scan_operator(&mut self, start: usize, ch: char) -> Token<'a> {
         let end : usize;
         let kind = match ch {
 	    '&' => {
-		// FIXME: &&
+		if self.scan_following('&') {
+		    end = start + 2;
+		    TokenType::AmpersandAmpersand
+		} else {
+		    end = start + 1;
+		    TokenType::Ampersand
+		}
+	    }
+	    '@' => {
 		end = start + 1;
-                TokenType::Ampersand
+                TokenType::At
 	    }
 	    '|' => {
-		// FIXME: ||
-		end = start + 1;
-                TokenType::Bar
+		if self.scan_following('|') {
+		    end = start + 2;
+		    TokenType::BarBar
+		} else {
+		    end = start + 1;
+		    TokenType::Bar
+		}
 	    }
 	    ':' => {
 		end = start + 1;
@@ -338,9 +431,13 @@ impl<'a> Lexer<'a> {
                 TokenType::Dot
 	    }
 	    '=' => {
-		// FIXME: ==		
-		end = start + 1;
-                TokenType::Equal
+		if self.scan_following('=') {
+		    end = start + 2;
+		    TokenType::EqualEqual
+		} else {
+		    end = start + 1;
+		    TokenType::Equal
+		}
 	    }
 	    '<' => {
 		// FIXME: <=
@@ -361,16 +458,26 @@ impl<'a> Lexer<'a> {
             }
 	    '-' => {
 		// FIXME: ->
-                end = start + 1;
-                TokenType::Minus
+		if self.scan_following('-') {
+		    end = start + 2;
+		    TokenType::MinusMinus
+		} else {
+                    end = start + 1;
+                    TokenType::Minus
+		}
             }
 	    '%' => {
                 end = start + 1;
                 TokenType::Percent
             }
 	    '+' => {
-                end = start + 1;
-                TokenType::Plus
+		if self.scan_following('+') {
+		    end = start + 2;
+		    TokenType::PlusPlus
+		} else {
+                    end = start + 1;
+                    TokenType::Plus
+		}
             }
             '>' => {
 		// FIXME: >=
@@ -386,7 +493,11 @@ impl<'a> Lexer<'a> {
                 TokenType::RightCurly
             }
 	    '/' => {
-		// FIXME: //
+		if self.scan_following('/') {
+		    return self.scan_line_comment();
+		} else if self.scan_following('*') {
+		    return self.scan_block_comment();
+		}
                 end = start + 1;
                 TokenType::RightSlash
             }
@@ -399,9 +510,13 @@ impl<'a> Lexer<'a> {
                 TokenType::SemiColon
 	    }
 	    '!' => {
-		// FIXME: != 
-                end = start + 1;
-                TokenType::Shreak
+		if self.scan_following('=') {
+		    end = start + 2;
+		    TokenType::ShreakEquals
+		} else {
+                    end = start + 1;
+                    TokenType::Shreak
+		}
             }
 	    '*' => {
                 end = start + 1;
@@ -415,6 +530,19 @@ impl<'a> Lexer<'a> {
         Token{kind,start,content}
     }
 
+    /// Check whether the next character matches a given character
+    /// and, if so, gobble it.  This is useful for operators made up
+    /// from more than one character (e.g. `++`).
+    fn scan_following(&mut self, ch: char) -> bool {
+	match self.chars.peek() {
+	    Some((_,c)) if *c == ch => {
+		self.chars.next();
+		true
+	    }
+	    _ => false
+	}
+    }
+
     /// Gobble all characters matched by an acceptor.  For example, we
     /// might want to continue matching digits until we encounter
     /// something which isn't a digit (or is the end of the file).
@@ -525,24 +653,24 @@ fn test_10() {
 #[test]
 fn test_11() {
     let mut l = Lexer::new("  1");
-    assert!(l.peek().as_int() == 1);    
-    assert!(l.next().as_int() == 1);
+    assert!(l.peek().as_int() == Some(1));    
+    assert!(l.next().as_int() == Some(1));
     assert!(l.next() == EOF);
 }
 
 #[test]
 fn test_12() {
     let mut l = Lexer::new("1234");
-    assert!(l.peek().as_int() == 1234);
-    assert!(l.next().as_int() == 1234);
+    assert!(l.peek().as_int() == Some(1234));
+    assert!(l.next().as_int() == Some(1234));
     assert!(l.next() == EOF);
 }
 
 #[test]
 fn test_13() {
     let mut l = Lexer::new("1234 ");
-    assert!(l.peek().as_int() == 1234);
-    assert!(l.next().as_int() == 1234);
+    assert!(l.peek().as_int() == Some(1234));
+    assert!(l.next().as_int() == Some(1234));
     assert!(l.next() == EOF);
 }
 
@@ -559,8 +687,8 @@ fn test_14() {
 #[test]
 fn test_15() {
     let mut l = Lexer::new("1234X");
-    assert!(l.peek().as_int() == 1234);
-    assert!(l.next().as_int() == 1234);
+    assert!(l.peek().as_int() == Some(1234));
+    assert!(l.next().as_int() == Some(1234));
     assert!(l.peek().kind == TokenType::Identifier);
     assert!(l.next().kind == TokenType::Identifier);
     assert!(l.peek() == EOF);
@@ -569,10 +697,10 @@ fn test_15() {
 #[test]
 fn test_16() {
     let mut l = Lexer::new("1234 12");
-    assert!(l.peek().as_int() == 1234);
-    assert!(l.next().as_int() == 1234);
-    assert!(l.peek().as_int() == 12);
-    assert!(l.next().as_int() == 12);
+    assert!(l.peek().as_int() == Some(1234));
+    assert!(l.next().as_int() == Some(1234));
+    assert!(l.peek().as_int() == Some(12));
+    assert!(l.next().as_int() == Some(12));
 }
 
 // Identifiers
@@ -695,6 +823,52 @@ fn test_44() {
     assert!(l.next() == EOF);
 }
 
+#[test]
+fn test_45() {
+    let mut l = Lexer::new("++");
+    let t = l.next();
+    assert!(t.kind == TokenType::PlusPlus);
+    assert!(t.content == "++");
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_46() {
+    let mut l = Lexer::new("+-");
+    assert!(l.next().kind == TokenType::Plus);
+    assert!(l.next().kind == TokenType::Minus);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_47() {
+    let mut l = Lexer::new("---");
+    let t = l.next();
+    assert!(t.kind == TokenType::MinusMinus);
+    assert!(t.content == "--");
+    assert!(l.next().kind == TokenType::Minus);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_48() {
+    let mut l = Lexer::new("@");
+    let t = l.next();
+    assert!(t.kind == TokenType::At);
+    assert!(t.content == "@");
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_49() {
+    let mut l = Lexer::new("@inline");
+    assert!(l.next().kind == TokenType::At);
+    let t = l.next();
+    assert!(t.kind == TokenType::Identifier);
+    assert!(t.content == "inline");
+    assert!(l.next() == EOF);
+}
+
 // Combinations
 
 #[test]
@@ -714,9 +888,162 @@ fn test_61() {
     let mut l = Lexer::new("12345(");
     let t1 = l.next();
     assert!(t1.kind == TokenType::Integer);
-    assert!(t1.as_int() == 12345);
+    assert!(t1.as_int() == Some(12345));
     let t2 = l.next();
     assert!(t2.kind == TokenType::LeftBrace);
     assert!(t2.content == "(");
     assert!(l.next() == EOF);
 }
+
+#[test]
+fn test_62() {
+    let mut l = Lexer::new("while(x)");
+    assert!(l.remaining() == "while(x)");
+    assert!(l.next().kind == TokenType::While);
+    assert!(l.remaining() == "(x)");
+}
+
+#[test]
+fn test_63() {
+    let mut l = Lexer::new("while (x)");
+    assert!(l.next().kind == TokenType::While);
+    assert!(l.peek().kind == TokenType::LeftBrace);
+    assert!(l.remaining() == "(x)");
+    assert!(l.next().kind == TokenType::LeftBrace);
+    assert!(l.remaining() == "x)");
+}
+
+#[test]
+fn test_64() {
+    let mut l = Lexer::new("x ");
+    assert!(l.next().kind == TokenType::Identifier);
+    assert!(l.peek() == EOF);
+    assert!(l.remaining() == "");
+}
+
+#[test]
+fn test_65() {
+    let mut l = Lexer::new("// hello\nx");
+    let t = l.next();
+    assert!(t.kind == TokenType::Identifier);
+    assert!(t.start == 9);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_66() {
+    let mut l = Lexer::new("/* a\n * b */x/y");
+    let t = l.next();
+    assert!(t.kind == TokenType::Identifier);
+    assert!(t.start == 12);
+    assert!(l.next().kind == TokenType::RightSlash);
+    assert!(l.next().kind == TokenType::Identifier);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_67() {
+    let mut l = Lexer::new("x /* **/ y // z");
+    assert!(l.next().start == 0);
+    assert!(l.next().start == 9);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_68() {
+    let mut l = Lexer::new("x /* y");
+    assert!(l.next().kind == TokenType::Identifier);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_69() {
+    let mut l = Lexer::new("while x < 10;");
+    let t = l.next().kind;
+    assert!(t.is_keyword() && !t.is_operator() && !t.is_literal());
+    let t = l.next().kind;
+    assert!(t.class() == TokenClass::Identifier);
+    let t = l.next().kind;
+    assert!(t.is_operator() && !t.is_keyword());
+    let t = l.next().kind;
+    assert!(t.is_literal() && !t.is_operator());
+    assert!(l.next().kind.is_operator());
+    assert!(l.next().kind.class() == TokenClass::EOF);
+}
+
+#[test]
+fn test_70() {
+    assert!(TokenType::True.is_literal());
+    assert!(TokenType::Null.is_literal());
+    assert!(TokenType::I32.is_keyword());
+    assert!(TokenType::At.is_operator());
+}
+
+#[test]
+fn test_71() {
+    let mut l = Lexer::new("i128 u128 i1280");
+    assert!(l.next().kind == TokenType::I128);
+    assert!(l.next().kind == TokenType::U128);
+    assert!(l.next().kind == TokenType::Identifier);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_72() {
+    let keywords = ["assert","bool","break","case","const","continue","default",
+		    "do","delete","else","false","for","if","i8","i16","i32","i64",
+		    "i128","len","new","null","print","return","sizeof","skip","switch","true",
+		    "type","var","while","u8","u16","u32","u64","u128","void"];
+    for k in &keywords {
+	let kind = TokenType::from_keyword(k);
+	assert!(kind.is_some());
+	assert!(Lexer::new(k).next().kind == kind.unwrap());
+    }
+    assert!(TokenType::from_keyword("whilst").is_none());
+    assert!(TokenType::from_keyword("While").is_none());
+}
+
+#[test]
+fn test_73() {
+    let cursor = io::Cursor::new("while x < 10".as_bytes());
+    let input = read_source(cursor).unwrap();
+    let mut l = Lexer::new(&input);
+    assert!(l.next().kind == TokenType::While);
+    assert!(l.next().kind == TokenType::Identifier);
+    assert!(l.next().kind == TokenType::LeftAngle);
+    assert!(l.next().kind == TokenType::Integer);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_74() {
+    // Invalid UTF8 is reported as an error
+    let cursor = io::Cursor::new(vec![0x69u8,0xff]);
+    assert!(read_source(cursor).is_err());
+}
+
+#[test]
+fn test_75() {
+    let mut l = Lexer::new("== = !=!");
+    let t = l.next();
+    assert!(t.kind == TokenType::EqualEqual);
+    assert!(t.content == "==");
+    assert!(l.next().kind == TokenType::Equal);
+    let t = l.next();
+    assert!(t.kind == TokenType::ShreakEquals);
+    assert!(t.content == "!=");
+    assert!(l.next().kind == TokenType::Shreak);
+    assert!(l.next() == EOF);
+}
+
+#[test]
+fn test_76() {
+    let mut l = Lexer::new("&& & ||| &&&");
+    assert!(l.next().kind == TokenType::AmpersandAmpersand);
+    assert!(l.next().kind == TokenType::Ampersand);
+    assert!(l.next().kind == TokenType::BarBar);
+    assert!(l.next().kind == TokenType::Bar);
+    assert!(l.next().kind == TokenType::AmpersandAmpersand);
+    assert!(l.next().kind == TokenType::Ampersand);
+    assert!(l.next() == EOF);
+}
@@ -2,5 +2,138 @@ pub mod ast;
 pub mod lexer;
 pub mod parser;
 pub mod typer;
+pub mod verifier;
+pub mod reachability;
+pub mod lint;
 pub mod source_map;
 pub mod error;
+pub mod interpreter;
+pub mod printer;
+pub mod desugar;
+pub mod optimizer;
+
+use std::time::{Duration,Instant};
+use crate::ast::{AbstractSyntaxTree,Decl};
+use crate::lexer::{Lexer,TokenType};
+use crate::typer::Env;
+use crate::parser::Parser;
+use crate::typer::TypeChecker;
+use crate::error::CompileError;
+
+/// Parse a given source string into a sequence of declarations.  The
+/// heap holding the parsed declarations is returned alongside them.
+pub fn parse(src: &str) -> Result<(AbstractSyntaxTree,Vec<Decl>),parser::Error> {
+    // Roughly one node for every few bytes of input.
+    let mut ast = AbstractSyntaxTree::with_capacity(src.len() / 4);
+    let decls = Parser::new(src,&mut ast,|_,_| {}).parse_program()?;
+    Ok((ast,decls))
+}
+
+/// Parse and type check a given source string, including checking
+/// that every statement is reachable and that non-void methods always
+/// return.  The heap holding the checked program is returned, or the
+/// first error encountered.
+pub fn typecheck(src: &str) -> Result<AbstractSyntaxTree,CompileError> {
+    let (mut ast,decls) = parse(src)?;
+    TypeChecker::new(&mut ast,|_,_| {}).check_program(&decls)?;
+    reachability::check(&ast,&decls)?;
+    Ok(ast)
+}
+
+/// Parse and type check a given source string as for `typecheck`,
+/// but reporting up to `max_errors` errors rather than just the
+/// first.  Type checking only goes ahead when the input parses
+/// without error, and likewise for reachability.
+pub fn typecheck_all(src: &str, max_errors: usize) -> Result<AbstractSyntaxTree,Vec<CompileError>> {
+    let mut ast = AbstractSyntaxTree::with_capacity(src.len() / 4);
+    let mut parser = Parser::new(src,&mut ast,|_,_| {});
+    parser.set_max_errors(max_errors);
+    let decls = parser.parse_program_partial();
+    let errors : Vec<CompileError> = parser.errors().iter().cloned().map(CompileError::from).collect();
+    if !errors.is_empty() {
+	return Err(errors);
+    }
+    let errors = TypeChecker::new(&mut ast,|_,_| {}).with_max_errors(max_errors).check_program_all(&decls);
+    if !errors.is_empty() {
+	return Err(errors.into_iter().map(CompileError::from).collect());
+    }
+    reachability::check(&ast,&decls).map_err(|e| vec![e.into()])?;
+    Ok(ast)
+}
+
+/// Records how long each phase of checking a source string took.  A
+/// phase which was not reached (e.g. because parsing failed) has zero
+/// duration.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Timings {
+    pub lexing : Duration,
+    pub parsing : Duration,
+    pub typing : Duration
+}
+
+/// As for `typecheck`, but additionally reporting how long each phase
+/// took.  Since the parser lexes on demand, lexing is timed by
+/// tokenising the entire input up front and, hence, the parsing time
+/// includes lexing as well.
+pub fn typecheck_timed(src: &str) -> (Result<AbstractSyntaxTree,CompileError>,Timings) {
+    let mut timings = Timings::default();
+    // Lexing
+    let now = Instant::now();
+    let mut lexer = Lexer::new(src);
+    while lexer.next().kind != TokenType::EOF {}
+    timings.lexing = now.elapsed();
+    // Parsing
+    let now = Instant::now();
+    let r = parse(src);
+    timings.parsing = now.elapsed();
+    let (mut ast,decls) = match r {
+	Ok(r) => r,
+	Err(e) => { return (Err(e.into()),timings); }
+    };
+    // Type checking
+    let now = Instant::now();
+    let r = TypeChecker::new(&mut ast,|_,_| {}).check_program(&decls)
+	.and_then(|_| reachability::check(&ast,&decls));
+    timings.typing = now.elapsed();
+    //
+    match r {
+	Ok(_) => (Ok(ast),timings),
+	Err(e) => (Err(e.into()),timings)
+    }
+}
+
+/// Parse and type check a given source string, and then format each
+/// declaration after lowering it into the core language (see
+/// `desugar::desugar`).  This is used by the `--desugar` flag.
+pub fn format_desugared(src: &str) -> Result<String,CompileError> {
+    let (mut ast,decls) = parse(src)?;
+    TypeChecker::new(&mut ast,|_,_| {}).check_program(&decls)?;
+    let mut out = String::new();
+    for d in decls {
+	let d = desugar::desugar(&mut ast,d);
+	out.push_str(&printer::format(&ast,d));
+    }
+    Ok(out)
+}
+
+/// Parse and type check a given expression in an empty environment,
+/// returning its static type in source form (e.g. `bool` for `1 <
+/// 2`).  This is used by the REPL's `:t` command.
+pub fn type_of(src: &str) -> Result<String,CompileError> {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(src,&mut ast,|_,_| {});
+    let e = parser.parse_expr()?;
+    parser.expect_end()?;
+    let t = TypeChecker::new(&mut ast,|_,_| {}).check_expr(&Env::new(),e)?;
+    Ok(printer::display_type(&ast,t))
+}
+
+/// Describe a given error code (e.g. `E0002`) in more detail,
+/// including a minimal example of the error.  This is used by the
+/// `--explain` flag.
+pub fn explain(code: &str) -> String {
+    match error::explain(code) {
+	Some(text) => text.to_string(),
+	None => format!("unknown error code '{}'",code)
+    }
+}
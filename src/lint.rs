@@ -0,0 +1,76 @@
+use crate::ast::*;
+use crate::error::*;
+
+// =================================================================
+// Lint
+// =================================================================
+
+/// Check for code within a given set of declarations which is legal,
+/// but likely to be a mistake.  Unlike other checks, this does not
+/// stop at the first problem found and, instead, returns all warnings
+/// found.  This check is opt-in and is not performed as part of type
+/// checking.  Currently, the only warning reported is for a local
+/// variable which shadows an enclosing parameter or local variable.
+pub fn check(ast: &AbstractSyntaxTree, roots: &[Decl]) -> Vec<SyntaxError> {
+    let mut warnings = Vec::new();
+    for d in roots {
+	check_decl(ast,*d,&mut warnings);
+    }
+    warnings
+}
+
+fn check_decl(ast: &AbstractSyntaxTree, d: Decl, warnings: &mut Vec<SyntaxError>) {
+    match ast.get(d.index) {
+	Node::AnnotatedDecl(_,d) => check_decl(ast,*d,warnings),
+	Node::MethodDecl(_,_,params,body) => {
+	    let mut scope : Vec<&str> = params.iter().map(|p| p.name.as_str(ast)).collect();
+	    check_stmt(ast,*body,&mut scope,warnings);
+	}
+	_ => {}
+    }
+}
+
+/// Check a given statement for shadowed variables, where `scope`
+/// holds the names of all variables currently in scope.  Any variable
+/// declared by the statement itself is added to `scope`, whilst those
+/// declared in nested blocks are removed again on exit.
+fn check_stmt<'a>(ast: &'a AbstractSyntaxTree, s: Stmt, scope: &mut Vec<&'a str>, warnings: &mut Vec<SyntaxError>) {
+    match ast.get(s.0) {
+	Node::BlockStmt(stmts) => {
+	    let n = scope.len();
+	    for s in stmts {
+		check_stmt(ast,*s,scope,warnings);
+	    }
+	    scope.truncate(n);
+	}
+	Node::ForStmt(init,_,update,body) => {
+	    let n = scope.len();
+	    check_stmt(ast,*init,scope,warnings);
+	    check_stmt(ast,*update,scope,warnings);
+	    check_stmt(ast,*body,scope,warnings);
+	    scope.truncate(n);
+	}
+	Node::IfStmt(_,tt,ff) => {
+	    check_stmt(ast,*tt,scope,warnings);
+	    if let Some(ff) = ff {
+		check_stmt(ast,*ff,scope,warnings);
+	    }
+	}
+	Node::SwitchStmt(_,cases) => {
+	    for (_,body) in cases {
+		check_stmt(ast,*body,scope,warnings);
+	    }
+	}
+	Node::VarDeclStmt(_,name,_) => {
+	    let name = name.as_str(ast);
+	    if scope.contains(&name) {
+		warnings.push(shadowed_variable(s.0));
+	    }
+	    scope.push(name);
+	}
+	Node::WhileStmt(_,body) => {
+	    check_stmt(ast,*body,scope,warnings);
+	}
+	_ => {}
+    }
+}
@@ -1,25 +1,131 @@
+use std::env;
+use std::fs;
 use std::io;
 use std::io::Write;
+use std::process;
 
-mod ast;
-mod lexer;
-mod parser;
-mod source_map;
-//mod typer;
-//mod type_map;
-mod error;
-
-use crate::parser::Parser;
-use crate::parser::Error;
-use crate::source_map::SourceMap;
-use crate::error::SyntaxError;
-use crate::ast::AbstractSyntaxTree;
+use lil::parser::Parser;
+use lil::parser::Error;
+use lil::source_map::SourceMap;
+use lil::error::{CompileError,Diagnostic};
+use lil::ast::AbstractSyntaxTree;
 
 fn main() -> Result<(),io::Error> {
-    repl()?;
+    let args : Vec<String> = env::args().skip(1).collect();
+    // Explain an error code?
+    if let Some(i) = args.iter().position(|a| a == "--explain") {
+	match args.get(i+1) {
+	    Some(code) => println!("{}",lil::explain(code)),
+	    None => println!("expected error code after --explain")
+	}
+	return Ok(());
+    }
+    // Report phase durations?
+    let time = args.iter().any(|a| a == "--time");
+    // Print lowered program?
+    let desugar = args.iter().any(|a| a == "--desugar");
+    // Print tree as JSON?
+    let ast_json = args.iter().any(|a| a == "--ast-json");
+    // Report several errors (rather than just the first)?
+    let max_errors = args.iter().position(|a| a == "--max-errors");
+    let limit = match max_errors.map(|i| args.get(i+1).and_then(|n| n.parse::<usize>().ok()).filter(|n| *n > 0)) {
+	Some(None) => {
+	    eprintln!("expected positive number after --max-errors");
+	    process::exit(1);
+	}
+	Some(limit) => limit,
+	None => None
+    };
+    let files : Vec<&String> = args.iter().enumerate()
+	.filter(|(i,a)| !a.starts_with("--") && Some(*i) != max_errors.map(|j| j+1))
+	.map(|(_,a)| a).collect();
+    //
+    if files.is_empty() {
+	repl()?;
+    } else {
+	for f in files {
+	    if ast_json {
+		ast_json_file(f)?;
+	    } else if desugar {
+		desugar_file(f)?;
+	    } else {
+		check_file(f,time,limit)?;
+	    }
+	}
+    }
     Ok(())
 }
 
+/// Parse a given file and print its tree as JSON (see
+/// `Ast::to_json`).  If the file does not parse, then the error is
+/// reported on stderr and the process exits with a non-zero status.
+fn ast_json_file(filename: &str) -> Result<(),io::Error> {
+    let input = fs::read_to_string(filename)?;
+    //
+    match lil::parse(&input) {
+	Ok((ast,_)) => println!("{}",ast.to_json()),
+	Err(err) => {
+	    eprint!("{}",Diagnostic::from(&err).render(&input));
+	    process::exit(1);
+	}
+    }
+    Ok(())
+}
+
+/// Parse and type check a given file, and then print it after
+/// lowering into the core language.  This helps to show how (for
+/// example) a `for` loop is executed.
+fn desugar_file(filename: &str) -> Result<(),io::Error> {
+    let input = fs::read_to_string(filename)?;
+    //
+    match lil::format_desugared(&input) {
+	Ok(out) => print!("{}",out),
+	Err(CompileError::Parse(err)) => {
+	    print!("{}",Diagnostic::from(&err).render(&input));
+	}
+	Err(CompileError::Syntax(err)) => println!("error: {}",err.errno)
+    }
+    Ok(())
+}
+
+/// Parse and type check a given file, reporting the first error (if
+/// any).  Optionally, the time taken by each phase is also reported.
+/// Alternatively, when a maximum number of errors is given, up to
+/// that many errors are reported (but timings are not).
+fn check_file(filename: &str, time: bool, max_errors: Option<usize>) -> Result<(),io::Error> {
+    let input = fs::read_to_string(filename)?;
+    //
+    if let Some(n) = max_errors {
+	if let Err(errs) = lil::typecheck_all(&input,n) {
+	    for err in errs {
+		print_compile_error(&input,err);
+	    }
+	}
+	return Ok(());
+    }
+    let (r,timings) = lil::typecheck_timed(&input);
+    //
+    if let Err(err) = r {
+	print_compile_error(&input,err);
+    }
+    if time {
+	println!("lexing: {:?}",timings.lexing);
+	println!("parsing: {:?}",timings.parsing);
+	println!("typing: {:?}",timings.typing);
+    }
+    Ok(())
+}
+
+/// Print an error arising from checking a given input.
+fn print_compile_error(input: &str, err: CompileError) {
+    match err {
+	CompileError::Parse(err) => {
+	    print!("{}",Diagnostic::from(&err).render(input));
+	}
+	CompileError::Syntax(err) => println!("{}",err)
+    }
+}
+
 fn repl() -> Result<(),io::Error> {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
@@ -34,6 +140,12 @@ fn repl() -> Result<(),io::Error> {
 	// Read input line
     stdin.read_line(&mut input)?;
 	let line = input.as_str();
+	// Check for commands
+	if let Some(expr) = line.trim_end().strip_prefix(":t ") {
+	    print_type_of(expr);
+	    input.clear();
+	    continue;
+	}
 	// Construct temporary source map
 	let mut source_map = SourceMap::new(line);
 	// Construct temporary type map
@@ -62,40 +174,18 @@ fn repl() -> Result<(),io::Error> {
     }
 }
 
-fn print_error(line: &str, err: Error) {
-    println!("error:{}: {}",err.start,err.message);
-    println!();
-    print!("{}",line);
-    print_highlight(line,err.start,err.end);
-}
-
-fn print_syntax_error<'a>(err: &SyntaxError, map: &SourceMap<'a>) {
-    println!("error: {}",err.errno);
-    // Determine the highlight
-    let hl = map.get_highlight(err.node);
-    // Print the enclosing line
-    print!("{}",hl.line);
-    // Highlight relevant section
-    print_highlight(hl.line,hl.start,hl.end);
-}
-
-fn print_highlight<'a>(line: &'a str, start: usize, end: usize) {
-    // Convert the given line into equivalent whitespace
-    let indent = to_whitespace(line,start);
-    // Print out preamble
-    print!("{}",indent);
-    //
-    for _ in start .. end {
-	print!("^");
+/// Print the static type of a given expression, as requested by the
+/// `:t` command.
+fn print_type_of(expr: &str) {
+    match lil::type_of(expr) {
+	Ok(t) => println!("{}",t),
+	Err(CompileError::Parse(err)) => {
+	    print!("{}",Diagnostic::from(&err).render(expr));
+	}
+	Err(CompileError::Syntax(err)) => println!("error: {}",err.errno)
     }
-    println!("");
 }
 
-/// Convert the start of a given line into corresponding whitespace.
-/// This is pretty straightforward, where most characters are simply
-/// converted into spaces.  However, in some cases, we want to keep
-/// the character as is (e.g. for a tab).
-fn to_whitespace(line: &str, offset: usize) -> String {
-    // Personally, a loop has more clarity than this jiberish :)
-    line.char_indices().filter(|s| s.0 < offset).map(|_| " ").collect()
+fn print_error(line: &str, err: Error) {
+    print!("{}",Diagnostic::from(&err).render(line.trim_end()));
 }
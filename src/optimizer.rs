@@ -0,0 +1,125 @@
+use crate::ast::*;
+
+// =================================================================
+// Constant Folding
+// =================================================================
+
+/// Optimise a given declaration by folding constant expressions,
+/// returning the optimised declaration.  Currently, this only folds
+/// boolean operators applied to literals.  For example, `true &&
+/// false` becomes `false` and `!false` becomes `true`.
+///
+/// As for desugaring, the optimised declaration is added as new nodes
+/// and any part of the original declaration which is unchanged is
+/// shared with it.
+pub fn optimize(ast: &mut AbstractSyntaxTree, d: Decl) -> Decl {
+    match ast.get(d.index).clone() {
+	Node::AnnotatedDecl(anns,inner) => {
+	    let inner = optimize(ast,inner);
+	    Decl::new(ast,Node::AnnotatedDecl(anns,inner))
+	}
+	Node::ConstDecl(t,name,e) => {
+	    let e = fold(ast,e);
+	    Decl::new(ast,Node::ConstDecl(t,name,e))
+	}
+	Node::MethodDecl(name,ret,params,body) => {
+	    let body = optimize_stmt(ast,body);
+	    Decl::new(ast,Node::MethodDecl(name,ret,params,body))
+	}
+	_ => d
+    }
+}
+
+fn optimize_stmt(ast: &mut AbstractSyntaxTree, s: Stmt) -> Stmt {
+    let node = match ast.get(s.0).clone() {
+	Node::AssertStmt(e) => Node::AssertStmt(fold(ast,e)),
+	Node::AssignStmt(lhs,rhs) => Node::AssignStmt(lhs,fold(ast,rhs)),
+	Node::BlockStmt(stmts) => {
+	    Node::BlockStmt(stmts.into_iter().map(|s| optimize_stmt(ast,s)).collect())
+	}
+	Node::ForStmt(init,cond,update,body) => {
+	    let init = optimize_stmt(ast,init);
+	    let cond = fold(ast,cond);
+	    let update = optimize_stmt(ast,update);
+	    Node::ForStmt(init,cond,update,optimize_stmt(ast,body))
+	}
+	Node::IfStmt(cond,tt,ff) => {
+	    let cond = fold(ast,cond);
+	    let tt = optimize_stmt(ast,tt);
+	    let ff = ff.map(|ff| optimize_stmt(ast,ff));
+	    Node::IfStmt(cond,tt,ff)
+	}
+	Node::PrintStmt(e) => Node::PrintStmt(fold(ast,e)),
+	Node::ReturnStmt(e) => Node::ReturnStmt(e.map(|e| fold(ast,e))),
+	Node::SwitchStmt(e,cases) => {
+	    let e = fold(ast,e);
+	    let cases = cases.into_iter().map(|(l,b)| (l,optimize_stmt(ast,b))).collect();
+	    Node::SwitchStmt(e,cases)
+	}
+	Node::VarDeclStmt(t,name,e) => Node::VarDeclStmt(t,name,fold(ast,e)),
+	Node::WhileStmt(cond,body) => {
+	    let cond = fold(ast,cond);
+	    Node::WhileStmt(cond,optimize_stmt(ast,body))
+	}
+	_ => { return s; }
+    };
+    Stmt::new(ast,node)
+}
+
+/// Fold a given expression, returning either the original expression
+/// (if nothing could be folded) or a new expression.  Since boolean
+/// operators are only folded when their operands are literals, this
+/// is safe in the presence of short-circuiting (i.e. no operand with
+/// side-effects is ever removed).
+pub fn fold(ast: &mut AbstractSyntaxTree, e: Expr) -> Expr {
+    let node = match ast.get(e.0).clone() {
+	Node::AndExpr(l,r) => fold_binary(ast,l,r,Node::AndExpr,|a,b| a && b),
+	Node::OrExpr(l,r) => fold_binary(ast,l,r,Node::OrExpr,|a,b| a || b),
+	Node::EqualsExpr(l,r) => fold_binary(ast,l,r,Node::EqualsExpr,|a,b| a == b),
+	Node::NotEqualsExpr(l,r) => fold_binary(ast,l,r,Node::NotEqualsExpr,|a,b| a != b),
+	Node::NotExpr(e) => {
+	    let e = fold(ast,e);
+	    match as_bool(ast,e) {
+		Some(b) => Node::BoolExpr(!b),
+		None => Node::NotExpr(e)
+	    }
+	}
+	Node::AddExpr(l,r) => Node::AddExpr(fold(ast,l),fold(ast,r)),
+	Node::LessThanExpr(l,r) => Node::LessThanExpr(fold(ast,l),fold(ast,r)),
+	Node::SubtractExpr(l,r) => Node::SubtractExpr(fold(ast,l),fold(ast,r)),
+	Node::CallExpr(name,args) => {
+	    Node::CallExpr(name,args.into_iter().map(|a| fold(ast,a)).collect())
+	}
+	Node::LenExpr(e) => Node::LenExpr(fold(ast,e)),
+	_ => { return e; }
+    };
+    // Only allocate a new node if something actually changed
+    if &node == ast.get(e.0) {
+	e
+    } else {
+	Expr::new(ast,node)
+    }
+}
+
+/// Fold a binary operator over booleans.  The operands are folded
+/// first and, if both are then literals, the operator is applied
+/// directly.  Otherwise, the operator is rebuilt using the folded
+/// operands.
+fn fold_binary<C,F>(ast: &mut AbstractSyntaxTree, lhs: Expr, rhs: Expr, ctor: C, op: F) -> Node
+where C : Fn(Expr,Expr) -> Node, F : Fn(bool,bool) -> bool {
+    let lhs = fold(ast,lhs);
+    let rhs = fold(ast,rhs);
+    match (as_bool(ast,lhs),as_bool(ast,rhs)) {
+	(Some(l),Some(r)) => Node::BoolExpr(op(l,r)),
+	(_,_) => ctor(lhs,rhs)
+    }
+}
+
+/// Determine whether a given expression is a boolean literal and, if
+/// so, its value.
+fn as_bool(ast: &AbstractSyntaxTree, e: Expr) -> Option<bool> {
+    match ast.get(e.0) {
+	Node::BoolExpr(b) => Some(*b),
+	_ => None
+    }
+}
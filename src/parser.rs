@@ -1,3 +1,4 @@
+use std::fmt;
 use std::result;
 use std::collections::HashMap;
 use crate::lexer::Lexer;
@@ -13,74 +14,328 @@ type Env = HashMap<String, Name>;
 // =================================================================
 
 /// Identifies possible errors stemming from the parser.
-#[derive(Debug)]
+#[derive(Clone,Debug)]
 pub struct Error {
     pub start: usize,
     pub end: usize,
-    pub message: &'static str
+    pub message: String
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
 impl Error {
-    pub fn new<'a>(tok: Token<'a>, message: &'static str) -> Error {
+    pub fn new<'a>(tok: Token<'a>, message: &str) -> Error {
 	let start = tok.start;
 	let end = tok.end();
-	Error{start,end,message}
+	Error{start,end,message: message.to_string()}
+    }
+
+    /// Construct an error spanning a sequence of tokens, from the
+    /// start of the first token to the end of the last.  This is used
+    /// for errors about multi-token constructs, such as a whole type
+    /// or expression.
+    pub fn span_from<'a>(first: Token<'a>, last: Token<'a>, message: &str) -> Error {
+	let start = first.start;
+	let end = last.end();
+	Error{start,end,message: message.to_string()}
+    }
+
+    /// Construct an error arising at the end of the input.  Since the
+    /// `EOF` token has no meaningful position, the error is placed
+    /// immediately after the last character.
+    pub fn at_eof(input_len: usize, message: &str) -> Error {
+	Error{start: input_len, end: input_len, message: message.to_string()}
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f,"{} (at {}..{})",self.message,self.start,self.end)
+    }
+}
+
+impl std::error::Error for Error {}
+
 // =================================================================
 // Parser
 // =================================================================
 
+/// The default maximum nesting depth of expressions, statements and
+/// types.  This prevents deeply nested input from overflowing the
+/// stack.
+pub const DEFAULT_RECURSION_LIMIT : usize = 256;
+
+/// Default maximum number of errors collected by
+/// `parse_program_partial`.
+pub const DEFAULT_MAX_ERRORS : usize = 20;
+
 /// Response for turning a stream of tokens into an Abstract Syntax
-/// Tree and/or producing error messages along the way.
-pub struct Parser<'a, F>
+/// Tree and/or producing error messages along the way.  All parse
+/// methods take a plain `&mut self` and, hence, a parser can be used
+/// repeatedly (e.g. to parse one declaration after another) and then
+/// dropped to release its borrow of the AST:
+///
+/// ```ignore
+/// let mut parser = Parser::new("type t = i32; type u = t;",&mut ast,|_,_| {});
+/// let d1 = parser.parse_decl()?;
+/// let d2 = parser.parse_decl()?;
+/// ```
+///
+/// Nodes are pushed onto the tree in a deterministic order: a node's
+/// children are pushed before the node itself, in the order they
+/// appear in the source.  For example, `type rec = {i32 f, u16 g};`
+/// gives `rec`, `i32`, `f`, `u16`, `g`, the record type and, finally,
+/// the declaration.  The only exceptions are that a type identical to
+/// one already parsed reuses the earlier node (see `new_type`), and
+/// likewise for a name (see `parse_identifier`).
+/// Consumers (such as tests) may rely on this ordering, so changes to
+/// it should be made deliberately.
+pub struct Parser<'a, 't, F>
 where F : FnMut(usize,&'a str) {
     /// Provides access to our token stream.
     lexer: Lexer<'a>,
-    /// Provides access to the ast
-    ast: &'a mut AbstractSyntaxTree,
+    /// Provides access to the ast.  Note this is borrowed separately
+    /// from the input, so that source maps (which borrow the input)
+    /// can outlive the parser's use of the ast.
+    ast: &'t mut AbstractSyntaxTree,
     /// Provides name cache
     env: Env,
     /// Provides mechanism for source maps
-    mapper : F
-
+    mapper : F,
+    /// Identifies the last token consumed (if any).
+    last: Option<Token<'a>>,
+    /// Current nesting depth of expressions, statements and types.
+    depth: usize,
+    /// Maximum nesting depth permitted.
+    limit: usize,
+    /// Maximum number of errors collected by `parse_program_partial`.
+    max_errors: usize,
+    /// Type aliases currently in scope, with the innermost last.
+    /// Aliases are resolved during parsing and, hence, must be
+    /// declared before they are used.
+    aliases: Vec<(String,Type)>,
+    /// Errors recovered from whilst parsing the current declaration,
+    /// in the order they were encountered.
+    pending: Vec<Error>,
+    /// All errors encountered by `parse_program_partial`, in the
+    /// order they were encountered.
+    errors: Vec<Error>,
+    /// Number of `{` consumed without a matching `}`, such that a
+    /// broken declaration can be skipped in its entirety.
+    nesting: usize,
+    /// Types created so far, such that structurally identical types
+    /// share a single node on the tree.
+    types: HashMap<Node,Type>
 }
 
-impl<'a,'b,F> Parser<'a,F>
-where 'a :'b, F : FnMut(usize,&'a str) {
+impl<'a,'t,F> Parser<'a,'t,F>
+where F : FnMut(usize,&'a str) {
 
-    pub fn new(input: &'a str, ast: &'a mut AbstractSyntaxTree, mapper : F) -> Self {
+    pub fn new(input: &'a str, ast: &'t mut AbstractSyntaxTree, mapper : F) -> Self {
 	let env : Env = HashMap::new();
-	Self { lexer: Lexer::new(input), ast, env, mapper }
+	let limit = DEFAULT_RECURSION_LIMIT;
+	let max_errors = DEFAULT_MAX_ERRORS;
+	let aliases = Vec::new();
+	let pending = Vec::new();
+	let errors = Vec::new();
+	let types = HashMap::new();
+	Self { lexer: Lexer::new(input), ast, env, mapper, last: None, depth: 0, limit, max_errors, aliases, pending, errors, nesting: 0, types }
     }
 
     // =========================================================================
     // Accessors / Mutators
     // =========================================================================
 
+    /// Set the maximum nesting depth of expressions, statements and
+    /// types.  Input nested beyond this produces an error.
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+	self.limit = limit;
+    }
+
+    /// Set the maximum number of errors collected by
+    /// `parse_program_partial`, which is `DEFAULT_MAX_ERRORS` by
+    /// default.  Once this is exceeded, parsing stops.
+    pub fn set_max_errors(&mut self, max_errors: usize) {
+	self.max_errors = max_errors;
+    }
+
+    /// Get all errors encountered by `parse_program_partial`, in the
+    /// order they were encountered.  There are at most as many as
+    /// the maximum set by `set_max_errors`, followed by a final note
+    /// (positioned at the first error not reported) if parsing
+    /// stopped early.
+    pub fn errors(&self) -> &[Error] {
+	&self.errors
+    }
+
     // =========================================================================
     // Declarations
     // =========================================================================
 
-    /// Parse an arbitrary declaration
-    pub fn parse_decl(&'b mut self) -> Result<Decl> {
+    /// Parse a sequence of zero or more declarations until the end of
+    /// the input is reached.  Thus, an input containing only
+    /// whitespace and/or comments gives an empty program.  Each
+    /// declaration is also recorded as a root of the tree (see
+    /// `Ast::roots`).
+    pub fn parse_program(&mut self) -> Result<Vec<Decl>> {
+	let mut decls : Vec<Decl> = vec![];
+	// Keep going until end-of-file
+	while !self.is_at_end() {
+	    let d = self.parse_decl()?;
+	    // Record entry point on the tree itself
+	    self.ast.add_root(d.index);
+	    decls.push(d);
+	}
+	// Done
+	Ok(decls)
+    }
+
+    /// Parse a sequence of declarations until the end of the input
+    /// is reached, as for `parse_program`.  However, rather than
+    /// stopping at the first error, a declaration which cannot be
+    /// parsed is skipped (up to the end of its body) and parsing
+    /// resumes with the next.  Thus, this always returns those
+    /// declarations parsed successfully, whilst all errors are
+    /// available from `errors()`.  Parsing stops early once the
+    /// maximum number of errors is exceeded (see `set_max_errors`).
+    pub fn parse_program_partial(&mut self) -> Vec<Decl> {
+	let mut decls : Vec<Decl> = vec![];
+	// Keep going until end-of-file
+	while !self.is_at_end() {
+	    self.nesting = 0;
+	    let d = self.parse_decl_inner();
+	    // Retain every error recovered from (if any)
+	    let broken = !self.pending.is_empty();
+	    self.errors.append(&mut self.pending);
+	    match d {
+		Ok(d) if !broken => {
+		    // Record entry point on the tree itself
+		    self.ast.add_root(d.index);
+		    decls.push(d);
+		}
+		Ok(_) => {}
+		Err(err) => {
+		    self.errors.push(err);
+		    self.skip_decl();
+		}
+	    }
+	    if self.errors.len() > self.max_errors {
+		// Replace those beyond the maximum with a final note
+		let Error{start,end,..} = self.errors[self.max_errors];
+		self.errors.truncate(self.max_errors);
+		self.errors.push(Error{start,end,message: "too many errors; stopping".to_string()});
+		break;
+	    }
+	}
+	// Done
+	decls
+    }
+
+    /// Skip the remainder of a declaration which could not be parsed.
+    /// Tokens are skipped up to (and including) the next `;` or `}`
+    /// at the outermost level of the declaration.
+    fn skip_decl(&mut self) {
+	loop {
+	    match self.lexer.peek().kind {
+		TokenType::EOF => break,
+		TokenType::SemiColon|TokenType::RightCurly => {
+		    self.advance();
+		    if self.nesting == 0 { break; }
+		}
+		_ => {
+		    self.advance();
+		}
+	    }
+	}
+    }
+
+    /// Parse an arbitrary declaration, which may be preceded by zero
+    /// or more annotations.  Statements which cannot be parsed are
+    /// replaced by `Node::Error` placeholders, such that the remainder
+    /// of the declaration is still added to the tree.  Nevertheless,
+    /// the first such error is reported.
+    pub fn parse_decl(&mut self) -> Result<Decl> {
+	let decl = self.parse_decl_inner();
+	// Report first error recovered from (if any)
+	match self.pending.drain(..).next() {
+	    Some(err) => Err(err),
+	    None => decl
+	}
+    }
+
+    fn parse_decl_inner(&mut self) -> Result<Decl> {
+	// [Annotation]*
+	let annotations = self.parse_annotations()?;
 	let lookahead = self.lexer.peek();
 	// Attempt to parse declaration
-	match lookahead.kind {
+	let decl = match lookahead.kind {
+	    TokenType::Const => {
+		self.parse_decl_const()?
+	    }
 	    TokenType::Type => {
-		self.parse_decl_type()
+		self.parse_decl_type()?
 	    }
 	    _ => {
-		self.parse_decl_method()
+		self.parse_decl_method()?
 	    }
+	};
+	// Attach annotations (if any)
+	if annotations.is_empty() {
+	    Ok(decl)
+	} else {
+	    Ok(Decl::new(self.ast,Node::AnnotatedDecl(annotations,decl)))
 	}
     }
 
+    /// Parse zero or more annotations of the form `@name` or
+    /// `@name(Expr, ..., Expr)`.
+    pub fn parse_annotations(&mut self) -> Result<Vec<Annotation>> {
+	let mut annotations : Vec<Annotation> = vec![];
+	// Keep going until no more "@"
+	while self.snap(TokenType::At).is_ok() {
+	    // Identifier
+	    let name = self.parse_identifier()?;
+	    let mut args : Vec<Expr> = vec![];
+	    // ["(" [Expr]* ")"]
+	    if self.snap(TokenType::LeftBrace).is_ok() {
+		while self.snap(TokenType::RightBrace).is_err() {
+		    // Check if first time or not
+		    if !args.is_empty() {
+			// Not first time, so match comma
+			self.snap(TokenType::Comma)?;
+		    }
+		    args.push(self.parse_expr()?);
+		}
+	    }
+	    annotations.push(Annotation{name,args});
+	}
+	// Done
+	Ok(annotations)
+    }
+
+    /// Parse a constant declaration of the form `const Type name =
+    /// Expr;`.
+    pub fn parse_decl_const(&mut self) -> Result<Decl> {
+	// "const"
+	let start = self.snap(TokenType::Const)?;
+	// Type
+	let typ_e = self.parse_type()?;
+	// Identifier
+	let name = self.parse_identifier()?;
+	// "="
+	self.snap(TokenType::Equal)?;
+	// Expr
+	let expr = self.parse_expr()?;
+	// Semi-colon
+	self.snap(TokenType::SemiColon)?;
+	// Done
+	let decl = Decl::new(self.ast,Node::ConstDecl(typ_e,name,expr));
+	self.map(decl.index,start.start);
+	Ok(decl)
+    }
+
     /// Parse a type declaration of the from `type name is type;`.
-    pub fn parse_decl_type(&'b mut self) -> Result<Decl> {
+    pub fn parse_decl_type(&mut self) -> Result<Decl> {
 	// "type"
 	let start = self.snap(TokenType::Type)?;
 	// Identifier
@@ -90,50 +345,65 @@ where 'a :'b, F : FnMut(usize,&'a str) {
 	// Type
 	let typ_e = self.parse_type()?;
 	// Semi-colon
-	let end = self.snap(TokenType::SemiColon)?;
-	// Extract corresponding (sub)slice
-	let slice = &self.lexer.input[start.start .. end.end()];
-	// Apply source map
-	//let attr = (self.mapper)(slice);
+	self.snap(TokenType::SemiColon)?;
+	// Bring alias into scope
+	self.declare_alias(name,typ_e);
 	// Done
-	Ok(Decl::new(self.ast,Node::TypeDecl(name,typ_e)))
+	let decl = Decl::new(self.ast,Node::TypeDecl(name,typ_e));
+	self.map(decl.index,start.start);
+	Ok(decl)
     }
 
     /// Parse a method declaration of the form `Type name([Type
-    /// Identifier]*) Stmt.Block`.
-    pub fn parse_decl_method(&'b mut self) -> Result<Decl> {
+    /// Identifier]*) Stmt.Block`.  The return type may also be given
+    /// as `var`, in which case it is inferred by the type checker.
+    pub fn parse_decl_method(&mut self) -> Result<Decl> {
+	let start = self.lexer.peek().start;
 	// Type
-	let ret_type = self.parse_type()?;
+	let ret_type = if self.snap(TokenType::Var).is_ok() {
+	    self.new_type(Node::InferredType)
+	} else {
+	    self.parse_type()?
+	};
 	// Identifier
 	let name = self.parse_identifier()?;
 	// "(" [Type Identifier]+ ")"
 	let params = self.parse_decl_parameters()?;
 	// "{" [Stmt]* "}"
 	let body = self.parse_stmt_block()?;
-	// // Apply source map
-	// //let attr = (self.mapper)("test");
 	//
-	Ok(Decl::new(self.ast,Node::MethodDecl(name,ret_type,params,body)))
+	let decl = Decl::new(self.ast,Node::MethodDecl(name,ret_type,params,body));
+	self.map(decl.index,start);
+	Ok(decl)
     }
 
     /// Parse a list of parameter declarations
     pub fn parse_decl_parameters(&mut self) -> Result<Vec<Parameter>> {
     	let mut params : Vec<Parameter> = vec![];
     	// "("
-    	self.snap(TokenType::LeftBrace)?;
+    	let open = self.snap(TokenType::LeftBrace)?;
     	// Keep going until a right brace
     	while self.snap(TokenType::RightBrace).is_err() {
+    	    // Check for unterminated list
+    	    self.check_not_eof(open,"unclosed '(' opened here")?;
     	    // Check if first time or not
     	    if !params.is_empty() {
     		// Not first time, so match comma
     		self.snap(TokenType::Comma)?;
+    		// Allow trailing comma
+    		if self.snap(TokenType::RightBrace).is_ok() { break; }
     	    }
     	    // Type
     	    let f_type = self.parse_type()?;
     	    // Identifier
+    	    let start = self.lexer.peek().start;
     	    let f_name = self.parse_identifier()?;
+    	    // Record this occurrence separately from the (shared) name
+    	    let content = f_name.as_str(self.ast).to_string();
+    	    let f_site = Name::new(self.ast,&content);
+    	    self.map(f_site.0,start);
     	    //
-    	    params.push(Parameter{declared:f_type,name:f_name});
+    	    params.push(Parameter{declared:f_type,name:f_name,site:f_site});
     	}
     	// Done
     	Ok(params)
@@ -144,28 +414,156 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     // =========================================================================
 
     /// Parse a block of zero or more statements surrounded by curly
-    /// braces.  For example, `{ int x = 1; x = x + 1; }`.
+    /// braces.  For example, `{ int x = 1; x = x + 1; }`.  Empty
+    /// statements (i.e. a lone `;`) are permitted, but are discarded
+    /// rather than added to the block.
     pub fn parse_stmt_block(&mut self) -> Result<Stmt> {
     	let mut stmts : Vec<Stmt> = Vec::new();
     	// "{"
-    	self.snap(TokenType::LeftCurly)?;
+    	let open = self.snap(TokenType::LeftCurly)?;
+    	// Aliases declared in the block are local to it
+    	let scope = self.aliases.len();
     	// Keep going until a right curly
     	while self.snap(TokenType::RightCurly).is_err() {
-    	    stmts.push(self.parse_stmt()?);
+    	    // Check for unterminated block
+    	    self.check_not_eof(open,"unclosed '{' opened here")?;
+    	    // Skip empty statements (i.e. stray ";")
+    	    if self.snap(TokenType::SemiColon).is_ok() {
+    		continue;
+    	    }
+    	    let start = self.lexer.peek().start;
+    	    let stmt = match self.parse_stmt() {
+    		Ok(stmt) => stmt,
+    		Err(err) => self.recover(err,start)
+    	    };
+    	    stmts.push(stmt);
     	}
+    	self.aliases.truncate(scope);
     	// Done
-    	Ok(Stmt::new(self.ast,Node::BlockStmt(stmts)))
+    	let stmt = Stmt::new(self.ast,Node::BlockStmt(stmts));
+    	self.map(stmt.0,open.start);
+    	Ok(stmt)
     }
 
-    /// Parse an arbitrary statement.
+    /// Parse an arbitrary statement.  Errors recovered from within a
+    /// nested block are not reported here, but by the enclosing
+    /// declaration (see `parse_decl`).
     pub fn parse_stmt(&mut self) -> Result<Stmt> {
+    	self.enter()?;
+    	let stmt = self.parse_stmt_inner();
+    	self.depth -= 1;
+    	stmt
+    }
+
+    fn parse_stmt_inner(&mut self) -> Result<Stmt> {
     	let lookahead = self.lexer.peek();
     	//
     	match lookahead.kind {
+    	    TokenType::If => self.parse_stmt_if(),
+    	    TokenType::LeftCurly => self.parse_stmt_block(),
+    	    TokenType::Switch => self.parse_stmt_switch(),
+    	    TokenType::For => self.parse_stmt_for(),
+    	    TokenType::While => self.parse_stmt_while(),
     	    _ => self.parse_unit_stmt()
     	}
     }
 
+    /// Parse an if statement, such as `if (x < y) { ... }` or `if b {
+    /// ... } else { ... }`.
+    pub fn parse_stmt_if(&mut self) -> Result<Stmt> {
+    	// "if"
+    	let start = self.snap(TokenType::If)?;
+    	// Expr
+    	let cond = self.parse_expr()?;
+    	// Stmt.Block
+    	let tt = self.parse_stmt_block()?;
+    	// ["else" (Stmt.If | Stmt.Block)]
+    	let ff = if self.snap(TokenType::Else).is_err() {
+    	    None
+    	} else if self.lexer.peek().kind == TokenType::If {
+    	    // NOTE: an "else if" is represented directly as an if
+    	    // statement in the else branch, rather than being wrapped
+    	    // in a block.
+    	    Some(self.parse_stmt_if()?)
+    	} else {
+    	    Some(self.parse_stmt_block()?)
+    	};
+    	// Done
+    	let stmt = Stmt::new(self.ast,Node::IfStmt(cond,tt,ff));
+    	self.map(stmt.0,start.start);
+    	Ok(stmt)
+    }
+
+    /// Parse a switch statement, such as `switch b { case true: {
+    /// ... } default: { ... } }`.  A case without a label represents
+    /// the default case.
+    pub fn parse_stmt_switch(&mut self) -> Result<Stmt> {
+    	// "switch"
+    	let start = self.snap(TokenType::Switch)?;
+    	// Expr
+    	let expr = self.parse_expr()?;
+    	// "{"
+    	let open = self.snap(TokenType::LeftCurly)?;
+    	let mut cases = Vec::new();
+    	// (("case" Expr | "default") ":" Stmt.Block)*
+    	while self.snap(TokenType::RightCurly).is_err() {
+    	    self.check_not_eof(open,"unclosed '{' opened here")?;
+    	    let label = if self.snap(TokenType::Default).is_ok() {
+    		None
+    	    } else {
+    		self.snap(TokenType::Case)?;
+    		Some(self.parse_expr()?)
+    	    };
+    	    self.snap(TokenType::Colon)?;
+    	    let body = self.parse_stmt_block()?;
+    	    cases.push((label,body));
+    	}
+    	// Done
+    	let stmt = Stmt::new(self.ast,Node::SwitchStmt(expr,cases));
+    	self.map(stmt.0,start.start);
+    	Ok(stmt)
+    }
+
+    /// Parse a for statement, such as `for (i32 i = 0; i < n; i++) {
+    /// ... }`.  The initialiser and update are simple statements and,
+    /// hence, are not themselves terminated by a ";".
+    pub fn parse_stmt_for(&mut self) -> Result<Stmt> {
+    	// "for"
+    	let start = self.snap(TokenType::For)?;
+    	// "("
+    	self.snap(TokenType::LeftBrace)?;
+    	// Stmt.Simple ";"
+    	let init = self.parse_simple_stmt()?;
+    	self.snap(TokenType::SemiColon)?;
+    	// Expr ";"
+    	let cond = self.parse_expr()?;
+    	self.snap(TokenType::SemiColon)?;
+    	// Stmt.Simple
+    	let update = self.parse_simple_stmt()?;
+    	// ")"
+    	self.snap(TokenType::RightBrace)?;
+    	// Stmt.Block
+    	let body = self.parse_stmt_block()?;
+    	// Done
+    	let stmt = Stmt::new(self.ast,Node::ForStmt(init,cond,update,body));
+    	self.map(stmt.0,start.start);
+    	Ok(stmt)
+    }
+
+    /// Parse a while statement, such as `while (x < y) { ... }`.
+    pub fn parse_stmt_while(&mut self) -> Result<Stmt> {
+    	// "while"
+    	let start = self.snap(TokenType::While)?;
+    	// Expr
+    	let cond = self.parse_expr()?;
+    	// Stmt.Block
+    	let body = self.parse_stmt_block()?;
+    	// Done
+    	let stmt = Stmt::new(self.ast,Node::WhileStmt(cond,body));
+    	self.map(stmt.0,start.start);
+    	Ok(stmt)
+    }
+
     /// Parse a unit statement.  This one which does not contain other
     /// statements, and is terminated with a ";".
     pub fn parse_unit_stmt(&mut self) -> Result<Stmt> {
@@ -175,17 +573,123 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     	    TokenType::Assert => {
     	    	self.parse_stmt_assert()
     	    }
+    	    TokenType::Break => {
+    		self.parse_stmt_break()
+    	    }
+    	    TokenType::Continue => {
+    		self.parse_stmt_continue()
+    	    }
+    	    TokenType::Print => {
+    	    	self.parse_stmt_print()
+    	    }
+    	    TokenType::Return => {
+    		self.parse_stmt_return()
+    	    }
     	    TokenType::Skip => {
     		self.parse_stmt_skip()
     	    }
+    	    TokenType::Type => {
+    		self.parse_stmt_type()
+    	    }
     	    _ => {
-    		return Err(Error::new(lookahead,"unknown token encountered"));
+    		// NOTE: simple statements apply their own source map.
+    		let stmt = self.parse_simple_stmt()?;
+    		self.snap(TokenType::SemiColon)?;
+    		return Ok(stmt);
     	    }
-    	};
+    	}?;
+    	// Apply source map (excluding ";")
+    	self.map(stmt.0,lookahead.start);
     	// ";"
     	self.snap(TokenType::SemiColon)?;
     	// Done
-    	stmt
+    	Ok(stmt)
+    }
+
+    /// Parse a simple statement, which is either a variable
+    /// declaration or an assignment (including increments and
+    /// decrements).  Unlike a unit statement, this does not consume a
+    /// terminating ";" and, hence, can be used in positions such as
+    /// the initialiser of a `for` loop.
+    pub fn parse_simple_stmt(&mut self) -> Result<Stmt> {
+    	let lookahead = self.lexer.peek();
+    	//
+    	let stmt = match lookahead.kind {
+    	    TokenType::Identifier if self.lookup_alias(lookahead.content).is_some() => {
+    		self.parse_stmt_vardecl()
+    	    }
+    	    TokenType::Identifier => {
+    		self.parse_stmt_assign()
+    	    }
+    	    TokenType::MinusMinus|TokenType::PlusPlus => {
+    		self.parse_stmt_prefix()
+    	    }
+    	    kind if is_type_start(kind) => {
+    		self.parse_stmt_vardecl()
+    	    }
+    	    _ => {
+    		return Err(self.error(lookahead,"unknown token encountered"));
+    	    }
+    	}?;
+    	// Apply source map
+    	self.map(stmt.0,lookahead.start);
+    	// Done
+    	Ok(stmt)
+    }
+
+    /// Parse a local type alias, such as `type t = i32`.  The alias is
+    /// in scope for the remainder of the enclosing block.
+    pub fn parse_stmt_type(&mut self) -> Result<Stmt> {
+    	// "type"
+    	self.snap(TokenType::Type)?;
+    	// Identifier
+    	let name = self.parse_identifier()?;
+    	// "="
+    	self.snap(TokenType::Equal)?;
+    	// Type
+    	let t = self.parse_type()?;
+    	// Bring alias into scope
+    	self.declare_alias(name,t);
+    	// Done
+    	Ok(Stmt::new(self.ast,Node::TypeAliasStmt(name,t)))
+    }
+
+    /// Parse a variable declaration, such as `i32 x = 0`.
+    pub fn parse_stmt_vardecl(&mut self) -> Result<Stmt> {
+    	// Type
+    	let t = self.parse_type()?;
+    	// Identifier
+    	let name = self.parse_identifier()?;
+    	// "="
+    	self.snap(TokenType::Equal)?;
+    	// Expr
+    	let init = self.parse_expr()?;
+    	// Done
+    	Ok(Stmt::new(self.ast,Node::VarDeclStmt(t,name,init)))
+    }
+
+    /// Parse an assignment, such as `x = y + 1`, or a postfix increment
+    /// or decrement, such as `x++`.
+    pub fn parse_stmt_assign(&mut self) -> Result<Stmt> {
+    	// LVal
+    	let lval = self.parse_lval()?;
+    	// "=" Expr | "++" | "--"
+    	let op = self.lexer.peek();
+    	//
+    	match op.kind {
+    	    TokenType::Equal => {
+    		self.advance();
+    		let rhs = self.parse_expr()?;
+    		Ok(Stmt::new(self.ast,Node::AssignStmt(lval,rhs)))
+    	    }
+    	    TokenType::MinusMinus|TokenType::PlusPlus => {
+    		self.advance();
+    		Ok(self.desugar_increment(op,lval))
+    	    }
+    	    _ => {
+    		Err(self.error(op,"expected '=', '++' or '--'"))
+    	    }
+    	}
     }
 
     pub fn parse_stmt_assert(&mut self) -> Result<Stmt> {
@@ -197,6 +701,73 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     	Ok(Stmt::new(self.ast,Node::AssertStmt(expr)))
     }
 
+    /// Parse a print statement, such as `print x + 1`, which outputs
+    /// the value of the given expression when executed.
+    pub fn parse_stmt_print(&mut self) -> Result<Stmt> {
+    	// "print"
+    	self.snap(TokenType::Print)?;
+    	// Expr
+    	let expr = self.parse_expr()?;
+    	// Done
+    	Ok(Stmt::new(self.ast,Node::PrintStmt(expr)))
+    }
+
+    /// Parse a prefix increment or decrement statement, such as `++x`
+    /// or `--x`.  Note that these are only supported in statement
+    /// position (i.e. not within larger expressions) to avoid issues
+    /// around precedence and side-effects.
+    pub fn parse_stmt_prefix(&mut self) -> Result<Stmt> {
+    	// "++" | "--"
+    	let op = self.advance();
+    	// LVal
+    	let lval = self.parse_lval()?;
+    	// Done
+    	Ok(self.desugar_increment(op,lval))
+    }
+
+    /// Desugar an increment (or decrement) of a given lval `x` into
+    /// the assignment `x = x + 1` (resp. `x = x - 1`).
+    fn desugar_increment(&mut self, op: Token<'a>, lhs: Expr) -> Stmt {
+	let var = match self.ast.get(lhs.0) {
+	    Node::VarExpr(name) => Expr::new(self.ast,Node::VarExpr(*name)),
+	    _ => Expr(clone_subtree(self.ast,lhs.0))
+	};
+	let one = Expr::new(self.ast,Node::IntExpr(false,1));
+	let rhs = match op.kind {
+	    TokenType::PlusPlus => Expr::new(self.ast,Node::AddExpr(var,one)),
+	    _ => Expr::new(self.ast,Node::SubtractExpr(var,one))
+	};
+	Stmt::new(self.ast,Node::AssignStmt(lhs,rhs))
+    }
+
+    /// Parse a return statement, such as `return;` or `return x;`.
+    pub fn parse_stmt_return(&mut self) -> Result<Stmt> {
+    	// "return"
+    	self.snap(TokenType::Return)?;
+    	// [Expr]
+    	let expr = if self.lexer.peek().kind == TokenType::SemiColon {
+    	    None
+    	} else {
+    	    Some(self.parse_expr()?)
+    	};
+    	// Done
+    	Ok(Stmt::new(self.ast,Node::ReturnStmt(expr)))
+    }
+
+    pub fn parse_stmt_break(&mut self) -> Result<Stmt> {
+    	// "break"
+    	self.snap(TokenType::Break)?;
+    	// Done
+    	Ok(Stmt::new(self.ast,Node::BreakStmt))
+    }
+
+    pub fn parse_stmt_continue(&mut self) -> Result<Stmt> {
+    	// "continue"
+    	self.snap(TokenType::Continue)?;
+    	// Done
+    	Ok(Stmt::new(self.ast,Node::ContinueStmt))
+    }
+
     pub fn parse_stmt_skip(&mut self) -> Result<Stmt> {
     	// "skip"
     	self.snap(TokenType::Skip)?;
@@ -209,20 +780,39 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     // =========================================================================
 
     pub fn parse_expr(&mut self) -> Result<Expr> {
-    	let lhs = self.parse_expr_term()?;
-	// Check for binary expression
-    	let lookahead = self.lexer.peek();
-	//
-	match lookahead.kind {
-	    TokenType::LeftAngle => {
-		self.lexer.next();
-		let rhs = self.parse_expr_term()?;
-		Ok(Expr::new(self.ast,Node::LessThanExpr(lhs,rhs)))
-	    }
-	    _ => {
-		Ok(lhs)
-	    }
-	}
+    	self.enter()?;
+    	let expr = self.parse_expr_binary(0);
+    	self.depth -= 1;
+    	expr
+    }
+
+    /// Parse a binary expression, such as `x + 1` or `a || b && c`,
+    /// whose operators all have at least a given precedence.  This
+    /// uses precedence climbing, driven by the table of operators
+    /// (see `BINARY_OPERATORS`).  For example, `a || b && c` is parsed
+    /// as `a || (b && c)`, whilst `x - y + z` is parsed as `(x - y) +
+    /// z`.
+    fn parse_expr_binary(&mut self, min: u8) -> Result<Expr> {
+    	let start = self.lexer.peek().start;
+    	let mut lhs = self.parse_expr_term()?;
+    	//
+    	loop {
+    	    let lookahead = self.lexer.peek();
+    	    let (prec,assoc,ctor) = match binary_operator(lookahead.kind) {
+    		Some((prec,assoc,ctor)) if prec >= min => (prec,assoc,ctor),
+    		_ => { return Ok(lhs); }
+    	    };
+    	    self.advance();
+    	    // Operands on the right must bind more tightly
+    	    let rhs = self.parse_expr_binary(prec + 1)?;
+    	    lhs = Expr::new(self.ast,ctor(lhs,rhs));
+    	    self.map(lhs.0,start);
+    	    // Reject chains such as `a < b < c` or `a == b == c`
+    	    let next = self.lexer.peek();
+    	    if assoc == Assoc::None && is_relational(next.kind) {
+    		return Err(self.error(next,"comparison operators cannot be chained; use parentheses"));
+    	    }
+    	}
     }
 
     pub fn parse_expr_term(&mut self) -> Result<Expr> {
@@ -230,49 +820,184 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     	//
     	let expr = match lookahead.kind {
     	    TokenType::False => {
-    		self.lexer.next();
+    		self.advance();
     		Expr::new(self.ast,Node::BoolExpr(false))
     	    }
 	    TokenType::Identifier => {
-		let n = self.parse_identifier();
-		Expr::new(self.ast,Node::VarExpr(n.unwrap()))
+		let n = self.parse_identifier()?;
+		// Check for method call
+		if self.lexer.peek().kind == TokenType::LeftBrace {
+		    let args = self.parse_expr_arguments()?;
+		    Expr::new(self.ast,Node::CallExpr(n,args))
+		} else {
+		    Expr::new(self.ast,Node::VarExpr(n))
+		}
 	    }
     	    TokenType::Integer => {
-    	    	self.lexer.next();
-		Expr::new(self.ast,Node::IntExpr(lookahead.as_int()))
+    	    	self.advance();
+		let i = self.int_literal(lookahead,lookahead,false)?;
+		Expr::new(self.ast,Node::IntExpr(false,i))
     	    }
     	    TokenType::LeftBrace => {
     	    	return self.parse_expr_bracketed()
     	    }
+    	    TokenType::Minus => {
+    	    	// "-" Integer
+    	    	self.advance();
+    	    	let tok = self.snap(TokenType::Integer)?;
+		let i = self.int_literal(lookahead,tok,true)?;
+    	    	Expr::new(self.ast,Node::IntExpr(true,i))
+    	    }
+    	    TokenType::Plus => {
+    	    	// "+" Integer (which is a no-op)
+    	    	self.advance();
+    	    	let tok = self.snap(TokenType::Integer)?;
+		let i = self.int_literal(lookahead,tok,false)?;
+    	    	Expr::new(self.ast,Node::IntExpr(false,i))
+    	    }
+    	    TokenType::Len => {
+    	    	return self.parse_expr_len()
+    	    }
+    	    TokenType::Null => {
+    		self.advance();
+    		Expr::new(self.ast,Node::NullExpr)
+    	    }
+    	    TokenType::Shreak => {
+    	    	// "!" Term
+    	    	self.advance();
+    	    	self.enter()?;
+    	    	let e = self.parse_expr_term();
+    	    	self.depth -= 1;
+    	    	let e = e?;
+    	    	let expr = Expr::new(self.ast,Node::NotExpr(e));
+    	    	self.map(expr.0,lookahead.start);
+    	    	return Ok(expr);
+    	    }
+    	    TokenType::Sizeof => {
+    	    	return self.parse_expr_sizeof()
+    	    }
     	    TokenType::True => {
-    		self.lexer.next();
+    		self.advance();
     		Expr::new(self.ast,Node::BoolExpr(true))
     	    }
     	    _ => {
-    		return Err(Error::new(lookahead,"unknown token encountered"))
+    		return Err(self.error(lookahead,"unknown token encountered"))
     	    }
     	};
-    	//
-    	Ok(expr)
+    	// Apply source map
+    	self.map(expr.0,lookahead.start);
+    	// Check for field accesses
+    	self.parse_expr_postfix(expr,lookahead.start)
+    }
+
+    /// Parse the arguments of a method call, such as `(1, x)`.
+    pub fn parse_expr_arguments(&mut self) -> Result<Vec<Expr>> {
+    	let mut args : Vec<Expr> = vec![];
+    	// "("
+    	let open = self.snap(TokenType::LeftBrace)?;
+    	// Keep going until a right brace
+    	while self.snap(TokenType::RightBrace).is_err() {
+    	    // Check for unterminated list
+    	    self.check_not_eof(open,"unclosed '(' opened here")?;
+    	    // Check if first time or not
+    	    if !args.is_empty() {
+    		// Not first time, so match comma
+    		self.snap(TokenType::Comma)?;
+    	    }
+    	    args.push(self.parse_expr()?);
+    	}
+    	// Done
+    	Ok(args)
+    }
+
+    /// Parse any field or array accesses following a given term, such
+    /// as `r.f`, `r.f.g` or `xs[i].f`.
+    pub fn parse_expr_postfix(&mut self, mut expr: Expr, start: usize) -> Result<Expr> {
+    	// ("." Identifier | "[" Expr "]")*
+    	loop {
+    	    let node = if self.snap(TokenType::Dot).is_ok() {
+    		let name = self.parse_identifier()?;
+    		Node::FieldAccessExpr(expr,name)
+    	    } else if self.snap(TokenType::LeftSquare).is_ok() {
+    		let index = self.parse_expr()?;
+    		self.snap(TokenType::RightSquare)?;
+    		Node::ArrayAccessExpr(expr,index)
+    	    } else {
+    		return Ok(expr);
+    	    };
+    	    expr = Expr::new(self.ast,node);
+    	    self.map(expr.0,start);
+    	}
     }
 
     pub fn parse_expr_bracketed(&mut self) -> Result<Expr> {
     	// "("
     	self.snap(TokenType::LeftBrace)?;
     	// Expr
-    	let expr = self.parse_expr();
+    	let expr = self.parse_expr()?;
     	// ")"
     	self.snap(TokenType::RightBrace)?;
     	//
-    	expr
+    	Ok(expr)
+    }
+
+    /// Parse a `sizeof` expression, such as `sizeof(i32)` or
+    /// `sizeof({bool f, u8[] g})`.
+    pub fn parse_expr_sizeof(&mut self) -> Result<Expr> {
+    	// "sizeof"
+    	let start = self.snap(TokenType::Sizeof)?;
+    	// "("
+    	self.snap(TokenType::LeftBrace)?;
+    	// Type
+    	let typ_e = self.parse_type()?;
+    	// ")"
+    	self.snap(TokenType::RightBrace)?;
+    	// Done
+    	let expr = Expr::new(self.ast,Node::SizeofExpr(typ_e));
+    	self.map(expr.0,start.start);
+    	Ok(expr)
+    }
+
+    /// Parse a `len` expression, such as `len(xs)` or `len(r.f)`.
+    pub fn parse_expr_len(&mut self) -> Result<Expr> {
+    	// "len"
+    	let start = self.snap(TokenType::Len)?;
+    	// "("
+    	self.snap(TokenType::LeftBrace)?;
+    	// Expr
+    	let e = self.parse_expr()?;
+    	// ")"
+    	self.snap(TokenType::RightBrace)?;
+    	// Done
+    	let expr = Expr::new(self.ast,Node::LenExpr(e));
+    	self.map(expr.0,start.start);
+    	Ok(expr)
+    }
+
+    /// Parse an expression which can be assigned to.  This is either
+    /// a variable (e.g. `x`), or a field of an lval (e.g. `x.f.g`).
+    pub fn parse_lval(&mut self) -> Result<Expr> {
+	let start = self.lexer.peek().start;
+	let name = self.parse_identifier()?;
+	let expr = Expr::new(self.ast,Node::VarExpr(name));
+	self.map(expr.0,start);
+	// ("." Identifier)*
+	self.parse_expr_postfix(expr,start)
     }
 
     // =========================================================================
     // Types
     // =========================================================================
 
+    /// Parse an arbitrary type.  Note that the array suffix `[]` binds
+    /// more tightly than the reference prefix `&`.  Thus, `&i32[]` is
+    /// a reference to an array (i.e. `&(i32[])`), whilst an array of
+    /// references must be bracketed (i.e. `(&i32)[]`).
     pub fn parse_type(&mut self) -> Result<Type> {
-	self.parse_type_compound()
+	self.enter()?;
+	let t = self.parse_type_compound();
+	self.depth -= 1;
+	t
     }
 
     pub fn parse_type_compound(&mut self) -> Result<Type> {
@@ -281,38 +1006,43 @@ where 'a :'b, F : FnMut(usize,&'a str) {
 	match lookahead.kind {
 	    TokenType::EOF => {
 		// Something went wrong
-		Err(Error::new(lookahead,"unexpected end-of-file"))
+		Err(self.error(lookahead,"unexpected end-of-file"))
 	    }
-	    TokenType::Ampersand => {
+	    TokenType::Ampersand|TokenType::AmpersandAmpersand => {
 	    	// Looks like a reference type
 	    	self.parse_type_ref()
 	    }
-	    TokenType::LeftCurly => {
-	    	// Looks like a record type
-	    	self.parse_type_record()
-	    }
 	    _ => {
-	    	// Could be an array type
+	    	// Could be an array type (including of records)
 	    	self.parse_type_array()
 	    }
 	}
     }
 
-    /// Parse a reference type, such as `&i32`, `&(i32[])`, `&&u16`,
-    /// etc.
+    /// Parse a reference type, such as `&i32`, `&i32[]`, `&&u16`,
+    /// etc.  Since array suffixes bind more tightly, the element of a
+    /// reference may itself be an array.
     pub fn parse_type_ref(&mut self) -> Result<Type> {
-    	let mut n = 1;
+    	let mut n = 0;
     	// "&"
-    	self.snap(TokenType::Ampersand)?;
-    	// Check for nested references
-    	while self.snap(TokenType::Ampersand).is_ok() {
-    	    n = n + 1;
+    	if self.lexer.peek().kind != TokenType::AmpersandAmpersand {
+    	    self.snap(TokenType::Ampersand)?;
+    	    n = 1;
+    	}
+    	// Check for nested references (noting "&&" is lexed as one token)
+    	loop {
+    	    match self.lexer.peek().kind {
+    		TokenType::Ampersand => n += 1,
+    		TokenType::AmpersandAmpersand => n += 2,
+    		_ => break
+    	    }
+    	    self.advance();
     	}
     	// Type
-    	let mut t = self.parse_type_bracketed()?;
+    	let mut t = self.parse_type_array()?;
     	// Unwind references
     	for i in 0..n {
-            t = Type::new(self.ast,Node::ReferenceType(t));
+            t = self.new_type(Node::ReferenceType(t));
     	}
     	// Done
     	Ok(t)
@@ -323,13 +1053,17 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     pub fn parse_type_record(&mut self) -> Result<Type> {
     	let mut fields : Vec<(Type,Name)> = vec![];
     	// "{"
-    	self.snap(TokenType::LeftCurly)?;
+    	let open = self.snap(TokenType::LeftCurly)?;
     	// Keep going until a right brace
     	while self.snap(TokenType::RightCurly).is_err() {
+    	    // Check for unterminated record
+    	    self.check_not_eof(open,"unclosed '{' opened here")?;
     	    // Check if first time or not
     	    if !fields.is_empty() {
     		// Not first time, so match comma
     		self.snap(TokenType::Comma)?;
+    		// Allow trailing comma
+    		if self.snap(TokenType::RightCurly).is_ok() { break; }
     	    }
     	    // Type
     	    let f_type = self.parse_type()?;
@@ -339,17 +1073,22 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     	    fields.push((f_type,f_name));
     	}
     	// Done
-    	Ok(Type::new(self.ast,Node::RecordType(fields)))
+    	Ok(self.new_type(Node::RecordType(fields)))
     }
 
     /// Parse an array type, such as `i32[]`, `bool[][]`, etc.
-    pub fn parse_type_array(&'b mut self) -> Result<Type> {
+    pub fn parse_type_array(&mut self) -> Result<Type> {
     	// Type
     	let mut t = self.parse_type_bracketed()?;
-    	// ([])*
+    	// ("[" [Expr] "]")*
     	while self.snap(TokenType::LeftSquare).is_ok() {
-    	    self.snap(TokenType::RightSquare)?;
-            t = Type::new(self.ast,Node::ArrayType(t));
+    	    if self.snap(TokenType::RightSquare).is_ok() {
+    		t = self.new_type(Node::ArrayType(t));
+    	    } else {
+    		let size = self.parse_expr()?;
+    		self.snap(TokenType::RightSquare)?;
+    		t = self.new_type(Node::SizedArrayType(t,size));
+    	    }
     	}
     	//
     	Ok(t)
@@ -357,7 +1096,7 @@ where 'a :'b, F : FnMut(usize,&'a str) {
 
     /// Parse a type which may (or may not) be bracketed.  For
     /// example, in `(&int)[]` the type `&int` is bracketed.
-    pub fn parse_type_bracketed(&'b mut self) -> Result<Type> {
+    pub fn parse_type_bracketed(&mut self) -> Result<Type> {
     	// Try and match bracket!
     	if self.snap(TokenType::LeftBrace).is_ok() {
     	    // Bingo!
@@ -371,53 +1110,45 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     	}
     }
 
-    pub fn parse_type_base(&'b mut self) -> Result<Type> {
+    pub fn parse_type_base(&mut self) -> Result<Type> {
 	let lookahead = self.lexer.peek();
 	// Look at what we've got!
 	let typ_e : Type = match lookahead.kind {
+	    TokenType::LeftCurly => {
+		return self.parse_type_record();
+	    }
 	    TokenType::Null => {
-		Type::new(self.ast,Node::NullType)
+		self.new_type(Node::NullType)
 	    }
 	    //
 	    TokenType::Bool => {
-                Type::new(self.ast,Node::BoolType)
+                self.new_type(Node::BoolType)
 	    }
 	    //
-	    TokenType::I8 => {
-                Type::new(self.ast,Node::IntType(true,8))
-	    }
-	    TokenType::I16 => {
-                Type::new(self.ast,Node::IntType(true,16))
-	    }
-	    TokenType::I32 => {
-                Type::new(self.ast,Node::IntType(true,32))
-	    }
-	    TokenType::I64 => {
-                Type::new(self.ast,Node::IntType(true,64))
+	    kind if int_token_to_type(kind).is_some() => {
+		let (signed,width) = int_token_to_type(kind).unwrap();
+                self.new_type(Node::IntType(signed,width))
 	    }
 	    //
-	    TokenType::U8 => {
-                Type::new(self.ast,Node::IntType(false,8))
-	    }
-	    TokenType::U16 => {
-                Type::new(self.ast,Node::IntType(false,16))
-	    }
-	    TokenType::U32 => {
-                Type::new(self.ast,Node::IntType(false,32))
-	    }
-	    TokenType::U64 => {
-                Type::new(self.ast,Node::IntType(false,64))
+	    TokenType::Void => {
+                self.new_type(Node::VoidType)
 	    }
 	    //
-	    TokenType::Void => {
-                Type::new(self.ast,Node::VoidType)
+	    TokenType::Identifier => {
+		match self.lookup_alias(lookahead.content) {
+		    Some(t) => t,
+		    None => {
+			let msg = format!("unknown type '{}'",lookahead.content);
+			return Err(self.error(lookahead,&msg));
+		    }
+		}
 	    }
 	    _ => {
-		return Err(Error::new(lookahead,"unknown token encountered"));
+		return Err(self.error(lookahead,"unknown token encountered"));
 	    }
 	};
 	// Move over it
-	self.lexer.next();
+	self.advance();
 	//
 	Ok(typ_e)
     }
@@ -426,21 +1157,184 @@ where 'a :'b, F : FnMut(usize,&'a str) {
     // Misc
     // =========================================================================
 
+    /// Parse an identifier, giving the name it represents.  Every use
+    /// of the same identifier gives the same node on the tree and,
+    /// hence, names can be compared (and hashed) by index.  Such a
+    /// node is source mapped to the first use of the identifier.
     pub fn parse_identifier(&mut self) -> Result<Name> {
+	let lookahead = self.lexer.peek();
+	// Check for (reserved) keywords
+	if lookahead.kind.is_keyword() {
+	    let msg = format!("'{}' is a reserved keyword and cannot be used as a name",lookahead.content);
+	    return Err(Error::new(lookahead,&msg));
+	}
 	let tok = self.snap(TokenType::Identifier)?;
-	// FIXME: should employ cache!
-	Ok(Name::new(self.ast,&tok.content))
+	// Reuse the node for this name, if there is one
+	if let Some(name) = self.env.get(tok.content) {
+	    return Ok(*name);
+	}
+	let name = Name::new(self.ast,&tok.content);
+	self.env.insert(tok.content.to_string(),name);
+	// Map name so errors can be reported against it
+	self.map(name.0,tok.start);
+	Ok(name)
     }
 
     // =========================================================================
     // Helpers
     // =========================================================================
 
-    // fn source_attr(&self, first : Token<'a>, last: Token<'a>) -> Attributes {
-    // 	let start = first.start;
-    // 	let end = last.end();
-    // 	Attributes{start,end}
-    // }
+    /// Record the source span of a given node in the source map.  The
+    /// span runs from a given starting offset to the end of the last
+    /// token consumed.
+    fn map(&mut self, index: usize, start: usize) {
+	let input = self.lexer.input;
+	let end = self.last.map_or(0,|t| t.end());
+	(self.mapper)(index,&input[start..end]);
+    }
+
+    /// Consume the next token in the stream, whilst keeping track of
+    /// it (for use with the source map and error spans).
+    fn advance(&mut self) -> Token<'a> {
+	let tok = self.lexer.next();
+	// NOTE: the EOF token has no meaningful end
+	if tok.kind != TokenType::EOF {
+	    self.last = Some(tok);
+	}
+	match tok.kind {
+	    TokenType::LeftCurly => { self.nesting += 1; }
+	    TokenType::RightCurly => { self.nesting = self.nesting.saturating_sub(1); }
+	    _ => {}
+	}
+	tok
+    }
+
+    /// Determine the magnitude of an integer literal, which starts
+    /// from a given token (e.g. a leading `-`) and whose digits are
+    /// held in another.  A literal which lies outside the range of
+    /// every integer type is reported as an error (e.g. `-2147483648`
+    /// is fine, but `-170141183460469231731687303715884105729` is
+    /// not).  Whether a literal fits the type expected of it is left
+    /// to the type checker.
+    fn int_literal(&self, first: Token<'a>, tok: Token<'a>, negative: bool) -> Result<u128> {
+	match tok.as_int() {
+	    Some(m) if !negative || m <= 1u128 << 127 => Ok(m),
+	    _ => Err(self.error_from(first,"integer literal out of range"))
+	}
+    }
+
+    /// Construct an error about a construct spanning several tokens,
+    /// starting from a given token and ending at the last token
+    /// consumed.  For example, an error about the type `&i32[]`
+    /// covers the whole type, rather than just the `&`.
+    fn error_from(&self, first: Token<'a>, message: &str) -> Error {
+	let last = self.last.unwrap_or(first);
+	Error::span_from(first,last,message)
+    }
+
+    /// Construct an error for a given token.  If this is the end of
+    /// the input, then the error is positioned at the end of the input
+    /// (since the `EOF` token itself has no meaningful position).
+    fn error(&self, tok: Token<'a>, message: &str) -> Error {
+	if tok.kind == TokenType::EOF {
+	    Error::at_eof(self.lexer.input.len(),message)
+	} else {
+	    Error::new(tok,message)
+	}
+    }
+
+    /// Enter a nested construct, checking the recursion limit has not
+    /// been exceeded.  The caller is responsible for decrementing the
+    /// depth on exit.
+    fn enter(&mut self) -> Result<()> {
+	if self.depth >= self.limit {
+	    let lookahead = self.lexer.peek();
+	    return Err(self.error(lookahead,"input nested too deeply"));
+	}
+	self.depth += 1;
+	Ok(())
+    }
+
+    /// Determine whether the parser has reached the end of the input
+    /// (i.e. there are no further tokens to consume).
+    pub fn is_at_end(&mut self) -> bool {
+	self.lexer.peek().kind == TokenType::EOF
+    }
+
+    /// Create a type node, unless a structurally identical one has
+    /// already been created (in which case that is returned).  Since
+    /// component types are themselves shared, comparing nodes
+    /// directly is sufficient.  Types are never source mapped, hence
+    /// sharing does not affect error reporting.
+    fn new_type(&mut self, node: Node) -> Type {
+	if let Some(t) = self.types.get(&node) {
+	    return *t;
+	}
+	let t = Type::new(self.ast,node.clone());
+	self.types.insert(node,t);
+	t
+    }
+
+    /// Recover from an error encountered whilst parsing a statement
+    /// (which began at a given position).  The error is recorded and
+    /// tokens are skipped up to (and including) the next `;`, or up to
+    /// (but not including) the next `}`.  A placeholder is returned in
+    /// place of the statement.
+    fn recover(&mut self, err: Error, start: usize) -> Stmt {
+	self.pending.push(err);
+	//
+	loop {
+	    match self.lexer.peek().kind {
+		TokenType::EOF|TokenType::RightCurly => break,
+		TokenType::SemiColon => {
+		    self.advance();
+		    break;
+		}
+		_ => {
+		    self.advance();
+		}
+	    }
+	}
+	let stmt = Stmt::new(self.ast,Node::Error);
+	self.map(stmt.0,start);
+	stmt
+    }
+
+    /// Bring a given type alias into scope, such that subsequent uses
+    /// of its name (in type position) resolve to the aliased type.
+    fn declare_alias(&mut self, name: Name, t: Type) {
+	let name = name.as_str(self.ast).to_string();
+	self.aliases.push((name,t));
+    }
+
+    /// Look up the type aliased by a given name, if it is in scope.
+    /// Inner aliases take precedence over outer ones.
+    fn lookup_alias(&self, name: &str) -> Option<Type> {
+	self.aliases.iter().rev().find(|(n,_)| n == name).map(|(_,t)| *t)
+    }
+
+    /// Check all input has been consumed, such that no tokens remain
+    /// following (for example) a parsed expression.
+    pub fn expect_end(&mut self) -> Result<()> {
+	let lookahead = self.lexer.peek();
+	if lookahead.kind == TokenType::EOF {
+	    Ok(())
+	} else {
+	    Err(self.error(lookahead,"unexpected token"))
+	}
+    }
+
+    /// Check we have not reached the end of the token stream whilst
+    /// looking for the closing delimiter of some construct.  If we
+    /// have, then an error is reported against the opening delimiter
+    /// (since the end-of-file has no meaningful location).
+    fn check_not_eof(&mut self, open: Token<'a>, message: &'static str) -> Result<()> {
+	if self.is_at_end() {
+	    Err(Error::new(open,message))
+	} else {
+	    Ok(())
+	}
+    }
 
     /// Match a given token type in the current stream.  If the kind
     /// matches, then the token stream advances.  Otherwise, it
@@ -451,12 +1345,79 @@ where 'a :'b, F : FnMut(usize,&'a str) {
 	// Check it!
 	if lookahead.kind == kind {
 	    // Accept it
-	    self.lexer.next();
+	    self.advance();
 	    //
 	    Ok(lookahead)
 	} else {
 	    // Reject
-	    Err(Error::new(lookahead,"expected one thing, found another"))
+	    Err(self.error(lookahead,"expected one thing, found another"))
 	}
     }
 }
+
+/// Determine the integer type (i.e. signedness and width) represented
+/// by a given token, or `None` if it does not represent one.
+fn int_token_to_type(kind: TokenType) -> Option<(bool,u8)> {
+    match kind {
+	TokenType::I8 => Some((true,8)),
+	TokenType::I16 => Some((true,16)),
+	TokenType::I32 => Some((true,32)),
+	TokenType::I64 => Some((true,64)),
+	TokenType::I128 => Some((true,128)),
+	TokenType::U8 => Some((false,8)),
+	TokenType::U16 => Some((false,16)),
+	TokenType::U32 => Some((false,32)),
+	TokenType::U64 => Some((false,64)),
+	TokenType::U128 => Some((false,128)),
+	_ => None
+    }
+}
+
+/// Determine whether a given token can begin a type.  This is used to
+/// distinguish variable declarations from other statements.
+fn is_type_start(kind: TokenType) -> bool {
+    match kind {
+	TokenType::Ampersand|TokenType::AmpersandAmpersand|TokenType::Bool|TokenType::LeftCurly
+	    |TokenType::Null|TokenType::Void => true,
+	_ => int_token_to_type(kind).is_some()
+    }
+}
+
+/// Identifies how a binary operator groups with others of the same
+/// precedence.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Assoc {
+    /// Groups to the left, such that `x - y + z` is `(x - y) + z`.
+    Left,
+    /// Cannot be chained, such that `a < b < c` is an error.
+    None
+}
+
+/// Constructs the node for a binary operator from its operands.
+pub type BinaryConstructor = fn(Expr,Expr) -> Node;
+
+/// The binary operators, along with their precedence (where higher
+/// binds more tightly), associativity and the node they construct.
+/// Adding a binary operator requires only a new entry here.
+const BINARY_OPERATORS : &[(TokenType,u8,Assoc,BinaryConstructor)] = &[
+    (TokenType::BarBar, 1, Assoc::Left, Node::OrExpr),
+    (TokenType::AmpersandAmpersand, 2, Assoc::Left, Node::AndExpr),
+    (TokenType::EqualEqual, 3, Assoc::None, Node::EqualsExpr),
+    (TokenType::ShreakEquals, 3, Assoc::None, Node::NotEqualsExpr),
+    (TokenType::LeftAngle, 3, Assoc::None, Node::LessThanExpr),
+    (TokenType::Plus, 4, Assoc::Left, Node::AddExpr),
+    (TokenType::Minus, 4, Assoc::Left, Node::SubtractExpr)
+];
+
+/// Determine the precedence, associativity and constructor of a given
+/// binary operator, or `None` if the token is not one.
+pub fn binary_operator(kind: TokenType) -> Option<(u8,Assoc,BinaryConstructor)> {
+    BINARY_OPERATORS.iter().find(|(k,..)| *k == kind).map(|(_,p,a,c)| (*p,*a,*c))
+}
+
+/// Determine whether a given token is a relational operator.  Such
+/// operators are non-associative and, hence, cannot be chained.
+fn is_relational(kind: TokenType) -> bool {
+    matches!(kind,TokenType::EqualEqual|TokenType::LeftAngle|TokenType::LeftAngleEquals
+	     |TokenType::RightAngle|TokenType::RightAndleEquals|TokenType::ShreakEquals)
+}
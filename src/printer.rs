@@ -0,0 +1,364 @@
+use crate::ast::*;
+
+/// The number of spaces used for each level of indentation.
+const INDENT : usize = 4;
+
+// =================================================================
+// Printer
+// =================================================================
+
+/// Format a given declaration back into source form.  The output is
+/// normalised, such that (for example) statements are placed on
+/// separate lines and indented by nesting depth.
+pub fn format(ast: &AbstractSyntaxTree, d: Decl) -> String {
+    let mut p = Printer{ast, out: String::new()};
+    p.print_decl(d);
+    p.out
+}
+
+/// Format a given type into source form, such as `&(i32[])` or `{i32
+/// f, bool g}`.  This is useful for reporting types in error messages.
+pub fn display_type(ast: &AbstractSyntaxTree, t: Type) -> String {
+    let mut p = Printer{ast, out: String::new()};
+    p.print_type(t);
+    p.out
+}
+
+/// Responsible for turning an AST back into source form.
+struct Printer<'a> {
+    ast: &'a AbstractSyntaxTree,
+    out: String
+}
+
+impl<'a> Printer<'a> {
+
+    // Declarations
+    // -----------------------------------------------------------------
+
+    fn print_decl(&mut self, d: Decl) {
+	match self.ast.get(d.index) {
+	    Node::AnnotatedDecl(anns,d) => {
+		for a in anns {
+		    self.out.push('@');
+		    self.print_name(a.name);
+		    if !a.args.is_empty() {
+			self.out.push('(');
+			self.print_list(&a.args,|p,e| p.print_expr(*e));
+			self.out.push(')');
+		    }
+		    self.out.push('\n');
+		}
+		self.print_decl(*d);
+	    }
+	    Node::ConstDecl(t,n,e) => {
+		self.out.push_str("const ");
+		self.print_type(*t);
+		self.out.push(' ');
+		self.print_name(*n);
+		self.out.push_str(" = ");
+		self.print_expr(*e);
+		self.out.push_str(";\n");
+	    }
+	    Node::TypeDecl(n,t) => {
+		self.out.push_str("type ");
+		self.print_name(*n);
+		self.out.push_str(" = ");
+		self.print_type(*t);
+		self.out.push_str(";\n");
+	    }
+	    Node::MethodDecl(n,t,ps,b) => {
+		self.print_type(*t);
+		self.out.push(' ');
+		self.print_name(*n);
+		self.out.push('(');
+		self.print_list(ps,|p,x| {
+		    p.print_type(x.declared);
+		    p.out.push(' ');
+		    p.print_name(x.name);
+		});
+		self.out.push_str(") ");
+		self.print_stmt(*b,0);
+		self.out.push('\n');
+	    }
+	    n => panic!("invalid declaration ({:?})",n)
+	}
+    }
+
+    // Statements
+    // -----------------------------------------------------------------
+
+    /// Print a statement at a given indentation level.  This assumes
+    /// the current line has already been indented.
+    fn print_stmt(&mut self, s: Stmt, level: usize) {
+	match self.ast.get(s.0) {
+	    Node::AssertStmt(e) => {
+		self.out.push_str("assert ");
+		self.print_expr(*e);
+		self.out.push(';');
+	    }
+	    Node::AssignStmt(_,_)|Node::VarDeclStmt(_,_,_) => {
+		self.print_simple(s);
+		self.out.push(';');
+	    }
+	    Node::BlockStmt(ss) => {
+		self.out.push('{');
+		for s in ss {
+		    self.out.push('\n');
+		    self.print_indent(level + 1);
+		    self.print_stmt(*s,level + 1);
+		}
+		self.out.push('\n');
+		self.print_indent(level);
+		self.out.push('}');
+	    }
+	    Node::ForStmt(i,c,u,b) => {
+		self.out.push_str("for (");
+		self.print_simple(*i);
+		self.out.push_str("; ");
+		self.print_expr(*c);
+		self.out.push_str("; ");
+		self.print_simple(*u);
+		self.out.push_str(") ");
+		self.print_stmt(*b,level);
+	    }
+	    Node::IfStmt(c,t,f) => {
+		self.out.push_str("if ");
+		self.print_expr(*c);
+		self.out.push(' ');
+		self.print_stmt(*t,level);
+		if let Some(f) = f {
+		    // NOTE: an else branch which is itself an if statement
+		    // is printed at the same level, giving a flat chain.
+		    self.out.push_str(" else ");
+		    self.print_stmt(*f,level);
+		}
+	    }
+	    Node::PrintStmt(e) => {
+		self.out.push_str("print ");
+		self.print_expr(*e);
+		self.out.push(';');
+	    }
+	    Node::ReturnStmt(e) => {
+		self.out.push_str("return");
+		if let Some(e) = e {
+		    self.out.push(' ');
+		    self.print_expr(*e);
+		}
+		self.out.push(';');
+	    }
+	    Node::BreakStmt => {
+		self.out.push_str("break;");
+	    }
+	    Node::ContinueStmt => {
+		self.out.push_str("continue;");
+	    }
+	    Node::SkipStmt => {
+		self.out.push_str("skip;");
+	    }
+	    Node::SwitchStmt(e,cs) => {
+		self.out.push_str("switch ");
+		self.print_expr(*e);
+		self.out.push_str(" {");
+		for (l,s) in cs {
+		    self.out.push('\n');
+		    self.print_indent(level + 1);
+		    match l {
+			Some(l) => {
+			    self.out.push_str("case ");
+			    self.print_expr(*l);
+			}
+			None => self.out.push_str("default")
+		    }
+		    self.out.push_str(": ");
+		    self.print_stmt(*s,level + 1);
+		}
+		self.out.push('\n');
+		self.print_indent(level);
+		self.out.push('}');
+	    }
+	    Node::TypeAliasStmt(n,t) => {
+		self.out.push_str("type ");
+		self.print_name(*n);
+		self.out.push_str(" = ");
+		self.print_type(*t);
+		self.out.push(';');
+	    }
+	    Node::WhileStmt(c,b) => {
+		self.out.push_str("while ");
+		self.print_expr(*c);
+		self.out.push(' ');
+		self.print_stmt(*b,level);
+	    }
+	    n => panic!("invalid statement ({:?})",n)
+	}
+    }
+
+    /// Print a simple statement without its terminating `;`, as
+    /// needed for the initialiser and update of a `for` loop.
+    fn print_simple(&mut self, s: Stmt) {
+	match self.ast.get(s.0) {
+	    Node::AssignStmt(l,r) => {
+		self.print_expr(*l);
+		self.out.push_str(" = ");
+		self.print_expr(*r);
+	    }
+	    Node::VarDeclStmt(t,n,e) => {
+		self.print_type(*t);
+		self.out.push(' ');
+		self.print_name(*n);
+		self.out.push_str(" = ");
+		self.print_expr(*e);
+	    }
+	    n => panic!("invalid simple statement ({:?})",n)
+	}
+    }
+
+    fn print_indent(&mut self, level: usize) {
+	for _ in 0 .. (level * INDENT) {
+	    self.out.push(' ');
+	}
+    }
+
+    // Expressions
+    // -----------------------------------------------------------------
+
+    fn print_expr(&mut self, e: Expr) {
+	match self.ast.get(e.0) {
+	    Node::AddExpr(l,r) => self.print_binary(*l,"+",*r),
+	    Node::AndExpr(l,r) => self.print_binary(*l,"&&",*r),
+	    Node::BoolExpr(b) => self.out.push_str(&b.to_string()),
+	    Node::CallExpr(n,args) => {
+		self.print_name(*n);
+		self.out.push('(');
+		self.print_list(args,|p,e| p.print_expr(*e));
+		self.out.push(')');
+	    }
+	    Node::EqualsExpr(l,r) => self.print_binary(*l,"==",*r),
+	    Node::ArrayAccessExpr(e,i) => {
+		self.print_operand(*e);
+		self.out.push('[');
+		self.print_expr(*i);
+		self.out.push(']');
+	    }
+	    Node::FieldAccessExpr(e,n) => {
+		self.print_operand(*e);
+		self.out.push('.');
+		self.print_name(*n);
+	    }
+	    Node::NotEqualsExpr(l,r) => self.print_binary(*l,"!=",*r),
+	    Node::NotExpr(e) => {
+		self.out.push('!');
+		self.print_operand(*e);
+	    }
+	    Node::NullExpr => self.out.push_str("null"),
+	    Node::OrExpr(l,r) => self.print_binary(*l,"||",*r),
+	    Node::LessThanExpr(l,r) => self.print_binary(*l,"<",*r),
+	    Node::IntExpr(negative,i) => {
+		if *negative {
+		    self.out.push('-');
+		}
+		self.out.push_str(&i.to_string());
+	    }
+	    Node::LenExpr(e) => {
+		self.out.push_str("len(");
+		self.print_expr(*e);
+		self.out.push(')');
+	    }
+	    Node::SizeofExpr(t) => {
+		self.out.push_str("sizeof(");
+		self.print_type(*t);
+		self.out.push(')');
+	    }
+	    Node::SubtractExpr(l,r) => self.print_binary(*l,"-",*r),
+	    Node::VarExpr(n) => self.print_name(*n),
+	    n => panic!("invalid expression ({:?})",n)
+	}
+    }
+
+    fn print_binary(&mut self, lhs: Expr, op: &str, rhs: Expr) {
+	self.print_operand(lhs);
+	self.out.push(' ');
+	self.out.push_str(op);
+	self.out.push(' ');
+	self.print_operand(rhs);
+    }
+
+    /// Print an operand of a binary expression, bracketing it when
+    /// it is itself a binary expression.
+    fn print_operand(&mut self, e: Expr) {
+	let binary = matches!(self.ast.get(e.0),
+	    Node::AddExpr(_,_)|Node::AndExpr(_,_)|Node::EqualsExpr(_,_)|Node::NotEqualsExpr(_,_)
+		|Node::LessThanExpr(_,_)|Node::OrExpr(_,_)|Node::SubtractExpr(_,_));
+	//
+	if binary {
+	    self.out.push('(');
+	    self.print_expr(e);
+	    self.out.push(')');
+	} else {
+	    self.print_expr(e);
+	}
+    }
+
+    // Types
+    // -----------------------------------------------------------------
+
+    fn print_type(&mut self, t: Type) {
+	match self.ast.get(t.0) {
+	    Node::ArrayType(e) => {
+		self.print_bracketed_type(*e,matches!(self.ast.get(e.0),Node::ReferenceType(_)));
+		self.out.push_str("[]");
+	    }
+	    Node::BoolType => self.out.push_str("bool"),
+	    Node::IntType(signed,width) => {
+		self.out.push(if *signed { 'i' } else { 'u' });
+		self.out.push_str(&width.to_string());
+	    }
+	    Node::NullType => self.out.push_str("null"),
+	    Node::RecordType(fs) => {
+		self.out.push('{');
+		self.print_list(fs,|p,(t,n)| {
+		    p.print_type(*t);
+		    p.out.push(' ');
+		    p.print_name(*n);
+		});
+		self.out.push('}');
+	    }
+	    Node::ReferenceType(e) => {
+		self.out.push('&');
+		self.print_bracketed_type(*e,matches!(self.ast.get(e.0),Node::ArrayType(_)));
+	    }
+	    Node::SizedArrayType(e,s) => {
+		self.print_bracketed_type(*e,matches!(self.ast.get(e.0),Node::ReferenceType(_)));
+		self.out.push('[');
+		self.print_expr(*s);
+		self.out.push(']');
+	    }
+	    Node::InferredType => self.out.push_str("var"),
+	    Node::VoidType => self.out.push_str("void"),
+	    n => panic!("invalid type ({:?})",n)
+	}
+    }
+
+    fn print_bracketed_type(&mut self, t: Type, bracket: bool) {
+	if bracket { self.out.push('('); }
+	self.print_type(t);
+	if bracket { self.out.push(')'); }
+    }
+
+    // Misc
+    // -----------------------------------------------------------------
+
+    fn print_name(&mut self, n: Name) {
+	self.out.push_str(n.as_str(self.ast));
+    }
+
+    /// Print a comma-separated list of items using a given printer
+    /// for each item.
+    fn print_list<T,F>(&mut self, items: &[T], mut f: F)
+    where F : FnMut(&mut Self,&T) {
+	for (i,item) in items.iter().enumerate() {
+	    if i != 0 { self.out.push_str(", "); }
+	    f(self,item);
+	}
+    }
+}
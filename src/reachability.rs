@@ -0,0 +1,119 @@
+use std::result;
+use crate::ast::*;
+use crate::error::*;
+
+// =================================================================
+// Error
+// =================================================================
+
+pub type Result<T> = result::Result<T, SyntaxError>;
+
+// =================================================================
+// Reachability
+// =================================================================
+
+/// Check the reachability of statements within all methods of a
+/// given set of declarations.  Specifically, no statement may follow
+/// one which cannot complete normally (e.g. a `return`), and every
+/// path through a non-void method must end in a `return`.  Both are
+/// determined by a single pass and, hence, at most one error is
+/// reported for any given statement.
+pub fn check(ast: &AbstractSyntaxTree, roots: &[Decl]) -> Result<()> {
+    for d in roots {
+	check_decl(ast,*d)?;
+    }
+    Ok(())
+}
+
+fn check_decl(ast: &AbstractSyntaxTree, d: Decl) -> Result<()> {
+    match ast.get(d.index) {
+	Node::AnnotatedDecl(_,d) => check_decl(ast,*d),
+	Node::MethodDecl(_,ret,_,body) => {
+	    let falls_through = check_stmt(ast,*body)?;
+	    // Only void methods may fall off the end
+	    if falls_through && !matches!(ast.get(ret.0),Node::VoidType) {
+		Err(missing_return(body.0))
+	    } else {
+		Ok(())
+	    }
+	}
+	_ => Ok(())
+    }
+}
+
+/// Check the statements within a given statement are reachable,
+/// returning whether or not execution can fall through it (i.e. can
+/// complete normally).
+fn check_stmt(ast: &AbstractSyntaxTree, stmt: Stmt) -> Result<bool> {
+    match ast.get(stmt.0) {
+	Node::BlockStmt(stmts) => {
+	    let mut reachable = true;
+	    for s in stmts {
+		if !reachable {
+		    return Err(unreachable_code(s.0));
+		}
+		reachable = check_stmt(ast,*s)?;
+	    }
+	    Ok(reachable)
+	}
+	Node::IfStmt(_,tt,ff) => {
+	    let t = check_stmt(ast,*tt)?;
+	    match ff {
+		Some(ff) => Ok(check_stmt(ast,*ff)? || t),
+		None => Ok(true)
+	    }
+	}
+	Node::ReturnStmt(_) => Ok(false),
+	// NOTE: execution resumes after (or at the start of) the
+	// enclosing loop, rather than with the next statement.
+	Node::BreakStmt|Node::ContinueStmt => Ok(false),
+	Node::SwitchStmt(_,cases) => {
+	    let mut falls_through = false;
+	    for (_,body) in cases {
+		falls_through |= check_stmt(ast,*body)?;
+	    }
+	    Ok(falls_through || !is_exhaustive(ast,cases))
+	}
+	Node::ForStmt(_,cond,_,body)|Node::WhileStmt(cond,body) => {
+	    check_stmt(ast,*body)?;
+	    // NOTE: the loop condition may be false on entry, hence a
+	    // loop can complete normally unless its condition is
+	    // literally `true` (in which case only `break` exits it).
+	    let forever = matches!(ast.get(cond.0),Node::BoolExpr(true));
+	    Ok(!forever || has_break(ast,*body))
+	}
+	_ => Ok(true)
+    }
+}
+
+/// Determine whether a given loop body contains a `break` which
+/// exits that loop (i.e. which is not within a nested loop).
+fn has_break(ast: &AbstractSyntaxTree, s: Stmt) -> bool {
+    match ast.get(s.0) {
+	Node::BreakStmt => true,
+	Node::BlockStmt(stmts) => stmts.iter().any(|s| has_break(ast,*s)),
+	Node::IfStmt(_,tt,ff) => {
+	    has_break(ast,*tt) || ff.is_some_and(|ff| has_break(ast,ff))
+	}
+	Node::SwitchStmt(_,cases) => cases.iter().any(|(_,b)| has_break(ast,*b)),
+	_ => false
+    }
+}
+
+/// Determine whether the cases of a switch cover every possible value
+/// of the discriminant.  This holds when there is a default, or when
+/// both `true` and `false` are covered.
+fn is_exhaustive(ast: &AbstractSyntaxTree, cases: &[(Option<Expr>,Stmt)]) -> bool {
+    let mut seen = [false,false];
+    for (label,_) in cases {
+	match label {
+	    None => { return true; }
+	    Some(l) => {
+		if let Node::BoolExpr(b) = ast.get(l.0) {
+		    seen[*b as usize] = true;
+		}
+	    }
+	}
+    }
+    seen[0] && seen[1]
+}
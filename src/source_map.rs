@@ -22,13 +22,25 @@ impl<'a> SourceMap<'a> {
 	self.map.insert(index,element);
     }
 
+    /// Determine the highlight for a given node.  This identifies the
+    /// line enclosing the start of the node's source, along with the
+    /// region within that line occupied by the node.
     pub fn get_highlight(&self, index: usize) -> Highlight {
 	// Lookup given node in the map
 	let val = self.map.get(&index);
 	// See what we got
 	match val { 
 	    Some(s) => {
-		Highlight{line: s, start:0, end: 1}
+		let offset = subslice_offset(self.input,s);
+		// Determine enclosing line
+		let line_start = self.input[..offset].rfind('\n').map_or(0,|i| i + 1);
+		let line_end = self.input[offset..].find('\n').map_or(self.input.len(),|i| offset + i);
+		let line = &self.input[line_start..line_end];
+		// Determine region within line (which is clamped to the
+		// line, since a node may span several lines).
+		let start = offset - line_start;
+		let end = (start + s.len()).min(line.len());
+		Highlight{line, start, end}
 	    }
 	    None => {
 		EMPTY_HIGHLIGHT
@@ -44,10 +56,52 @@ pub struct Highlight<'a> {
     pub end: usize
 }
 
+impl<'a> Highlight<'a> {
+    /// Render this highlight as the enclosing line, followed by a
+    /// line of carets underlining the highlighted region.  The region
+    /// may span several tokens, and is clamped to the line.  However,
+    /// it may extend one position past the end of the line, so that
+    /// errors at the end of the input can still be highlighted.
+    pub fn render(&self, tab_width: usize) -> String {
+	let end = self.end.min(self.line.len() + 1);
+	let start = self.start.min(end);
+	let indent = to_whitespace(self.line,start,tab_width);
+	// Determine width of region as displayed (i.e. after expanding
+	// any tabs within it).
+	let overhang = end.saturating_sub(self.line.len().max(start));
+	let width = to_whitespace(self.line,end,tab_width).len() - indent.len() + overhang;
+	let carets = "^".repeat(width);
+	format!("{}\n{}{}\n",self.line,indent,carets)
+    }
+}
+
 /// A dummy highlight to use when (for whatever reason) the necessary
 /// source information for a given node is missing.
 pub const EMPTY_HIGHLIGHT : Highlight<'static> = Highlight{ line: "", start: 0, end: 0 };
 
+/// The default number of columns spanned by a tab character when
+/// rendering highlights, matching most terminals.
+pub const DEFAULT_TAB_WIDTH : usize = 8;
+
+/// Convert the start of a given line (i.e. everything before a given
+/// offset) into corresponding whitespace.  Most characters are simply
+/// converted into a single space.  However, a tab is expanded to the
+/// next tab stop (as determined by the given tab width), since this
+/// is how it would be displayed.  Thus, carets printed after the
+/// resulting whitespace align with the original line.
+pub fn to_whitespace(line: &str, offset: usize, tab_width: usize) -> String {
+    let mut column = 0;
+    //
+    for (_,c) in line.char_indices().filter(|s| s.0 < offset) {
+	if c == '\t' {
+	    column = ((column / tab_width) + 1) * tab_width;
+	} else {
+	    column += 1;
+	}
+    }
+    " ".repeat(column)
+}
+
 /**
  * Calculate the offset of one slice from another.  Specifically,
  * we're expecting that `inner` is a subslice of `outer`.
@@ -62,3 +116,81 @@ fn subslice_offset<'a>(outer: &'a str, inner: &'a str) -> usize {
     // Done
     offset
 }
+
+// ======================================================
+// Tests
+// ======================================================
+
+#[test]
+fn test_01() {
+    assert_eq!(to_whitespace("abc",2,DEFAULT_TAB_WIDTH),"  ");
+}
+
+#[test]
+fn test_02() {
+    assert_eq!(to_whitespace("\tabc",1,DEFAULT_TAB_WIDTH),"        ");
+}
+
+#[test]
+fn test_03() {
+    assert_eq!(to_whitespace("\tabc",2,4),"     ");
+}
+
+#[test]
+fn test_04() {
+    assert_eq!(to_whitespace("ab\tc",3,4),"    ");
+}
+
+#[test]
+fn test_05() {
+    let input = "void f() {\n  assert b;\n}";
+    let mut map = SourceMap::new(input);
+    map.map(0,&input[20..21]);
+    let hl = map.get_highlight(0);
+    assert_eq!(hl.line,"  assert b;");
+    assert_eq!(hl.start,9);
+    assert_eq!(hl.end,10);
+    assert_eq!(hl.render(DEFAULT_TAB_WIDTH),"  assert b;\n         ^\n");
+}
+
+#[test]
+fn test_06() {
+    let map = SourceMap::new("skip;");
+    let hl = map.get_highlight(0);
+    assert_eq!(hl.line,"");
+    assert_eq!(hl.start,0);
+    assert_eq!(hl.end,0);
+}
+
+#[test]
+fn test_07() {
+    // Region spanning several tokens
+    let input = "assert a < b;";
+    let mut map = SourceMap::new(input);
+    map.map(0,&input[7..12]);
+    let hl = map.get_highlight(0);
+    assert_eq!(hl.render(DEFAULT_TAB_WIDTH),"assert a < b;\n       ^^^^^\n");
+}
+
+#[test]
+fn test_08() {
+    // Region spanning several lines is clamped to the first
+    let input = "if x {\n  skip;\n}";
+    let mut map = SourceMap::new(input);
+    map.map(0,&input[5..]);
+    let hl = map.get_highlight(0);
+    assert_eq!(hl.end,6);
+    assert_eq!(hl.render(DEFAULT_TAB_WIDTH),"if x {\n     ^\n");
+}
+
+#[test]
+fn test_09() {
+    // Tabs within the region are expanded
+    let hl = Highlight{line: "a\tb", start: 0, end: 3};
+    assert_eq!(hl.render(4),"a\tb\n^^^^^\n");
+    // Out of range regions are clamped
+    let hl = Highlight{line: "ab", start: 1, end: 10};
+    assert_eq!(hl.render(4),"ab\n ^^\n");
+    let hl = Highlight{line: "ab", start: 2, end: 3};
+    assert_eq!(hl.render(4),"ab\n  ^\n");
+}
@@ -1,8 +1,10 @@
 use std::result;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use crate::ast::*;
 use crate::ast::Node::*;
 use crate::error::*;
+use crate::printer::display_type;
 
 // =================================================================
 // Error
@@ -14,14 +16,49 @@ pub type Result<T> = result::Result<T, SyntaxError>;
 // Type Checker
 // =================================================================
 
+/// Maps variable names to their declared types.  Since the parser
+/// gives identical names in the source the same node on the heap,
+/// variables are keyed by name directly.
 pub type Env = HashMap<Name, Type>;
 
+/// The default maximum number of errors reported when checking a
+/// program with `check_program_all`.
+pub const DEFAULT_MAX_ERRORS : usize = 20;
+
 /// Responsible for determine appropriate types for all statements and
 /// expressions used within a given AST.
 pub struct TypeChecker<'a,F>
 where F : FnMut(usize,Type) {
     ast: &'a mut AbstractSyntaxTree,
     globals : Env,
+    /// Maps each variable access to the name node of the declaration
+    /// it refers to (i.e. that of the variable itself).
+    definitions : HashMap<usize,usize>,
+    /// Maps method names to their declarations, such that calls can
+    /// be checked against them.  Methods may be overloaded by arity
+    /// and, hence, a name can map to several declarations.
+    methods : HashMap<Name,Vec<Decl>>,
+    /// Determines the type given to integer literals (i.e. whether
+    /// signed, and what width).
+    int_default : (bool,u8),
+    /// Determines the maximum number of errors collected by
+    /// `check_program_all` before it gives up.
+    max_errors : usize,
+    /// Identifies types which have already been checked, such that
+    /// types shared between declarations (e.g. via an alias) are only
+    /// walked once.  Since the checker holds the only (`&mut`) borrow
+    /// of the tree, nodes cannot be modified whilst checking and,
+    /// hence, this cannot go stale.
+    checked : HashSet<usize>,
+    /// Maps methods declared with an inferred return type (i.e. `var`)
+    /// to the type inferred for them.  A method whose type is still
+    /// being inferred maps to `None`, such that recursion through it
+    /// can be detected.
+    inferred : HashMap<usize,Option<Type>>,
+    /// Number of loops enclosing the statement currently being
+    /// checked, such that `break` and `continue` can be rejected
+    /// when there is no loop for them to apply to.
+    loops : usize,
     mapper : F
 }
 
@@ -30,29 +67,193 @@ where F : FnMut(usize,Type) {
 
     pub fn new(ast: &'a mut AbstractSyntaxTree, mapper: F) -> Self {
 	let globals : Env = HashMap::new();
-	TypeChecker{ast,globals,mapper}
+	let definitions = HashMap::new();
+	let methods = HashMap::new();
+	let int_default = (true,32);
+	let max_errors = DEFAULT_MAX_ERRORS;
+	let checked = HashSet::new();
+	let inferred = HashMap::new();
+	let loops = 0;
+	TypeChecker{ast,globals,definitions,methods,int_default,max_errors,checked,inferred,loops,mapper}
+    }
+
+    /// Set the type given to integer literals, which is `i32` by
+    /// default.  For example, `with_int_default(true,64)` gives them
+    /// type `i64`.
+    pub fn with_int_default(mut self, signed: bool, width: u8) -> Self {
+	self.int_default = (signed,width);
+	self
+    }
+
+    /// Set the maximum number of errors collected by
+    /// `check_program_all`, which is `DEFAULT_MAX_ERRORS` by default.
+    pub fn with_max_errors(mut self, max_errors: usize) -> Self {
+	self.max_errors = max_errors;
+	self
+    }
+
+    /// Determine the declaration to which a given variable access
+    /// refers, as identified by the name node of that declaration
+    /// (e.g. a parameter's name).  This is only available for
+    /// expressions which have been checked.
+    pub fn definition_of(&self, expr : Expr) -> Option<usize> {
+	self.definitions.get(&expr.0).copied()
     }
 
     // Declarations
     // -----------------------------------------------------------------
+
+    /// Check a sequence of declarations making up a program.  These
+    /// are checked in order and, hence, a constant must be declared
+    /// before it is used.  Methods, however, may be called before
+    /// they are declared.
+    pub fn check_program(&mut self, decls : &[Decl]) -> Result<()> {
+	self.check_unique_names(decls)?;
+	self.declare_methods(decls);
+	for d in decls {
+	    self.check(*d)?;
+	}
+	Ok(())
+    }
+
+    /// Check a sequence of declarations making up a program, whilst
+    /// collecting errors rather than stopping at the first.  Each
+    /// declaration is checked independently, hence at most one error
+    /// is reported per declaration (in addition to any duplicate
+    /// declaration).  Once the maximum number of errors is exceeded,
+    /// checking stops and a final `TooManyErrors` note is added in
+    /// place of those remaining (identifying where checking stopped).
+    pub fn check_program_all(&mut self, decls : &[Decl]) -> Vec<SyntaxError> {
+	let mut errors = Vec::new();
+	let max_errors = self.max_errors;
+	//
+	let duplicate = self.check_unique_names(decls).err().map(|e| (e.node,e));
+	self.declare_methods(decls);
+	let found = decls.iter().filter_map(|d| self.check(*d).err().map(|e| (d.index,e)));
+	for (index,e) in duplicate.into_iter().chain(found) {
+	    if errors.len() >= max_errors {
+		errors.push(too_many_errors(index));
+		break;
+	    }
+	    errors.push(e);
+	}
+	errors
+    }
+
+    /// Check no two declarations share the same name.  Since types,
+    /// methods and constants share a single namespace, a method
+    /// cannot have the same name as a type (and so on).  The only
+    /// exception is that methods with different numbers of
+    /// parameters can share a name.  The error is reported against
+    /// the second declaration.
+    pub fn check_unique_names(&self, decls : &[Decl]) -> Result<()> {
+	// Maps each name to the arities declared with it (where
+	// `None` indicates a declaration other than a method).
+	let mut names : HashMap<Name,Vec<Option<usize>>> = HashMap::new();
+	//
+	for d in decls {
+	    let name = self.decl_name(*d);
+	    let arity = self.decl_arity(*d);
+	    let arities = names.entry(name).or_default();
+	    if arities.iter().any(|a| a.is_none() || arity.is_none() || *a == arity) {
+		return Err(duplicate_declaration(d.index));
+	    }
+	    arities.push(arity);
+	}
+	Ok(())
+    }
+
+    /// Register every method in a given sequence of declarations, such
+    /// that calls to them can be checked.
+    fn declare_methods(&mut self, decls : &[Decl]) {
+	for d in decls {
+	    self.declare_method(d.unannotated(self.ast));
+	}
+    }
+
+    /// Register a given declaration (if it is a method), such that
+    /// calls to it can be checked.
+    fn declare_method(&mut self, d : Decl) {
+	if let Node::MethodDecl(name,_,_,_) = self.ast.get(d.index) {
+	    let ds = self.methods.entry(*name).or_default();
+	    if !ds.contains(&d) {
+		ds.push(d);
+	    }
+	}
+    }
+
+    /// Determine the number of parameters of a given declaration, or
+    /// `None` if it is not a method.
+    fn decl_arity(&self, d : Decl) -> Option<usize> {
+	match self.ast.get(d.unannotated(self.ast).index) {
+	    Node::MethodDecl(_,_,params,_) => Some(params.len()),
+	    _ => None
+	}
+    }
+
+    /// Determine the name of a given declaration.
+    fn decl_name(&self, d : Decl) -> Name {
+	match self.ast.get(d.index) {
+	    Node::AnnotatedDecl(_,d) => self.decl_name(*d),
+	    Node::ConstDecl(_,name,_) => *name,
+	    Node::TypeDecl(name,_) => *name,
+	    Node::MethodDecl(name,_,_,_) => *name,
+	    _ => panic!("invalid declaration")
+	}
+    }
+
     pub fn check(&mut self, d : Decl) -> Result<()> {
 	let n = self.ast.get(d.index);
 	//
 	match n {
+	    Node::AnnotatedDecl(_,d) => {
+		// Annotations are ignored by the type checker
+		self.check(*d)
+	    }
+	    Node::ConstDecl(t,name,e) => {
+		self.check_const(*t,*name,*e)
+	    }
 	    Node::TypeDecl(name,alias) => {
 	    	self.check_type_alias(*name,*alias)
 	    }
-	    Node::MethodDecl(name,ret,params,body) => {
+	    Node::MethodDecl(name,_,params,body) => {
 		// FIXME: would be nice to avoid cloning here!  To do
 		// this, I think the most sensible approach is to put
 		// a collection kind into the AST.
-	    	self.check_method(*name,*ret,params.clone(),*body)
+		let (name,params,body) = (*name,params.clone(),*body);
+		// Register method (e.g. for recursive calls)
+		self.declare_method(d);
+		let ret = self.return_type(d)?;
+	    	self.check_method(name,ret,params,body)
+	    }
+	    n => {
+		let msg = format!("expected declaration, found {}",n.kind_name());
+		Err(internal_failure(d.index,&msg))
 	    }
-	    _ => Err(internal_failure(0,"unknown declaration"))
 	}
     }
 
-    pub fn check_type_alias(&self, name : Name, alias : Type) -> Result<()> {
+    /// Check a constant declaration, whose initialiser must be a
+    /// compile-time constant matching the declared type.  If so, the
+    /// constant is registered in the global environment.
+    pub fn check_const(&mut self, t : Type, name : Name, init : Expr) -> Result<()> {
+	// Sanity check declared type
+	self.check_type(&t)?;
+	self.check_not_void(&t,"constant cannot be void")?;
+	// Check initialiser is constant
+	if !self.is_constant(init) {
+	    return Err(expected_constant(init.0));
+	}
+	let env = self.globals.clone();
+	// Check initialiser matches declared type
+	self.check_expr_against(&env,t,init)?;
+	// Register constant
+	self.globals.insert(name,t);
+	// Done
+	Ok(())
+    }
+
+    pub fn check_type_alias(&mut self, name : Name, alias : Type) -> Result<()> {
 	// Sanity check alias type
 	self.check_type(&alias)?;
 	// Done!
@@ -60,16 +261,91 @@ where F : FnMut(usize,Type) {
     }
 
     pub fn check_method(&mut self, name : Name, ret: Type, params : Vec<Parameter>, body : Stmt) -> Result<()> {
+    	let env = self.method_env(&params)?;
+    	// Sanity check return type
+    	self.check_type(&ret)?;
+    	// Check the body
+    	self.check_stmt(&env, ret, body)?;
+    	// Done
+    	Ok(())
+    }
+
+    /// Construct the environment in which a method's body is checked,
+    /// which consists of the globals along with its parameters.  A
+    /// duplicate parameter is reported against its second occurrence
+    /// (i.e. its `site`).
+    fn method_env(&mut self, params : &[Parameter]) -> Result<Env> {
     	// Clone environment, since we're going to update it.
     	let mut env = self.globals.clone();
+    	let mut names = HashSet::new();
     	// Allocate parameters into environment
     	for p in params {
+    	    // Sanity check parameter type
+    	    self.check_type(&p.declared)?;
+    	    self.check_not_void(&p.declared,"parameter cannot be void")?;
+    	    // Check parameter name not already used (noting that
+    	    // parameters may shadow globals)
+    	    if !names.insert(p.name) {
+    		return Err(duplicate_parameter(p.site.0));
+    	    }
     	    env.insert(p.name,p.declared);
     	}
-    	// Check the body
-    	let nbody = self.check_stmt(&env, body)?;
-    	// Done
-    	Ok(())
+    	Ok(env)
+    }
+
+    /// Determine the return type of a given method.  When declared as
+    /// `var`, this is inferred from the method's body, which must
+    /// consist of exactly one `return` statement (e.g. `var f() {
+    /// return 1 < 2; }` returns `bool`).  The inferred type is also
+    /// reported to the type mapper against the `var` node.
+    pub fn return_type(&mut self, d : Decl) -> Result<Type> {
+	let (ret,params,body) = match self.ast.get(d.index) {
+	    Node::MethodDecl(_,ret,params,body) => (*ret,params.clone(),*body),
+	    n => {
+		let msg = format!("expected method, found {}",n.kind_name());
+		return Err(internal_failure(d.index,&msg));
+	    }
+	};
+	if self.ast.get(ret.0) != &Node::InferredType {
+	    return Ok(ret);
+	}
+	// Check whether already inferred (or being inferred)
+	match self.inferred.get(&d.index) {
+	    Some(Some(t)) => { return Ok(*t); }
+	    Some(None) => {
+		return Err(invalid_type(ret.0,"cannot infer return type of recursive method"));
+	    }
+	    None => {}
+	}
+	// Body must be a single return statement
+	let expr = match self.ast.get(body.0) {
+	    Node::BlockStmt(stmts) if stmts.len() == 1 => {
+		match self.ast.get(stmts[0].0) {
+		    Node::ReturnStmt(Some(e)) => Some(*e),
+		    _ => None
+		}
+	    }
+	    _ => None
+	};
+	let expr = match expr {
+	    Some(e) => e,
+	    None => {
+		return Err(invalid_type(ret.0,"cannot infer return type unless body is a single return"));
+	    }
+	};
+	// Infer type from returned expression
+	self.inferred.insert(d.index,None);
+	let t = self.method_env(&params).and_then(|env| self.check_expr(&env,expr));
+	let t = match t {
+	    Ok(t) => t,
+	    Err(e) => {
+		self.inferred.remove(&d.index);
+		return Err(e);
+	    }
+	};
+	self.inferred.insert(d.index,Some(t));
+	(self.mapper)(ret.0,t);
+	Ok(t)
     }
 
     // Statements
@@ -77,24 +353,66 @@ where F : FnMut(usize,Type) {
 
     /// Check a given statement makes sense.  More specifically, that
     /// all expressions are used in a type-safe fashion.  For example,
-    /// a statement `assert 1;` is not type safe.
-    pub fn check_stmt(&mut self, env : &Env, stmt : Stmt) -> Result<()> {
+    /// a statement `assert 1;` is not type safe.  The return type of
+    /// the enclosing method is threaded through, such that `return`
+    /// statements (however deeply nested) can be checked against it.
+    pub fn check_stmt(&mut self, env : &Env, ret : Type, stmt : Stmt) -> Result<()> {
 	let n = self.ast.get(stmt.0);
 	//
 	match n {
 	    Node::AssertStmt(cond) => {
 		self.check_assert(env,*cond)
 	    }
+	    Node::AssignStmt(lhs,rhs) => {
+		self.check_assign(env,*lhs,*rhs)
+	    }
 	    Node::BlockStmt(stmts) => {
 		// FIXME: would be nice to avoid cloning here!  To do
 		// this, I think the most sensible approach is to put
 		// a collection kind into the AST.
-		self.check_block(env,stmts.clone())
+		self.check_block(env,ret,stmts.clone())
+	    }
+	    Node::BreakStmt => {
+		self.check_break(stmt)
+	    }
+	    Node::ContinueStmt => {
+		self.check_continue(stmt)
+	    }
+	    Node::ForStmt(init,cond,update,body) => {
+		self.check_for(env,ret,*init,*cond,*update,*body)
+	    }
+	    Node::IfStmt(cond,tt,ff) => {
+		self.check_if(env,ret,*cond,*tt,*ff)
+	    }
+	    Node::PrintStmt(expr) => {
+		self.check_print(env,*expr)
+	    }
+	    Node::ReturnStmt(expr) => {
+		self.check_return(env,ret,stmt,*expr)
 	    }
 	    Node::SkipStmt => {
 		self.check_skip(env)
 	    }
-	    _ => Err(internal_failure(0,"unknown statement"))
+	    Node::SwitchStmt(expr,cases) => {
+		self.check_switch(env,ret,stmt,*expr,cases.clone())
+	    }
+	    Node::TypeAliasStmt(name,alias) => {
+		self.check_type_alias(*name,*alias)
+	    }
+	    Node::VarDeclStmt(t,_,init) => {
+		self.check_vardecl(env,*t,*init)
+	    }
+	    Node::WhileStmt(cond,body) => {
+		self.check_while(env,ret,*cond,*body)
+	    }
+	    Node::Error => {
+		// Already reported by the parser
+		Ok(())
+	    }
+	    n => {
+		let msg = format!("expected statement, found {}",n.kind_name());
+		Err(internal_failure(stmt.0,&msg))
+	    }
 	}
     }
 
@@ -106,9 +424,163 @@ where F : FnMut(usize,Type) {
 	Ok(())
     }
 
-    pub fn check_block(&mut self, env : &Env, stmts: Vec<Stmt>) -> Result<()> {
+    pub fn check_assign(&mut self, env : &Env, lhs : Expr, rhs : Expr) -> Result<()> {
+	let lhs_t = self.check_expr(env,lhs)?;
+	// Ensure assigned value matches
+	self.check_expr_against(env,lhs_t,rhs)
+    }
+
+    /// Check a block of statements.  Variables declared within the
+    /// block are in scope for the remainder of that block only.
+    pub fn check_block(&mut self, env : &Env, ret : Type, stmts: Vec<Stmt>) -> Result<()> {
+	// Clone environment, since we may update it.
+	let mut env = env.clone();
+	//
 	for stmt in stmts {
-	    self.check_stmt(env,stmt)?;
+	    self.check_stmt(&env,ret,stmt)?;
+	    self.declare(&mut env,stmt);
+	}
+	Ok(())
+    }
+
+    /// Check a for statement.  A variable declared in the initialiser
+    /// is in scope for the condition, update and body of the loop.
+    pub fn check_for(&mut self, env : &Env, ret : Type, init : Stmt, cond : Expr, update : Stmt, body : Stmt) -> Result<()> {
+	// Clone environment, since we may update it.
+	let mut env = env.clone();
+	self.check_stmt(&env,ret,init)?;
+	self.declare(&mut env,init);
+	// Ensure boolean condition
+	let t = self.check_expr(&env,cond)?;
+	self.check_bool_type(t)?;
+	// Check update and loop body
+	self.check_stmt(&env,ret,update)?;
+	self.check_loop_body(&env,ret,body)
+    }
+
+    pub fn check_if(&mut self, env : &Env, ret : Type, cond : Expr, tt : Stmt, ff : Option<Stmt>) -> Result<()> {
+	let t = self.check_expr(env,cond)?;
+	// Ensure boolean condition
+	self.check_bool_type(t)?;
+	// Check both branches
+	self.check_stmt(env,ret,tt)?;
+	if let Some(ff) = ff {
+	    self.check_stmt(env,ret,ff)?;
+	}
+	Ok(())
+    }
+
+    /// Check a print statement, which accepts a value of any type.
+    pub fn check_print(&mut self, env : &Env, expr : Expr) -> Result<()> {
+	let t = self.check_expr(env,expr)?;
+	self.check_not_void(&t,"cannot print void")
+    }
+
+    /// Check a return statement against the return type of the
+    /// enclosing method.  A return without an expression is only
+    /// permitted in a method returning `void`.
+    pub fn check_return(&mut self, env : &Env, ret : Type, stmt : Stmt, expr : Option<Expr>) -> Result<()> {
+	match expr {
+	    Some(e) => {
+		// Ensure returned value matches
+		self.check_expr_against(env,ret,e)
+	    }
+	    None => {
+		match self.ast.get(ret.0) {
+		    VoidType => Ok(()),
+		    _ => Err(expected_subtype(stmt.0,&self.display(ret),"void"))
+		}
+	    }
+	}
+    }
+
+    /// Check a switch statement.  Every case label must be a constant
+    /// matching the type of the discriminant, and no label may be
+    /// repeated.  Furthermore, a switch over a boolean without a
+    /// default must cover both `true` and `false`.
+    pub fn check_switch(&mut self, env : &Env, ret : Type, stmt : Stmt, expr : Expr, cases : Vec<(Option<Expr>,Stmt)>) -> Result<()> {
+	let t = self.check_expr(env,expr)?;
+	let mut labels = HashSet::new();
+	let mut default = false;
+	//
+	for (label,body) in cases {
+	    match label {
+		Some(l) => {
+		    let lt = self.check_expr(env,l)?;
+		    self.check_matching_types(&t,&lt)?;
+		    match self.label_value(l) {
+			Some(v) if !labels.insert(v) => {
+			    return Err(duplicate_case(l.0));
+			}
+			Some(_) => {}
+			None => { return Err(expected_constant(l.0)); }
+		    }
+		}
+		None if default => { return Err(duplicate_case(body.0)); }
+		None => { default = true; }
+	    }
+	    self.check_stmt(env,ret,body)?;
+	}
+	// Check exhaustiveness of boolean switches
+	if !default && matches!(self.ast.get(t.0),BoolType) && labels.len() != 2 {
+	    return Err(non_exhaustive_switch(stmt.0));
+	}
+	Ok(())
+    }
+
+    /// Determine the value of a case label (if it is constant).  For
+    /// simplicity, booleans are represented as integers here.
+    fn label_value(&self, label : Expr) -> Option<i64> {
+	match self.ast.get(label.0) {
+	    BoolExpr(b) => Some(*b as i64),
+	    _ => label.as_constant(self.ast)
+	}
+    }
+
+    /// Check a variable declaration.  The declared type must be valid
+    /// and not `void`, and the initialiser must match it.
+    pub fn check_vardecl(&mut self, env : &Env, t : Type, init : Expr) -> Result<()> {
+	self.check_type(&t)?;
+	self.check_not_void(&t,"variable cannot be void")?;
+	// Ensure initialiser matches
+	self.check_expr_against(env,t,init)
+    }
+
+    /// Add the variable declared by a given statement (if any) into
+    /// an environment.
+    fn declare(&self, env : &mut Env, stmt : Stmt) {
+	if let Node::VarDeclStmt(t,name,_) = self.ast.get(stmt.0) {
+	    env.insert(*name,*t);
+	}
+    }
+
+    pub fn check_while(&mut self, env : &Env, ret : Type, cond : Expr, body : Stmt) -> Result<()> {
+	let t = self.check_expr(env,cond)?;
+	// Ensure boolean condition
+	self.check_bool_type(t)?;
+	// Check loop body
+	self.check_loop_body(env,ret,body)
+    }
+
+    /// Check the body of a loop, within which `break` and `continue`
+    /// are permitted.
+    fn check_loop_body(&mut self, env : &Env, ret : Type, body : Stmt) -> Result<()> {
+	self.loops += 1;
+	let r = self.check_stmt(env,ret,body);
+	self.loops -= 1;
+	r
+    }
+
+    pub fn check_break(&self, stmt : Stmt) -> Result<()> {
+	if self.loops == 0 {
+	    return Err(break_outside_loop(stmt.0));
+	}
+	Ok(())
+    }
+
+    pub fn check_continue(&self, stmt : Stmt) -> Result<()> {
+	if self.loops == 0 {
+	    return Err(continue_outside_loop(stmt.0));
 	}
 	Ok(())
     }
@@ -124,19 +596,61 @@ where F : FnMut(usize,Type) {
 	let n = self.ast.get(expr.0);
 	//
 	match n {
+	    Node::AddExpr(lhs,rhs) => {
+		self.check_arithmetic_operator(env,*lhs,*rhs)
+	    }
+	    Node::AndExpr(lhs,rhs) => {
+		self.check_logical_operator(env,*lhs,*rhs)
+	    }
 	    Node::BoolExpr(lit) => {
 		self.check_boolean_literal(env,*lit)
 	    }
-	    Node::IntExpr(lit) => {
+	    Node::CallExpr(name,args) => {
+		self.check_call(env,expr,*name,args.clone())
+	    }
+	    Node::EqualsExpr(lhs,rhs) => {
+		self.check_equality_comparator(env,*lhs,*rhs)
+	    }
+	    Node::ArrayAccessExpr(e,i) => {
+		self.check_array_access(env,*e,*i)
+	    }
+	    Node::FieldAccessExpr(e,name) => {
+		self.check_field_access(env,expr,*e,*name)
+	    }
+	    Node::IntExpr(_,lit) => {
 		self.check_integer_literal(env,*lit)
 	    }
 	    Node::LessThanExpr(lhs,rhs) => {
 		self.check_lessthan_comparator(env,*lhs,*rhs)
 	    }
+	    Node::NotEqualsExpr(lhs,rhs) => {
+		self.check_equality_comparator(env,*lhs,*rhs)
+	    }
+	    Node::NotExpr(e) => {
+		self.check_logical_not(env,*e)
+	    }
+	    Node::NullExpr => {
+		self.check_null_literal()
+	    }
+	    Node::OrExpr(lhs,rhs) => {
+		self.check_logical_operator(env,*lhs,*rhs)
+	    }
+	    Node::LenExpr(e) => {
+		self.check_len(env,*e)
+	    }
+	    Node::SizeofExpr(t) => {
+		self.check_sizeof(*t)
+	    }
+	    Node::SubtractExpr(lhs,rhs) => {
+		self.check_arithmetic_operator(env,*lhs,*rhs)
+	    }
 	    Node::VarExpr(name) => {
-		self.check_variable_access(env,name)
+		self.check_variable_access(env,expr,*name)
+	    }
+	    n => {
+		let msg = format!("expected expression, found {}",n.kind_name());
+		Err(internal_failure(expr.0,&msg))
 	    }
-	    _ => Err(internal_failure(expr.0, "unknown expression"))
 	}
 	// FIXME: how do we record type?
     }
@@ -145,9 +659,67 @@ where F : FnMut(usize,Type) {
 	Ok(Type::new(self.ast,Node::BoolType))
     }
 
-    pub fn check_integer_literal(&mut self, env : &Env, literal: i32) -> Result<Type> {
+    pub fn check_integer_literal(&mut self, env : &Env, literal: u128) -> Result<Type> {
 	// FIXME: for now this is a conservative assumption.
-	Ok(Type::new(self.ast,Node::IntType(true,32)))
+	let (signed,width) = self.int_default;
+	Ok(Type::new(self.ast,Node::IntType(signed,width)))
+    }
+
+    pub fn check_null_literal(&mut self) -> Result<Type> {
+	Ok(Type::new(self.ast,Node::NullType))
+    }
+
+    /// Check an expression given where a value of some type is
+    /// expected (e.g. the initialiser of a variable).  An integer
+    /// literal takes on the expected integer type, provided it lies
+    /// within the range of that type.  For example, `u8 x = 255;` is
+    /// permitted, but `u8 x = 256;` is not.  Otherwise, the type of
+    /// the expression must be assignable to that expected (see
+    /// `check_assignable`).
+    pub fn check_expr_against(&mut self, env : &Env, expected : Type, expr : Expr) -> Result<()> {
+	if let (IntType(signed,width),IntExpr(negative,lit)) = (self.ast.get(expected.0),self.ast.get(expr.0)) {
+	    let (min,max) = Type::int_range(*signed,*width);
+	    let bound = if *negative { min.unsigned_abs() } else { max };
+	    if *lit > bound {
+		return Err(literal_out_of_range(expr.0,*negative,*lit,*signed,*width));
+	    }
+	    return Ok(());
+	}
+	let t = self.check_expr(env,expr)?;
+	self.check_assignable(&expected,&t)
+    }
+
+    /// Check a value of one type can be assigned to a location of
+    /// another.  This is as for `check_matching_types`, except that
+    /// integers can be widened when no value is lost.  For example,
+    /// an `i32` can be assigned to an `i64` (but not vice versa), and
+    /// a `u8` to an `i16` (but not to an `i8`).
+    pub fn check_assignable(&self, t1 : &Type, t2 : &Type) -> Result<()> {
+	match (self.ast.get(t1.0),self.ast.get(t2.0)) {
+	    (IntType(s1,w1),IntType(s2,w2)) if s1 == s2 && w2 <= w1 => Ok(()),
+	    (IntType(true,w1),IntType(false,w2)) if w2 < w1 => Ok(()),
+	    _ => self.check_matching_types(t1,t2)
+	}
+    }
+
+    /// Check an equality comparison, such as `x == y` or `p != null`.
+    /// Both operands must have matching types, except that `null` may
+    /// be compared against any reference.  Thus, records can be
+    /// compared (structurally) provided they have identical fields
+    /// (e.g. `{i32 f}` with `{i32 f}`, but not with `{bool f}`).
+    pub fn check_equality_comparator(&mut self, env : &Env, lhs: Expr, rhs: Expr) -> Result<Type> {
+	let lhs_t = self.check_expr(env,lhs)?;
+	let rhs_t = self.check_expr(env,rhs)?;
+	//
+	match (self.ast.get(lhs_t.0),self.ast.get(rhs_t.0)) {
+	    (NullType,NullType|ReferenceType(_)) => {}
+	    _ => {
+		// Check rhs has matching type
+		self.check_matching_types(&lhs_t, &rhs_t)?;
+	    }
+	}
+	// Done
+	Ok(Type::new(self.ast,Node::BoolType))
     }
 
     pub fn check_lessthan_comparator(&mut self, env : &Env, lhs: Expr, rhs: Expr) -> Result<Type> {
@@ -161,14 +733,170 @@ where F : FnMut(usize,Type) {
 	Ok(Type::new(self.ast,Node::BoolType))
     }
 
-    pub fn check_variable_access(&self, env : &Env, name: &Name) -> Result<Type> {
-	let r = env.get(name);
+    pub fn check_arithmetic_operator(&mut self, env : &Env, lhs: Expr, rhs: Expr) -> Result<Type> {
+	let lhs_t = self.check_expr(env,lhs)?;
+	let rhs_t = self.check_expr(env,rhs)?;
+	// Check lhs is integer (of some kind)
+	self.check_int_type(lhs_t)?;
+	// Check rhs has matching type
+	self.check_matching_types(&lhs_t, &rhs_t)?;
+	// Done
+	Ok(lhs_t)
+    }
+
+    /// Check a logical operator, such as `x && y` or `x || y`.  Both
+    /// operands must be boolean, as is the result.
+    pub fn check_logical_operator(&mut self, env : &Env, lhs: Expr, rhs: Expr) -> Result<Type> {
+	let lhs_t = self.check_expr(env,lhs)?;
+	let rhs_t = self.check_expr(env,rhs)?;
+	// Check both sides are boolean
+	self.check_bool_type(lhs_t)?;
+	self.check_bool_type(rhs_t)?;
+	// Done
+	Ok(lhs_t)
+    }
+
+    /// Check a logical negation, such as `!b`.  The operand must be
+    /// boolean.
+    pub fn check_logical_not(&mut self, env : &Env, expr: Expr) -> Result<Type> {
+	let t = self.check_expr(env,expr)?;
+	self.check_bool_type(t)?;
+	Ok(t)
+    }
+
+    /// Check a `sizeof(T)` expression.  The given type must itself be
+    /// well-formed, and the expression always yields a `u64`.
+    pub fn check_sizeof(&mut self, t: Type) -> Result<Type> {
+	// Sanity check type being sized
+	self.check_type(&t)?;
 	//
-	match r {
-	    Some(t) => Ok(*t),
-	    None => {
-		panic!("variable not found");
+	Ok(Type::new(self.ast,Node::IntType(false,64)))
+    }
+
+    /// Check a `len(e)` expression.  The operand must have array type
+    /// (whether sized or not), and the expression always yields a
+    /// `u64`.
+    pub fn check_len(&mut self, env : &Env, expr: Expr) -> Result<Type> {
+	let t = self.check_expr(env,expr)?;
+	//
+	match self.ast.get(t.0) {
+	    ArrayType(_)|SizedArrayType(_,_) => {
+		Ok(Type::new(self.ast,Node::IntType(false,64)))
+	    }
+	    _ => Err(expected_subtype(expr.0,"array",&self.display(t)))
+	}
+    }
+
+    /// Check a field access, such as `r.f`.  The source must have
+    /// record type, and the field is located by name (i.e. its
+    /// position within the record is irrelevant).  A missing field is
+    /// reported against the access itself.
+    pub fn check_field_access(&mut self, env : &Env, expr: Expr, source: Expr, name: Name) -> Result<Type> {
+	let t = self.check_expr(env,source)?;
+	//
+	match self.ast.get(t.0) {
+	    RecordType(fields) => {
+		let field = self.resolve(&name);
+		//
+		for (ft,fn_) in fields {
+		    if self.resolve(fn_) == field {
+			return Ok(*ft);
+		    }
+		}
+		Err(field_not_found(expr.0))
+	    }
+	    _ => Err(expected_subtype(source.0,"record",&self.display(t)))
+	}
+    }
+
+    /// Check an array access, such as `xs[i]`.  The source must have
+    /// array type (whether sized or not) and the index must be an
+    /// integer.  This yields the array's element type.
+    pub fn check_array_access(&mut self, env : &Env, expr: Expr, index: Expr) -> Result<Type> {
+	let t = self.check_expr(env,expr)?;
+	let it = self.check_expr(env,index)?;
+	self.check_int_type(it)?;
+	//
+	match self.ast.get(t.0) {
+	    ArrayType(e)|SizedArrayType(e,_) => Ok(*e),
+	    _ => Err(expected_subtype(expr.0,"array",&self.display(t)))
+	}
+    }
+
+    /// Check a variable access, recording the declaration to which it
+    /// refers.
+    pub fn check_variable_access(&mut self, env : &Env, expr: Expr, name: Name) -> Result<Type> {
+	match env.get(&name) {
+	    Some(t) => {
+		self.definitions.insert(expr.0,name.0);
+		Ok(*t)
+	    }
+	    None => Err(variable_not_found(expr.0))
+	}
+    }
+
+    /// Check a method call, such as `f(1,x)`.  The method must exist,
+    /// and each argument must match the corresponding parameter.  The
+    /// call yields the method's return type.  Where a method is
+    /// overloaded, the call is resolved by its number of arguments.
+    pub fn check_call(&mut self, env : &Env, expr: Expr, name: Name, args: Vec<Expr>) -> Result<Type> {
+	let candidates = match self.methods.get(&name) {
+	    Some(ds) => ds.clone(),
+	    None => { return Err(method_not_found(expr.0)); }
+	};
+	let mut signatures = Vec::new();
+	for d in candidates {
+	    match self.ast.get(d.index) {
+		Node::MethodDecl(_,_,params,_) => signatures.push((d,params.clone())),
+		n => {
+		    let msg = format!("expected method, found {}",n.kind_name());
+		    return Err(internal_failure(d.index,&msg));
+		}
+	    }
+	}
+	// Resolve call by number of arguments
+	let arity = args.len();
+	let mut matches : Vec<_> = signatures.iter().filter(|(_,ps)| ps.len() == arity).collect();
+	let (d,params) = match matches.len() {
+	    0 if signatures.len() == 1 => {
+		return Err(arity_mismatch(expr.0,signatures[0].1.len(),arity));
 	    }
+	    0 => { return Err(no_matching_method(expr.0,arity)); }
+	    1 => matches.remove(0).clone(),
+	    _ => { return Err(ambiguous_call(expr.0)); }
+	};
+	let ret = self.return_type(d)?;
+	// Check each argument matches its parameter
+	for (p,arg) in params.iter().zip(args) {
+	    self.check_expr_against(env,p.declared,arg)?;
+	}
+	Ok(ret)
+    }
+
+    // Names
+    // -----------------------------------------------------------------
+
+    /// Resolve a given name into the string it represents.
+    pub fn resolve(&self, name: &Name) -> String {
+	name.as_str(self.ast).to_string()
+    }
+
+    /// Determine whether a given expression is a compile-time
+    /// constant.  For simplicity, this only includes expressions
+    /// built from literals (i.e. not other constants).
+    pub fn is_constant(&self, expr : Expr) -> bool {
+	let n = self.ast.get(expr.0);
+	//
+	match n {
+	    Node::BoolExpr(_)|Node::IntExpr(_,_)|Node::NullExpr|Node::SizeofExpr(_) => true,
+	    Node::AddExpr(lhs,rhs)|Node::SubtractExpr(lhs,rhs)
+		|Node::AndExpr(lhs,rhs)|Node::OrExpr(lhs,rhs)
+		|Node::EqualsExpr(lhs,rhs)|Node::NotEqualsExpr(lhs,rhs)
+		|Node::LessThanExpr(lhs,rhs) => {
+		    self.is_constant(*lhs) && self.is_constant(*rhs)
+		}
+	    Node::NotExpr(e) => self.is_constant(*e),
+	    _ => false
 	}
     }
 
@@ -177,8 +905,20 @@ where F : FnMut(usize,Type) {
 
     /// Check a declared type makes sense.  For example, if a compound
     /// type contains a nominal type which is unknown.
-    pub fn check_type(&self, t : &Type) -> Result<()> {
-	let n = self.ast.get(t.0);
+    pub fn check_type(&mut self, t : &Type) -> Result<()> {
+	// Check whether already done
+	if self.checked.contains(&t.0) {
+	    return Ok(());
+	}
+	self.check_type_structure(t)?;
+	// Only successful checks are remembered
+	self.checked.insert(t.0);
+	Ok(())
+    }
+
+    fn check_type_structure(&mut self, t : &Type) -> Result<()> {
+	// NOTE: clone needed since checking borrows self mutably
+	let n = self.ast.get(t.0).clone();
 	//
 	match n {
 	    // Primitives all fine
@@ -186,16 +926,32 @@ where F : FnMut(usize,Type) {
 	    NullType => { Ok(()) }
 	    IntType(_,_) => { Ok(()) }
 	    VoidType  => { Ok(()) }
+	    // Only permitted as a method's return type
+	    InferredType => Err(invalid_type(t.0,"cannot infer type here")),
 	    // Compounds depend on element
 	    ArrayType(bt) => {
-		self.check_type(&bt)
+		self.check_type(&bt)?;
+		self.check_not_void(&bt,"array element cannot be void")
 	    }
 	    ReferenceType(bt) => {
+		// NOTE: nested references are peeled off iteratively,
+		// since they can be very deeply nested.
+		let mut bt = bt;
+		while let ReferenceType(inner) = self.ast.get(bt.0) {
+		    bt = *inner;
+		}
+		self.check_not_void(&bt,"cannot reference void")?;
 	    	self.check_type(&bt)
 	    }
+	    SizedArrayType(bt,size) => {
+		self.check_type(&bt)?;
+		self.check_not_void(&bt,"array element cannot be void")?;
+		self.check_array_size(size)
+	    }
 	    RecordType(fields) => {
 	    	for (t,n) in fields {
 	    	    self.check_type(&t)?;
+		    self.check_not_void(&t,"record field cannot be void")?;
 	    	}
 	    	Ok(())
 	    }
@@ -205,30 +961,47 @@ where F : FnMut(usize,Type) {
 	}
     }
 
-    /// Check two types have identical structure.
+    /// Check the size given for a sized array type, such as `i32[4]`.
+    /// This must be a compile-time constant which is not negative.
+    pub fn check_array_size(&self, size : Expr) -> Result<()> {
+	match size.as_constant(self.ast) {
+	    Some(n) if n >= 0 => Ok(()),
+	    Some(_) => Err(invalid_type(size.0,"array size cannot be negative")),
+	    None => Err(expected_constant(size.0))
+	}
+    }
+
+    /// Determine the size (in bytes) of a given type (see
+    /// `Type::size_of`).
+    pub fn size_of(&self, t : &Type) -> Option<u64> {
+	t.size_of(self.ast)
+    }
+
+    /// Check two types have identical structure (see
+    /// `Type::structurally_eq`).  The only exception is that `null`
+    /// can be given where a reference is expected (e.g. `&i32 p =
+    /// null`).
     pub fn check_matching_types(&self, t1 : &Type, t2 : &Type) -> Result<()> {
-	let n1 : &Node = self.ast.get(t1.0);
-	let n2 : &Node = self.ast.get(t2.0);
+	let null_ref = matches!((self.ast.get(t1.0),self.ast.get(t2.0)),(ReferenceType(_),NullType));
 	//
-	match (n1,n2) {
-	    // Primitives all fine
-	    (BoolType, BoolType) => { Ok(()) }
-	    (NullType, NullType) => { Ok(()) }
-	    (IntType(b1,w1), IntType(b2,w2)) if (b1 == b2 && w1 == w2) => { Ok(()) }
-	    (VoidType, VoidType) => { Ok(()) }
-	    // Compounds depend on elements
-	    (ArrayType(e1), ArrayType(e2)) => {
-		self.check_matching_types(e1,e2)
-	    }
-	    (ReferenceType(e1), ReferenceType(e2)) => {
-		self.check_matching_types(e1,e2)
-	    }
-	    _ => {
-		panic!("do something");
-	    }
+	if null_ref || Type::structurally_eq(self.ast,*t1,*t2) {
+	    Ok(())
+	} else {
+	    Err(expected_subtype(t2.0,&self.display(*t1),&self.display(*t2)))
 	}
     }
 
+    /// Check a given type is not `void`.  This arises in positions
+    /// where a value is required (e.g. an array element), since no
+    /// value can have type `void`.
+    pub fn check_not_void(&self, t : &Type, reason: &str) -> Result<()> {
+	let n = self.ast.get(t.0);
+	//
+	match n {
+	    VoidType => Err(invalid_type(t.0,reason)),
+	    _ => Ok(())
+	}
+    }
 
     /// Check a given type is a boolean type.
     pub fn check_bool_type(&self, t : Type) -> Result<()> {
@@ -237,9 +1010,7 @@ where F : FnMut(usize,Type) {
 	match n {
 	    // Primitives all fine
 	    BoolType => { Ok(()) }
-	    _ => {
-		panic!("error, expected boolean");
-	    }
+	    _ => Err(expected_subtype(t.0,"bool",&self.display(t)))
 	}
     }
 
@@ -250,9 +1021,12 @@ where F : FnMut(usize,Type) {
 	match n {
 	    // Primitives all fine
 	    IntType(_,_) => { Ok(()) }
-	    _ => {
-		panic!("error, expected integer");
-	    }
+	    _ => Err(expected_subtype(t.0,"integer",&self.display(t)))
 	}
     }
+
+    /// Render a given type in source form for use in error messages.
+    fn display(&self, t : Type) -> String {
+	display_type(self.ast,t)
+    }
 }
@@ -0,0 +1,219 @@
+use std::result;
+use crate::ast::*;
+use crate::error::*;
+
+// =================================================================
+// Error
+// =================================================================
+
+pub type Result<T> = result::Result<T, SyntaxError>;
+
+// =================================================================
+// Verifier
+// =================================================================
+
+/// Identifies the category of node expected at a given position in
+/// the tree.
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum Category {
+    Decl,
+    Stmt,
+    Expr,
+    Type,
+    Name
+}
+
+/// Check the invariants of the heap for all nodes reachable from a
+/// given set of declarations.  Specifically, that every child index
+/// is within range and refers to a node of the expected category
+/// (e.g. the element of an `ArrayType` is a type).  This is useful
+/// when trees are produced by something other than the parser, such
+/// as a transformation pass.  The first violation found is reported
+/// against the offending parent node.
+pub fn verify(ast: &AbstractSyntaxTree, roots: &[Decl]) -> Result<()> {
+    for d in roots {
+	if d.index >= ast.len() {
+	    return Err(internal_failure(d.index,"declaration out of range"));
+	}
+	verify_node(ast,d.index,Category::Decl)?;
+    }
+    Ok(())
+}
+
+/// Verify a node (which is known to be in range) has the expected
+/// category and, if so, verify its children.
+fn verify_node(ast: &AbstractSyntaxTree, index: usize, expected: Category) -> Result<()> {
+    let n = ast.get(index);
+    // Check category matches
+    if category(n) != Some(expected) {
+	return Err(internal_failure(index,"unexpected node category"));
+    }
+    // Check children
+    match n {
+	Node::Utf8(_) => Ok(()),
+	Node::Error => Ok(()),
+	Node::AnnotatedDecl(anns,d) => {
+	    for a in anns {
+		verify_child(ast,index,a.name.0,Category::Name)?;
+		for e in &a.args {
+		    verify_child(ast,index,e.0,Category::Expr)?;
+		}
+	    }
+	    verify_child(ast,index,d.index,Category::Decl)
+	}
+	Node::ConstDecl(t,name,e) => {
+	    verify_child(ast,index,t.0,Category::Type)?;
+	    verify_child(ast,index,name.0,Category::Name)?;
+	    verify_child(ast,index,e.0,Category::Expr)
+	}
+	Node::TypeDecl(name,t) => {
+	    verify_child(ast,index,name.0,Category::Name)?;
+	    verify_child(ast,index,t.0,Category::Type)
+	}
+	Node::MethodDecl(name,ret,params,body) => {
+	    verify_child(ast,index,name.0,Category::Name)?;
+	    verify_child(ast,index,ret.0,Category::Type)?;
+	    for p in params {
+		verify_child(ast,index,p.declared.0,Category::Type)?;
+		verify_child(ast,index,p.name.0,Category::Name)?;
+		verify_child(ast,index,p.site.0,Category::Name)?;
+	    }
+	    verify_child(ast,index,body.0,Category::Stmt)
+	}
+	Node::AssertStmt(e)|Node::PrintStmt(e) => {
+	    verify_child(ast,index,e.0,Category::Expr)
+	}
+	Node::AssignStmt(lhs,rhs) => {
+	    verify_child(ast,index,lhs.0,Category::Expr)?;
+	    verify_child(ast,index,rhs.0,Category::Expr)
+	}
+	Node::BlockStmt(stmts) => {
+	    for s in stmts {
+		verify_child(ast,index,s.0,Category::Stmt)?;
+	    }
+	    Ok(())
+	}
+	Node::IfStmt(cond,tt,ff) => {
+	    verify_child(ast,index,cond.0,Category::Expr)?;
+	    verify_child(ast,index,tt.0,Category::Stmt)?;
+	    match ff {
+		Some(ff) => verify_child(ast,index,ff.0,Category::Stmt),
+		None => Ok(())
+	    }
+	}
+	Node::ReturnStmt(e) => {
+	    match e {
+		Some(e) => verify_child(ast,index,e.0,Category::Expr),
+		None => Ok(())
+	    }
+	}
+	Node::ForStmt(init,cond,update,body) => {
+	    verify_child(ast,index,init.0,Category::Stmt)?;
+	    verify_child(ast,index,cond.0,Category::Expr)?;
+	    verify_child(ast,index,update.0,Category::Stmt)?;
+	    verify_child(ast,index,body.0,Category::Stmt)
+	}
+	Node::BreakStmt|Node::ContinueStmt|Node::SkipStmt => Ok(()),
+	Node::SwitchStmt(e,cases) => {
+	    verify_child(ast,index,e.0,Category::Expr)?;
+	    for (label,body) in cases {
+		if let Some(l) = label {
+		    verify_child(ast,index,l.0,Category::Expr)?;
+		}
+		verify_child(ast,index,body.0,Category::Stmt)?;
+	    }
+	    Ok(())
+	}
+	Node::TypeAliasStmt(name,t) => {
+	    verify_child(ast,index,name.0,Category::Name)?;
+	    verify_child(ast,index,t.0,Category::Type)
+	}
+	Node::VarDeclStmt(t,name,e) => {
+	    verify_child(ast,index,t.0,Category::Type)?;
+	    verify_child(ast,index,name.0,Category::Name)?;
+	    verify_child(ast,index,e.0,Category::Expr)
+	}
+	Node::WhileStmt(cond,body) => {
+	    verify_child(ast,index,cond.0,Category::Expr)?;
+	    verify_child(ast,index,body.0,Category::Stmt)
+	}
+	Node::AddExpr(lhs,rhs)|Node::SubtractExpr(lhs,rhs)
+	    |Node::AndExpr(lhs,rhs)|Node::OrExpr(lhs,rhs)
+	    |Node::EqualsExpr(lhs,rhs)|Node::NotEqualsExpr(lhs,rhs)
+	    |Node::LessThanExpr(lhs,rhs) => {
+	    verify_child(ast,index,lhs.0,Category::Expr)?;
+	    verify_child(ast,index,rhs.0,Category::Expr)
+	}
+	Node::BoolExpr(_) => Ok(()),
+	Node::CallExpr(name,args) => {
+	    verify_child(ast,index,name.0,Category::Name)?;
+	    for a in args {
+		verify_child(ast,index,a.0,Category::Expr)?;
+	    }
+	    Ok(())
+	}
+	Node::ArrayAccessExpr(e,i) => {
+	    verify_child(ast,index,e.0,Category::Expr)?;
+	    verify_child(ast,index,i.0,Category::Expr)
+	}
+	Node::FieldAccessExpr(e,name) => {
+	    verify_child(ast,index,e.0,Category::Expr)?;
+	    verify_child(ast,index,name.0,Category::Name)
+	}
+	Node::IntExpr(_,_) => Ok(()),
+	Node::NullExpr => Ok(()),
+	Node::LenExpr(e)|Node::NotExpr(e) => {
+	    verify_child(ast,index,e.0,Category::Expr)
+	}
+	Node::SizeofExpr(t) => {
+	    verify_child(ast,index,t.0,Category::Type)
+	}
+	Node::VarExpr(name) => {
+	    verify_child(ast,index,name.0,Category::Name)
+	}
+	Node::ArrayType(t)|Node::ReferenceType(t) => {
+	    verify_child(ast,index,t.0,Category::Type)
+	}
+	Node::SizedArrayType(t,e) => {
+	    verify_child(ast,index,t.0,Category::Type)?;
+	    verify_child(ast,index,e.0,Category::Expr)
+	}
+	Node::RecordType(fields) => {
+	    for (t,name) in fields {
+		verify_child(ast,index,t.0,Category::Type)?;
+		verify_child(ast,index,name.0,Category::Name)?;
+	    }
+	    Ok(())
+	}
+	Node::BoolType|Node::IntType(_,_)|Node::InferredType|Node::NullType|Node::VoidType => Ok(())
+    }
+}
+
+/// Verify a child of a given parent node.  Any violation arising from
+/// the child being out of range or of the wrong category is reported
+/// against the parent, since the child may not exist.
+fn verify_child(ast: &AbstractSyntaxTree, parent: usize, child: usize, expected: Category) -> Result<()> {
+    if child >= ast.len() {
+	return Err(internal_failure(parent,"child index out of range"));
+    } else if ast.get(child) == &Node::Error {
+	// Placeholders are permitted in any position
+	return Ok(());
+    } else if category(ast.get(child)) != Some(expected) {
+	return Err(internal_failure(parent,"child has unexpected category"));
+    }
+    verify_node(ast,child,expected)
+}
+
+/// Determine the category of a given node.  Note this does not look
+/// at the node's children.
+fn category(n: &Node) -> Option<Category> {
+    match n {
+	Node::Utf8(_) => Some(Category::Name),
+	_ if Decl::is(n) => Some(Category::Decl),
+	_ if Stmt::is(n) => Some(Category::Stmt),
+	_ if Expr::is(n) => Some(Category::Expr),
+	Node::ArrayType(_)|Node::BoolType|Node::IntType(_,_)|Node::InferredType|Node::NullType
+	    |Node::RecordType(_)|Node::ReferenceType(_)|Node::SizedArrayType(_,_)|Node::VoidType => Some(Category::Type),
+	_ => None
+    }
+}
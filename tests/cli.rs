@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command,Output};
+
+// ======================================================
+// Tests (AST JSON)
+// ======================================================
+
+#[test]
+fn test_ast_json_01() {
+    let out = run("ast_json_01.lil","type t = i32;\nconst t c = 1;\nvoid f() { skip; }\n",&["--ast-json"]);
+    assert!(out.status.success());
+    let json : serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let nodes = json["nodes"].as_array().unwrap();
+    let kinds : Vec<&str> = json["roots"].as_array().unwrap().iter().map(|r| {
+	let node = nodes[r.as_u64().unwrap() as usize].as_object().unwrap();
+	node.keys().next().unwrap().as_str()
+    }).collect();
+    assert_eq!(kinds,["TypeDecl","ConstDecl","MethodDecl"]);
+}
+
+#[test]
+fn test_ast_json_02() {
+    // Output deserialises into an equivalent tree
+    let input = "@test bool f(i32 x) { return x < 1; }";
+    let out = run("ast_json_02.lil",input,&["--ast-json"]);
+    let ast = lil::ast::Ast::from_json(std::str::from_utf8(&out.stdout).unwrap()).unwrap();
+    let (expected,_) = lil::parse(input).unwrap();
+    assert_eq!(ast.len(),expected.len());
+    for i in 0..ast.len() {
+	assert_eq!(ast.get(i),expected.get(i));
+    }
+    assert_eq!(ast.roots(),expected.roots());
+}
+
+#[test]
+fn test_ast_json_03() {
+    // Parse errors are reported on stderr
+    let out = run("ast_json_03.lil","type t = ;",&["--ast-json"]);
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+    let err = String::from_utf8(out.stderr).unwrap();
+    assert!(err.starts_with("error: "));
+}
+
+// ======================================================
+// Tests (Max Errors)
+// ======================================================
+
+#[test]
+fn test_max_errors_01() {
+    let input = "bool f() { return 1; }\nbool g() { return 1; }\nbool h() { return 1; }\n";
+    let out = run("max_errors_01a.lil",input,&[]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(),"error[E0002]: expected bool, found i32\n");
+    let out = run("max_errors_01b.lil",input,&["--max-errors","5"]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap().lines().count(),3);
+    let out = run("max_errors_01c.lil",input,&["--max-errors","1"]);
+    let out = String::from_utf8(out.stdout).unwrap();
+    let lines : Vec<&str> = out.lines().collect();
+    assert_eq!(lines,["error[E0002]: expected bool, found i32","error[E0018]: too many errors; stopping"]);
+}
+
+#[test]
+fn test_max_errors_02() {
+    // Parse errors are capped as well
+    let input = "type t = ;\ntype u = ;\ntype v = ;\n";
+    let out = run("max_errors_02a.lil",input,&["--max-errors","2"]);
+    let out = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(out.matches("error: unknown token encountered").count(),2);
+    assert!(out.ends_with("error: too many errors; stopping\ntype v = ;\n         ^\n"));
+}
+
+#[test]
+fn test_max_errors_03() {
+    // A limit of zero is rejected
+    let out = run("max_errors_03a.lil","type t = ;\n",&["--max-errors","0"]);
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+    assert_eq!(String::from_utf8(out.stderr).unwrap(),"expected positive number after --max-errors\n");
+}
+
+// ======================================================
+// Helpers
+// ======================================================
+
+/// Write a given input to a file, and then run the compiler on it
+/// with the given flags.
+fn run(filename: &str, input: &str, flags: &[&str]) -> Output {
+    let path : PathBuf = [env!("CARGO_TARGET_TMPDIR"),filename].iter().collect();
+    fs::write(&path,input).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_lil")).args(flags).arg(&path).output().unwrap()
+}
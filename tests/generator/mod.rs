@@ -0,0 +1,25 @@
+// ======================================================
+// Program Generator
+// ======================================================
+
+/// Generate a (well-typed) program consisting of `n` method
+/// declarations.  Each method calls the one before it, such that the
+/// program exercises a reasonable mix of declarations, statements and
+/// expressions.  This is used for benchmarking, as well as in tests
+/// to check the generated program is valid.
+#[allow(dead_code)]
+pub fn generate_program(n: usize) -> String {
+    let mut out = String::new();
+    for i in 0..n {
+	out.push_str(&format!("i32 f{}(i32 x, bool b) {{\n",i));
+	out.push_str("    i32 y = x + 1;\n");
+	out.push_str("    while(y < 10 && b) {\n        y = y + 2;\n    }\n");
+	if i == 0 {
+	    out.push_str("    return y - x;\n");
+	} else {
+	    out.push_str(&format!("    return f{}(y,!b) - x;\n",i-1));
+	}
+	out.push_str("}\n");
+    }
+    out
+}
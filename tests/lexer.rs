@@ -0,0 +1,153 @@
+use lil::lexer::{Lexer,TokenType};
+use lil::lexer::TokenType::*;
+
+// ======================================================
+// Tests (Operators)
+// ======================================================
+
+#[test]
+fn test_tokens_01() {
+    check_tokens("= == ===",&[(Equal,"="),(EqualEqual,"=="),(EqualEqual,"=="),(Equal,"=")]);
+}
+
+#[test]
+fn test_tokens_02() {
+    check_tokens("! != !!=",&[(Shreak,"!"),(ShreakEquals,"!="),(Shreak,"!"),(ShreakEquals,"!=")]);
+}
+
+#[test]
+fn test_tokens_03() {
+    check_tokens("& && &&&",&[(Ampersand,"&"),(AmpersandAmpersand,"&&"),
+			      (AmpersandAmpersand,"&&"),(Ampersand,"&")]);
+}
+
+#[test]
+fn test_tokens_04() {
+    check_tokens("| || |||",&[(Bar,"|"),(BarBar,"||"),(BarBar,"||"),(Bar,"|")]);
+}
+
+#[test]
+fn test_tokens_05() {
+    check_tokens("x++ + --y-1",&[(Identifier,"x"),(PlusPlus,"++"),(Plus,"+"),(MinusMinus,"--"),
+				 (Identifier,"y"),(Minus,"-"),(Integer,"1")]);
+}
+
+#[test]
+fn test_tokens_06() {
+    check_tokens("a<b&&c!=d||!e",&[(Identifier,"a"),(LeftAngle,"<"),(Identifier,"b"),
+				   (AmpersandAmpersand,"&&"),(Identifier,"c"),(ShreakEquals,"!="),
+				   (Identifier,"d"),(BarBar,"||"),(Shreak,"!"),(Identifier,"e")]);
+}
+
+#[test]
+fn test_tokens_07() {
+    check_tokens("({[]});,.:@",&[(LeftBrace,"("),(LeftCurly,"{"),(LeftSquare,"["),(RightSquare,"]"),
+				 (RightCurly,"}"),(RightBrace,")"),(SemiColon,";"),(Comma,","),
+				 (Dot,"."),(Colon,":"),(At,"@")]);
+}
+
+// ======================================================
+// Tests (Comments)
+// ======================================================
+
+#[test]
+fn test_tokens_08() {
+    check_tokens("x // y = 1;\nz",&[(Identifier,"x"),(Identifier,"z")]);
+}
+
+#[test]
+fn test_tokens_09() {
+    check_tokens("x /* y\n = */ z",&[(Identifier,"x"),(Identifier,"z")]);
+}
+
+#[test]
+fn test_tokens_10() {
+    // Unterminated block comments run to the end of the input
+    check_tokens("x /* y = 1;",&[(Identifier,"x")]);
+}
+
+#[test]
+fn test_tokens_11() {
+    check_tokens("a / b * c // d",&[(Identifier,"a"),(RightSlash,"/"),(Identifier,"b"),
+				    (Star,"*"),(Identifier,"c")]);
+}
+
+// ======================================================
+// Tests (Literals)
+// ======================================================
+
+#[test]
+fn test_tokens_12() {
+    check_tokens("0 42 007",&[(Integer,"0"),(Integer,"42"),(Integer,"007")]);
+}
+
+#[test]
+fn test_tokens_13() {
+    check_tokens("true false null",&[(True,"true"),(False,"false"),(Null,"null")]);
+}
+
+#[test]
+fn test_tokens_14() {
+    // Keywords are only recognised as whole words
+    check_tokens("truex nullable i32x i32",&[(Identifier,"truex"),(Identifier,"nullable"),
+					     (Identifier,"i32x"),(I32,"i32")]);
+}
+
+// ======================================================
+// Tests (Declarations)
+// ======================================================
+
+#[test]
+fn test_tokens_15() {
+    check_tokens("type rec = {i32 f, u16 g};",&[(Type,"type"),(Identifier,"rec"),(Equal,"="),
+						(LeftCurly,"{"),(I32,"i32"),(Identifier,"f"),
+						(Comma,","),(U16,"u16"),(Identifier,"g"),
+						(RightCurly,"}"),(SemiColon,";")]);
+}
+
+#[test]
+fn test_tokens_16() {
+    check_tokens("bool f(&i32 p) { return p == null; }",
+		 &[(Bool,"bool"),(Identifier,"f"),(LeftBrace,"("),(Ampersand,"&"),(I32,"i32"),
+		   (Identifier,"p"),(RightBrace,")"),(LeftCurly,"{"),(Return,"return"),
+		   (Identifier,"p"),(EqualEqual,"=="),(Null,"null"),(SemiColon,";"),
+		   (RightCurly,"}")]);
+}
+
+// ======================================================
+// Tests (Debug)
+// ======================================================
+
+#[test]
+fn test_tokens_17() {
+    let mut lexer = Lexer::new("x = 42;");
+    lexer.next();
+    lexer.next();
+    let tok = lexer.next();
+    assert_eq!(format!("{:?}",tok),"Token { kind: Integer, start: 4, content: \"42\" }");
+}
+
+#[test]
+fn test_tokens_18() {
+    assert_eq!(format!("{:?}",AmpersandAmpersand),"AmpersandAmpersand");
+    let tok = Lexer::new("  i32").next();
+    assert_eq!(format!("{:?}",tok),"Token { kind: I32, start: 2, content: \"i32\" }");
+}
+
+// ======================================================
+// Helpers
+// ======================================================
+
+/// Lex a given input, and check it produces exactly the expected
+/// sequence of token types and contents (followed by the end of the
+/// input).
+fn check_tokens(input: &str, expected: &[(TokenType,&str)]) {
+    let mut lexer = Lexer::new(input);
+    for (i,(kind,content)) in expected.iter().enumerate() {
+	let tok = lexer.next();
+	assert_eq!(tok.content,*content,"token {} of {:?}",i,input);
+	assert_eq!(tok.kind,*kind,"token {} of {:?}",i,input);
+    }
+    let tok = lexer.next();
+    assert_eq!(tok.kind,TokenType::EOF,"unexpected token {:?} at end of {:?}",tok,input);
+}
@@ -1,6 +1,15 @@
 use lil::ast::*;
-use lil::parser::Parser;
-use lil::typer::TypeChecker;
+use lil::parser::{binary_operator,Assoc,Error,Parser};
+use lil::lexer::{Lexer,TokenType};
+use lil::typer::{Env,TypeChecker,DEFAULT_MAX_ERRORS};
+use lil::error::{CompileError,ErrorCode,Severity,SyntaxError};
+use lil::verifier::verify;
+use lil::interpreter::{Frame,Interpreter,Value};
+use lil::source_map::SourceMap;
+use lil::printer::{display_type,format};
+use lil::optimizer;
+
+mod generator;
 
 // ======================================================
 // Tests (Type Declarations)
@@ -155,6 +164,314 @@ fn test_type_19() {
     assert_eq!(ast.get(7),&Node::RecordType(vec![(Type(2),Name(3)),(Type(5),Name(6))]));
 }
 
+#[test]
+fn test_type_20() {
+    let ast = check_parse("type rec = {i32 f,};");
+    check_name(ast.get(2),"f");
+    assert_eq!(ast.get(3),&Node::RecordType(vec![(Type(1),Name(2))]));
+}
+
+#[test]
+fn test_type_21() {
+    check_parse_error("type rec = {i32 f,,};");
+}
+
+#[test]
+fn test_type_22() {
+    check_parse_error("type rec = {,};");
+}
+
+#[test]
+fn test_type_23() {
+    let err = check_type_error("type t = void[];");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
+}
+
+#[test]
+fn test_type_24() {
+    let err = check_type_error("type t = {void f};");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
+}
+
+#[test]
+fn test_type_25() {
+    let mut ast = check_parse("type arr = i32[];");
+    let root = clone_subtree(&mut ast,2);
+    // Fresh nodes
+    assert!(root != 2);
+    let elem = match ast.get(root) {
+	Node::ArrayType(t) => *t,
+	n => panic!("unexpected node {:?}",n)
+    };
+    assert!(elem != Type(1));
+    // Same structure
+    assert_eq!(ast.get(elem.0),&Node::IntType(true,32));
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::ArrayType(Type(1)));
+}
+
+#[test]
+fn test_type_26() {
+    let ast = check_parse("type rec = {i32 f, u16 g};");
+    assert_eq!(ast.get(5).children(),vec![1,2,3,4]);
+    assert!(!ast.get(5).is_leaf());
+    assert!(ast.get(1).is_leaf());
+    assert!(ast.get(2).is_leaf());
+}
+
+#[test]
+fn test_type_27() {
+    assert!(check_types_match("(&i32)[]","(&i32)[]"));
+    assert!(check_types_match("&(i32[])","&(i32[])"));
+}
+
+#[test]
+fn test_type_28() {
+    assert!(!check_types_match("(&i32)[]","&(i32[])"));
+    assert!(!check_types_match("&(i32[])","(&i32)[]"));
+}
+
+#[test]
+fn test_type_29() {
+    assert!(!check_types_match("&&i32","&(i32[])"));
+    assert!(!check_types_match("i32[][]","(&i32)[]"));
+}
+
+#[test]
+fn test_type_30() {
+    let ast = check_parse("type t = i32[4];");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::IntExpr(false,4));
+    assert_eq!(ast.get(3),&Node::SizedArrayType(Type(1),Expr(2)));
+}
+
+#[test]
+fn test_type_31() {
+    let ast = check_parse("type t = bool[2][];");
+    assert_eq!(ast.get(3),&Node::SizedArrayType(Type(1),Expr(2)));
+    assert_eq!(ast.get(4),&Node::ArrayType(Type(3)));
+}
+
+#[test]
+fn test_type_32() {
+    check_parse("type t = i32[0];");
+    check_parse("type t = i32[sizeof(i64)];");
+}
+
+#[test]
+fn test_type_33() {
+    let err = check_type_error("type t = i32[-1];");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
+}
+
+#[test]
+fn test_type_34() {
+    let err = check_type_error("type t = i32[x];");
+    assert!(matches!(err.errno,ErrorCode::ExpectedConstant));
+}
+
+#[test]
+fn test_type_35() {
+    let err = check_type_error("type t = void[1];");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
+}
+
+#[test]
+fn test_type_36() {
+    assert!(check_types_match("i32[2]","i32[sizeof(u16)]"));
+    assert!(!check_types_match("i32[2]","i32[3]"));
+    assert!(!check_types_match("i32[2]","i32[]"));
+}
+
+#[test]
+fn test_type_37() {
+    assert!(check_structurally_eq("bool","bool"));
+    assert!(check_structurally_eq("u16[]","u16[]"));
+    assert!(!check_structurally_eq("u16","i16"));
+    assert!(!check_structurally_eq("i32","i64"));
+}
+
+#[test]
+fn test_type_38() {
+    assert!(check_structurally_eq("{i32 f, bool g}","{i32 f, bool g}"));
+    assert!(!check_structurally_eq("{i32 f, bool g}","{i32 f, bool h}"));
+    assert!(!check_structurally_eq("{i32 f, bool g}","{bool g, i32 f}"));
+    assert!(!check_structurally_eq("{i32 f}","{i32 f, bool g}"));
+}
+
+#[test]
+fn test_type_39() {
+    assert!(check_structurally_eq("{&i8 f}","{&i8 f}"));
+    assert!(!check_structurally_eq("{&i8 f}","{i8 f}"));
+    assert!(!check_structurally_eq("null","void"));
+}
+
+#[test]
+fn test_type_40() {
+    let err = check_parse_error("type t = {i32 f");
+    assert_eq!(err.start,9);
+    assert_eq!(err.message,"unclosed '{' opened here");
+}
+
+#[test]
+fn test_type_41() {
+    let ast = check_parse("type t = &i32[];");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::ArrayType(Type(1)));
+    assert_eq!(ast.get(3),&Node::ReferenceType(Type(2)));
+}
+
+#[test]
+fn test_type_42() {
+    let ast = check_parse("type t = &(i32[]);");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::ArrayType(Type(1)));
+    assert_eq!(ast.get(3),&Node::ReferenceType(Type(2)));
+}
+
+#[test]
+fn test_type_43() {
+    let ast = check_parse("type t = &&i32[][];");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::ArrayType(Type(1)));
+    assert_eq!(ast.get(3),&Node::ArrayType(Type(2)));
+    assert_eq!(ast.get(4),&Node::ReferenceType(Type(3)));
+    assert_eq!(ast.get(5),&Node::ReferenceType(Type(4)));
+}
+
+#[test]
+fn test_type_44() {
+    let ast = check_parse("type t = (&i32[])[];");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::ArrayType(Type(1)));
+    assert_eq!(ast.get(3),&Node::ReferenceType(Type(2)));
+    assert_eq!(ast.get(4),&Node::ArrayType(Type(3)));
+}
+
+#[test]
+fn test_type_45() {
+    let ast = check_parse("type t = &(&i32)[];");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::ReferenceType(Type(1)));
+    assert_eq!(ast.get(3),&Node::ArrayType(Type(2)));
+    assert_eq!(ast.get(4),&Node::ReferenceType(Type(3)));
+}
+
+#[test]
+fn test_type_46() {
+    assert!(check_types_match("&i32[]","&(i32[])"));
+    assert!(!check_types_match("&i32[]","(&i32)[]"));
+}
+
+#[test]
+fn test_type_47() {
+    let err = check_parse_error("type while = i32;");
+    assert_eq!(err.start,5);
+    assert_eq!(err.end,10);
+    assert_eq!(err.message,"'while' is a reserved keyword and cannot be used as a name");
+}
+
+#[test]
+fn test_type_48() {
+    let err = check_parse_error("type t = {i32 bool};");
+    assert_eq!(err.message,"'bool' is a reserved keyword and cannot be used as a name");
+}
+
+#[test]
+fn test_type_49() {
+    let err = check_parse_error("type nat = i32");
+    assert_eq!(err.start,14);
+    assert_eq!(err.end,14);
+}
+
+#[test]
+fn test_type_50() {
+    let err = check_parse_error("type t = ");
+    assert_eq!(err.start,9);
+    assert_eq!(err.message,"unexpected end-of-file");
+}
+
+#[test]
+fn test_type_51() {
+    let types = [("i8",true,8),("i16",true,16),("i32",true,32),("i64",true,64),
+		 ("i128",true,128),("u8",false,8),("u16",false,16),("u32",false,32),
+		 ("u64",false,64),("u128",false,128)];
+    for (kw,signed,width) in types.iter() {
+	let ast = check_parse(&format!("type t = {};",kw));
+	assert_eq!(ast.get(1),&Node::IntType(*signed,*width));
+    }
+}
+
+#[test]
+fn test_type_52() {
+    let ast = check_parse("type t = {i128 f, u128[] g};");
+    assert_eq!(ast.get(1),&Node::IntType(true,128));
+    assert_eq!(ast.get(3),&Node::IntType(false,128));
+    assert!(check_types_match("i128","i128"));
+    assert!(!check_types_match("i128","u128"));
+    assert!(!check_types_match("i64","i128"));
+}
+
+#[test]
+fn test_type_53() {
+    // Structurally identical types share a single node
+    let (ast,_) = lil::parse("type t = {i32 f, i32[] g}; i32 h(i32 x, i32[] y, &i32 z) { return x; }").unwrap();
+    let count = |n: &Node| (0..ast.len()).filter(|i| ast.get(*i) == n).count();
+    assert_eq!(count(&Node::IntType(true,32)),1);
+    let i32_t = (0..ast.len()).find(|i| ast.get(*i) == &Node::IntType(true,32)).unwrap();
+    assert_eq!(count(&Node::ArrayType(Type(i32_t))),1);
+    assert_eq!(count(&Node::ReferenceType(Type(i32_t))),1);
+}
+
+#[test]
+fn test_type_54() {
+    let (ast,ds) = lil::parse("void f(i32 x, i32 y, u32 z) {}").unwrap();
+    match ast.get(ds[0].index) {
+	Node::MethodDecl(_,_,ps,_) => {
+	    assert_eq!(ps[0].declared,ps[1].declared);
+	    assert_ne!(ps[0].declared,ps[2].declared);
+	}
+	n => panic!("unexpected node {:?}",n)
+    }
+}
+
+#[test]
+fn test_type_55() {
+    // An error can cover several tokens, such as a whole type
+    let mut lexer = Lexer::new("&&(i32)[] x");
+    let first = lexer.next();
+    let mut last = first;
+    while lexer.peek().kind != TokenType::Identifier {
+	last = lexer.next();
+    }
+    let err = Error::span_from(first,last,"invalid type");
+    assert_eq!((err.start,err.end),(0,9));
+}
+
+#[test]
+fn test_type_56() {
+    // References to void parse, but are rejected by the type checker
+    for input in ["type t = &&(void);","void f(&void x) {}"] {
+	let err = check_program_error(input);
+	assert_eq!(err.errno.to_string(),"invalid type (cannot reference void)");
+    }
+}
+
+#[test]
+fn test_type_57() {
+    // Pins down the order in which the parser pushes nodes, which
+    // index-based consumers depend upon.
+    let ast = check_parse("type rec = {i32 f, u16 g};");
+    assert_eq!(ast.len(),7);
+    check_name(ast.get(0),"rec");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    check_name(ast.get(2),"f");
+    assert_eq!(ast.get(3),&Node::IntType(false,16));
+    check_name(ast.get(4),"g");
+    assert_eq!(ast.get(5),&Node::RecordType(vec![(Type(1),Name(2)),(Type(3),Name(4))]));
+    assert_eq!(ast.get(6),&Node::TypeDecl(Name(0),Type(5)));
+}
+
 // ======================================================
 // Tests (Method Declarations)
 // ======================================================
@@ -200,9 +517,10 @@ fn test_method_07() {
     check_name(ast.get(1),"f");
     assert_eq!(ast.get(2),&Node::IntType(true,32));
     check_name(ast.get(3),"x");
-    assert_eq!(ast.get(4),&Node::BlockStmt(vec![]));
-    let params = vec![Parameter{declared:Type(2),name:Name(3)}];
-    assert_eq!(ast.get(5),&Node::MethodDecl(Name(1),Type(0),params,Stmt(4)));
+    check_name(ast.get(4),"x");
+    assert_eq!(ast.get(5),&Node::BlockStmt(vec![]));
+    let params = vec![Parameter{declared:Type(2),name:Name(3),site:Name(4)}];
+    assert_eq!(ast.get(6),&Node::MethodDecl(Name(1),Type(0),params,Stmt(5)));
 }
 
 #[test]
@@ -212,145 +530,2850 @@ fn test_method_08() {
     check_name(ast.get(1),"f");
     assert_eq!(ast.get(2),&Node::IntType(true,32));
     check_name(ast.get(3),"i");
-    assert_eq!(ast.get(4),&Node::BoolType);
+    check_name(ast.get(4),"i");
+    // NOTE: bool type is shared
     check_name(ast.get(5),"b");
-    assert_eq!(ast.get(6),&Node::BlockStmt(vec![]));
-    let params = vec![Parameter{declared:Type(2),name:Name(3)},Parameter{declared:Type(4),name:Name(5)}];
-    assert_eq!(ast.get(7),&Node::MethodDecl(Name(1),Type(0),params,Stmt(6)));
+    check_name(ast.get(6),"b");
+    assert_eq!(ast.get(7),&Node::BlockStmt(vec![]));
+    let params = vec![Parameter{declared:Type(2),name:Name(3),site:Name(4)},Parameter{declared:Type(0),name:Name(5),site:Name(6)}];
+    assert_eq!(ast.get(8),&Node::MethodDecl(Name(1),Type(0),params,Stmt(7)));
 }
 
-// ======================================================
-// Tests (Skip)
-// ======================================================
-
 #[test]
-fn test_skip_01() {
-    check_parse_error("void f() { ski }");
+fn test_method_09() {
+    let ast = check_parse("void f(i32 x,) {}");
+    let params = vec![Parameter{declared:Type(2),name:Name(3),site:Name(4)}];
+    assert_eq!(ast.get(6),&Node::MethodDecl(Name(1),Type(0),params,Stmt(5)));
 }
 
 #[test]
-fn test_skip_02() {
-    check_parse_error("void f() { skip }");
+fn test_method_10() {
+    check_parse_error("void f(i32 x,,) {}");
 }
 
 #[test]
-fn test_skip_03() {
-    let ast = check_parse("void f() { skip; }");
-    assert_eq!(ast.get(2),&Node::SkipStmt);
+fn test_method_11() {
+    check_parse_error("void f(,) {}");
 }
 
-// ======================================================
-// Tests (Assert)
-// ======================================================
-
 #[test]
-fn test_assert_04() {
-    check_parse_error("void f() { asse");
+fn test_method_12() {
+    let err = check_type_error("void f(void x) {}");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
 }
 
 #[test]
-fn test_assert_05() {
-    check_parse_error("void f() { assert");
+fn test_method_13() {
+    let err = check_parse_error("void f() {");
+    assert_eq!(err.start,9);
+    assert_eq!(err.end,10);
+    assert_eq!(err.message,"unclosed '{' opened here");
 }
 
 #[test]
-fn test_assert_06() {
-    check_parse_error("void f() { assert true }");
+fn test_method_14() {
+    let err = check_parse_error("void f(i32 x");
+    assert_eq!(err.start,6);
+    assert_eq!(err.end,7);
+    assert_eq!(err.message,"unclosed '(' opened here");
 }
 
 #[test]
-fn test_assert_07() {
-    let ast = check_parse("void f() { assert true; }");
-    assert_eq!(ast.get(2),&Node::BoolExpr(true));
-    assert_eq!(ast.get(3),&Node::AssertStmt(Expr(2)));
+fn test_method_15() {
+    let err = check_parse_error("void f() { skip;");
+    assert_eq!(err.start,9);
+    assert_eq!(err.message,"unclosed '{' opened here");
 }
 
 #[test]
-fn test_assert_08() {
-    let ast = check_parse("void f() { assert false; }");
-    assert_eq!(ast.get(2),&Node::BoolExpr(false));
-    assert_eq!(ast.get(3),&Node::AssertStmt(Expr(2)));
+fn test_method_16() {
+    let ast = check_parse("bool f(i32 i, bool b) {}");
+    assert_eq!(ast.get(8).children(),vec![1,0,2,3,4,0,5,6,7]);
+    assert!(ast.get(5).is_leaf());
 }
 
 #[test]
-fn test_assert_09() {
-    let ast = check_parse("void f() { assert (false); }");
-    assert_eq!(ast.get(2),&Node::BoolExpr(false));
-    assert_eq!(ast.get(3),&Node::AssertStmt(Expr(2)));
+fn test_method_17() {
+    let err = check_program_error("type foo = i32; void foo() {}");
+    assert!(matches!(err.errno,ErrorCode::DuplicateDeclaration));
+    assert_eq!(err.node,5);
 }
 
 #[test]
-fn test_assert_10() {
-    check_type_error("void f() { assert b; }");
+fn test_method_18() {
+    let err = check_program_error("void f(i32 y) {} bool f(i32 x) {}");
+    assert!(matches!(err.errno,ErrorCode::DuplicateDeclaration));
+    assert_eq!(err.node,11);
 }
 
 #[test]
-fn test_assert_11() {
-    let ast = check_parse("void f(bool b) { assert b; }");
-    check_name(ast.get(4),"b");
-    assert_eq!(ast.get(5),&Node::VarExpr(Name(4)));
-    assert_eq!(ast.get(6),&Node::AssertStmt(Expr(5)));
+fn test_method_19() {
+    let err = check_program_error("type t = i32; type t = bool;");
+    assert!(matches!(err.errno,ErrorCode::DuplicateDeclaration));
 }
 
 #[test]
-fn test_assert_12() {
-    let ast = check_parse("void f(i32 i) { assert i < 0; }");
-    assert_eq!(ast.get(4),&Node::VarExpr(Name(3)));
-    assert_eq!(ast.get(5),&Node::IntExpr(0));
-    assert_eq!(ast.get(6),&Node::LessThanExpr(Expr(4),Expr(5)));
-    assert_eq!(ast.get(7),&Node::AssertStmt(Expr(6)));
+fn test_method_20() {
+    check_program("type t = i32; void f() {} void g() {}");
 }
 
-// ======================================================
-// Helpers
-// ======================================================
+#[test]
+fn test_method_21() {
+    let err = check_parse_error("void f(bool b) { while b { if b {");
+    assert_eq!(err.start,32);
+    assert_eq!(err.message,"unclosed '{' opened here");
+}
 
-/// A dummy source mapper which does nothing.
-fn source_mapper<'a>(_: usize, _: &'a str) { }
+#[test]
+fn test_method_22() {
+    let err = check_parse_error("void f(bool b) { switch b { case true: { skip; }");
+    assert_eq!(err.start,26);
+    assert_eq!(err.message,"unclosed '{' opened here");
+}
 
-/// A dummy type mapper which does nothing.
-fn type_mapper<'a>(_: usize, _: Type) { }
+#[test]
+fn test_method_23() {
+    let mut ast = AbstractSyntaxTree::new();
+    let d = Parser::new("i32 f(i32 x, i32 y) { return y; }",&mut ast,source_mapper).parse_decl().unwrap();
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    typer.check(d).unwrap();
+    // VarExpr for y resolves to parameter name
+    assert_eq!(typer.definition_of(Expr(6)),Some(4));
+    assert_eq!(typer.definition_of(Expr(7)),None);
+}
 
-#[cfg(test)]
-fn check_parse(input: &str) -> Box<AbstractSyntaxTree> {
+#[test]
+fn test_method_24() {
     let mut ast = AbstractSyntaxTree::new();
-    let mut parser = Parser::new(input,&mut ast, source_mapper);
-    // Parse input
-    let d = parser.parse_decl();
-    println!("PARSED {:?}",d);
-    assert!(!d.is_err());
-    // Type input
+    let ds = Parser::new("const i32 N = 1; i32 f() { return N; }",&mut ast,source_mapper).parse_program().unwrap();
     let mut typer = TypeChecker::new(&mut ast, type_mapper);
-    let r = typer.check(d.unwrap());
-    assert!(!r.is_err());
-    // Done
-    Box::new(ast)
+    typer.check_program(&ds).unwrap();
+    // VarExpr for N resolves to constant name
+    assert_eq!(typer.definition_of(Expr(5)),Some(1));
+
 }
 
-#[cfg(test)]
-fn check_parse_error(input: &str) {
+#[test]
+fn test_method_25() {
+    let err = check_parse_error("void if() {}");
+    assert_eq!(err.start,5);
+    assert_eq!(err.message,"'if' is a reserved keyword and cannot be used as a name");
+}
+
+#[test]
+fn test_method_26() {
+    let err = check_parse_error("void f(i32 return) {}");
+    assert_eq!(err.message,"'return' is a reserved keyword and cannot be used as a name");
+}
+
+#[test]
+fn test_method_27() {
+    assert!(lil::typecheck("bool lt(i32 a, i32 b) { return a < b; }").is_ok());
+}
+
+#[test]
+fn test_method_28() {
+    let r = lil::typecheck("bool lt(i32 a, i32 b) { return a; }");
+    assert!(matches!(r,Err(CompileError::Syntax(SyntaxError{errno:ErrorCode::ExpectedSubtype{..},..}))));
+}
+
+#[test]
+fn test_method_29() {
+    let r = lil::typecheck("i32 lt(i32 a, i32 b) { return a < b; }");
+    assert!(matches!(r,Err(CompileError::Syntax(SyntaxError{errno:ErrorCode::ExpectedSubtype{..},..}))));
+}
+
+#[test]
+fn test_method_30() {
     let mut ast = AbstractSyntaxTree::new();
-    let mut p = Parser::new(input,&mut ast, source_mapper);
-    let d = p.parse_decl();
-    assert!(d.is_err());
+    let e = Parser::new("123",&mut ast,source_mapper).parse_expr().unwrap();
+    let mut typer = TypeChecker::new(&mut ast, type_mapper).with_int_default(true,64);
+    let t = typer.check_expr(&Env::new(),e).unwrap();
+    assert_eq!(ast.get(t.0),&Node::IntType(true,64));
 }
 
-#[cfg(test)]
-fn check_type_error(input: &str) {
+#[test]
+fn test_method_31() {
+    assert_eq!(check_expr_type("123"),Node::IntType(true,32));
+}
+
+#[test]
+fn test_method_32() {
+    let err = check_type_error("void f({i32 f} x, {i32 f, i32 g} y) { x = y; }");
+    assert_eq!(err.errno.to_string(),"expected {i32 f}, found {i32 f, i32 g}");
+}
+
+#[test]
+fn test_method_33() {
+    let err = check_type_error("void f(i32 x) { assert x; }");
+    assert_eq!(err.errno.to_string(),"expected bool, found i32");
+    let err = check_type_error("i32 f() { return; }");
+    assert_eq!(err.errno.to_string(),"expected i32, found void");
+}
+
+#[test]
+fn test_method_34() {
+    // Duplicate parameter reported against second occurrence
+    let input = "void f(i32 x, bool x) {}";
+    let mut map = SourceMap::new(input);
     let mut ast = AbstractSyntaxTree::new();
-    let mut parser = Parser::new(input,&mut ast, source_mapper);
-    // Parse input
-    let d = parser.parse_decl();
-    println!("PARSED {:?}",d);
-    assert!(!d.is_err());
-    // Type input
-    let mut typer = TypeChecker::new(&mut ast, type_mapper);
-    let r = typer.check(d.unwrap());
-    assert!(r.is_err());
+    let d = Parser::new(input,&mut ast,|i,s| map.map(i,s)).parse_decl().unwrap();
+    let err = TypeChecker::new(&mut ast, type_mapper).check(d).err().unwrap();
+    assert!(matches!(err.errno,ErrorCode::DuplicateParameter));
+    assert_eq!(err.node,6);
+    let hl = map.get_highlight(err.node);
+    assert_eq!(hl.start,19);
+    assert_eq!(hl.end,20);
+    assert_eq!(&hl.line[hl.start..hl.end],"x");
+    // Likewise when the name appears earlier in the file
+    let (err,start,text) = check_program_error_highlight("const i32 x = 1; void f(i32 x, bool x) {}");
+    assert!(matches!(err.errno,ErrorCode::DuplicateParameter));
+    assert_eq!((start,text.as_str()),(36,"x"));
 }
 
-/// Check that a given node is an instance of Node::Utf8 and matches
-/// the corresponding string.
+#[test]
+fn test_method_35() {
+    let err = check_type_error("void f(i32 x, bool y, u8 x) {}");
+    assert!(matches!(err.errno,ErrorCode::DuplicateParameter));
+    assert_eq!(err.node,9);
+
+}
+
+#[test]
+fn test_method_36() {
+    let ast = check_parse("var f() { return 1 < 2; }");
+    assert_eq!(ast.get(0),&Node::InferredType);
+    check_name(ast.get(1),"f");
+    assert_eq!(ast.get(5),&Node::ReturnStmt(Some(Expr(4))));
+    assert_eq!(ast.get(7),&Node::MethodDecl(Name(1),Type(0),vec![],Stmt(6)));
+}
+
+#[test]
+fn test_method_37() {
+    check_parse_error("void f() { var x = 1; }");
+    check_parse_error("type t = var;");
+    check_format("var f(i32 x) {\n    return x;\n}\n");
+}
+
+#[test]
+fn test_method_38() {
+    let mut ast = AbstractSyntaxTree::new();
+    let d = Parser::new("var f() { return 1 < 2; }",&mut ast,source_mapper).parse_decl().unwrap();
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    typer.check(d).unwrap();
+    let t = typer.return_type(d).unwrap();
+    assert_eq!(ast.get(t.0),&Node::BoolType);
+}
+
+#[test]
+fn test_method_39() {
+    // Calls use the inferred return type
+    check_program("var f(i32 x) { return x; } i32 g() { return f(1); }");
+    let err = check_program_error("var f(i32 x) { return x; } bool g() { return f(1); }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_method_40() {
+    let err = check_type_error("var f(i32 x) { skip; return x; }");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
+    let err = check_program_error("var f(i32 x) { return f(x); }");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
+    assert_eq!(err.errno.to_string(),"invalid type (cannot infer return type of recursive method)");
+}
+
+#[test]
+fn test_method_41() {
+    // Reference parameters through the whole pipeline
+    check_program("void f(&i32 p) { assert (p == null); }");
+    check_program("void f(&&i32 p, bool b) { { assert b && p != null; } }");
+}
+
+#[test]
+fn test_method_42() {
+    // Null can be given wherever a reference is expected
+    check_program("void f(&i32 p) { &i32 q = null; p = null; assert q == p; }");
+    check_program("&i32 f() { return null; }");
+    check_program("bool f(&i32 p) { return f(null); }");
+}
+
+#[test]
+fn test_method_43() {
+    // But not the other way around
+    let err = check_program_error("void f(i32 x) { x = null; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+    let err = check_program_error("void f(&i32 p) { &bool q = p; }");
+    assert_eq!(err.errno.to_string(),"expected &bool, found &i32");
+}
+
+#[test]
+fn test_method_44() {
+    let (ast,ds) = lil::parse("bool f(&i32 p) { return p == null; }").unwrap();
+    let r = Interpreter::new(&ast).invoke(ds[0],&[Value::Null]);
+    assert_eq!(r,Ok(Some(Value::Bool(true))));
+}
+
+#[test]
+fn test_method_45() {
+    // A void method returns nothing, not even null
+    let err = check_program_error("void f() { return null; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+    assert_eq!(err.errno.to_string(),"expected void, found null");
+}
+
+#[test]
+fn test_method_46() {
+    check_program("&i32 g() { return null; }");
+    check_program("&&u8 g(bool b) { if b { return null; } return null; }");
+}
+
+#[test]
+fn test_method_47() {
+    // Null is not a value of non-reference types
+    let err = check_program_error("i32 g() { return null; }");
+    assert_eq!(err.errno.to_string(),"expected i32, found null");
+    let err = check_program_error("&i32 g() { return; }");
+    assert_eq!(err.errno.to_string(),"expected &i32, found void");
+}
+
+// ======================================================
+// Tests (Constant Declarations)
+// ======================================================
+
+#[test]
+fn test_const_01() {
+    let ast = check_parse("const i32 N = 42;");
+    assert_eq!(ast.get(0),&Node::IntType(true,32));
+    check_name(ast.get(1),"N");
+    assert_eq!(ast.get(2),&Node::IntExpr(false,42));
+    assert_eq!(ast.get(3),&Node::ConstDecl(Type(0),Name(1),Expr(2)));
+}
+
+#[test]
+fn test_const_02() {
+    check_parse_error("const i32 N = 42");
+}
+
+#[test]
+fn test_const_03() {
+    check_parse_error("const N = 42;");
+}
+
+#[test]
+fn test_const_04() {
+    check_program("const i32 N = 42; void f() { assert N < 43; }");
+}
+
+#[test]
+fn test_const_05() {
+    let err = check_type_error("const bool B = 1;");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_const_06() {
+    let err = check_type_error("const i32 N = x;");
+    assert!(matches!(err.errno,ErrorCode::ExpectedConstant));
+}
+
+#[test]
+fn test_const_07() {
+    let err = check_program_error("void f() { assert N < 43; } const i32 N = 42;");
+    assert!(matches!(err.errno,ErrorCode::VariableNotFound));
+}
+
+// ======================================================
+// Tests (Annotations)
+// ======================================================
+
+#[test]
+fn test_annotation_01() {
+    let ast = check_parse("@inline void f() {}");
+    check_name(ast.get(0),"inline");
+    assert_eq!(ast.get(4),&Node::MethodDecl(Name(2),Type(1),vec![],Stmt(3)));
+    let anns = vec![Annotation{name:Name(0),args:vec![]}];
+    assert_eq!(ast.get(5),&Node::AnnotatedDecl(anns,Decl{index:4}));
+}
+
+#[test]
+fn test_annotation_02() {
+    let ast = check_parse("@since(1) @deprecated type t = i32;");
+    check_name(ast.get(0),"since");
+    assert_eq!(ast.get(1),&Node::IntExpr(false,1));
+    check_name(ast.get(2),"deprecated");
+    assert_eq!(ast.get(5),&Node::TypeDecl(Name(3),Type(4)));
+    let anns = vec![Annotation{name:Name(0),args:vec![Expr(1)]},Annotation{name:Name(2),args:vec![]}];
+    assert_eq!(ast.get(6),&Node::AnnotatedDecl(anns,Decl{index:5}));
+}
+
+#[test]
+fn test_annotation_03() {
+    check_parse_error("@ void f() {}");
+}
+
+#[test]
+fn test_annotation_04() {
+    check_parse_error("@inline(1 void f() {}");
+}
+
+// ======================================================
+// Tests (Keywords)
+// ======================================================
+
+#[test]
+fn test_keyword_01() {
+    // The keyword is `do` (not `Do`), hence it is no longer a name
+    check_parse_error("void do() {}");
+    check_parse_error("void f(i32 do) {}");
+    check_parse("void Do() {}");
+}
+
+// ======================================================
+// Tests (Skip)
+// ======================================================
+
+#[test]
+fn test_skip_01() {
+    check_parse_error("void f() { ski }");
+}
+
+#[test]
+fn test_skip_02() {
+    check_parse_error("void f() { skip }");
+}
+
+#[test]
+fn test_skip_03() {
+    let ast = check_parse("void f() { skip; }");
+    assert_eq!(ast.get(2),&Node::SkipStmt);
+}
+
+#[test]
+fn test_skip_04() {
+    // Empty statements are discarded
+    let ast = check_parse("void f() { ; skip; ; }");
+    assert_eq!(ast.get(2),&Node::SkipStmt);
+    assert_eq!(ast.get(3),&Node::BlockStmt(vec![Stmt(2)]));
+}
+
+#[test]
+fn test_skip_05() {
+    let ast = check_parse("void f() { ;; }");
+    assert_eq!(ast.get(2),&Node::BlockStmt(vec![]));
+}
+
+#[test]
+fn test_skip_06() {
+    check_parse("i32 f(bool b) { if b { return 1;; } ; return 0; ; }");
+    check_parse_error("void f() { skip;; ");
+}
+
+// ======================================================
+// Tests (Assert)
+// ======================================================
+
+#[test]
+fn test_assert_04() {
+    check_parse_error("void f() { asse");
+}
+
+#[test]
+fn test_assert_05() {
+    check_parse_error("void f() { assert");
+}
+
+#[test]
+fn test_assert_06() {
+    check_parse_error("void f() { assert true }");
+}
+
+#[test]
+fn test_assert_07() {
+    let ast = check_parse("void f() { assert true; }");
+    assert_eq!(ast.get(2),&Node::BoolExpr(true));
+    assert_eq!(ast.get(3),&Node::AssertStmt(Expr(2)));
+}
+
+#[test]
+fn test_assert_08() {
+    let ast = check_parse("void f() { assert false; }");
+    assert_eq!(ast.get(2),&Node::BoolExpr(false));
+    assert_eq!(ast.get(3),&Node::AssertStmt(Expr(2)));
+}
+
+#[test]
+fn test_assert_09() {
+    let ast = check_parse("void f() { assert (false); }");
+    assert_eq!(ast.get(2),&Node::BoolExpr(false));
+    assert_eq!(ast.get(3),&Node::AssertStmt(Expr(2)));
+}
+
+#[test]
+fn test_assert_10() {
+    check_type_error("void f() { assert b; }");
+}
+
+#[test]
+fn test_assert_11() {
+    let ast = check_parse("void f(bool b) { assert b; }");
+    check_name(ast.get(3),"b");
+    assert_eq!(ast.get(5),&Node::VarExpr(Name(3)));
+    assert_eq!(ast.get(6),&Node::AssertStmt(Expr(5)));
+}
+
+#[test]
+fn test_assert_12() {
+    let ast = check_parse("void f(i32 i) { assert i < 0; }");
+    check_name(ast.get(3),"i");
+    assert_eq!(ast.get(5),&Node::VarExpr(Name(3)));
+    assert_eq!(ast.get(6),&Node::IntExpr(false,0));
+    assert_eq!(ast.get(7),&Node::LessThanExpr(Expr(5),Expr(6)));
+    assert_eq!(ast.get(8),&Node::AssertStmt(Expr(7)));
+}
+
+#[test]
+fn test_assert_13() {
+    let err = check_type_error("void f() { assert true < 1; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_assert_14() {
+    let err = check_type_error("void f() { assert 1 < false; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_assert_15() {
+    let err = check_type_error("void f() { assert 1; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_assert_16() {
+    check_parse("void f(&i32 p) { assert (p == null); }");
+}
+
+#[test]
+fn test_assert_17() {
+    let ast = check_parse("void f(&i32 p) { assert (p != null); }");
+    check_name(ast.get(4),"p");
+    assert_eq!(ast.get(6),&Node::VarExpr(Name(4)));
+    assert_eq!(ast.get(7),&Node::NullExpr);
+    assert_eq!(ast.get(8),&Node::NotEqualsExpr(Expr(6),Expr(7)));
+    assert_eq!(ast.get(9),&Node::AssertStmt(Expr(8)));
+}
+
+#[test]
+fn test_assert_18() {
+    check_parse("void f(&i32 p, &i32 q) { assert null == p; assert p != q; }");
+}
+
+#[test]
+fn test_assert_19() {
+    check_parse("void f(i32 x, bool b) { assert (x == 1) == b; assert x != 0; }");
+}
+
+#[test]
+fn test_assert_20() {
+    let err = check_type_error("void f(i32 x) { assert x == null; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_assert_21() {
+    let err = check_type_error("void f() { assert 1 == true; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_assert_22() {
+    check_parse_error("void f(i32 x) { assert x == 1 == 2; }");
+}
+
+#[test]
+fn test_assert_23() {
+    assert_eq!(check_evaluate("1 == 1"),Value::Bool(true));
+    assert_eq!(check_evaluate("1 != 1"),Value::Bool(false));
+    assert_eq!(check_evaluate("null == null"),Value::Bool(true));
+}
+
+#[test]
+fn test_assert_24() {
+    // Unary plus is folded away
+    let ast = check_parse("void f() { assert 0 < +1; }");
+    assert_eq!(ast.get(2),&Node::IntExpr(false,0));
+    assert_eq!(ast.get(3),&Node::IntExpr(false,1));
+    assert_eq!(ast.get(4),&Node::LessThanExpr(Expr(2),Expr(3)));
+    assert_eq!(ast.get(5),&Node::AssertStmt(Expr(4)));
+}
+
+#[test]
+fn test_assert_25() {
+    check_parse_error("void f() { assert +false; }");
+    check_parse_error("void f() { assert 1 < +true; }");
+}
+
+#[test]
+fn test_assert_26() {
+    // Inner blocks see parameters of the enclosing method
+    check_program("void f(bool b) { { assert b; } }");
+}
+
+#[test]
+fn test_assert_27() {
+    // Inner blocks see locals of enclosing blocks
+    check_program("void f() { bool c = true; { { assert c; } } }");
+    check_execute("void f(i32 x) { bool c = x < 2; { assert c; } }");
+}
+
+#[test]
+fn test_assert_28() {
+    // But not the other way around
+    let err = check_program_error("void f() { { bool c = true; } assert c; }");
+    assert!(matches!(err.errno,ErrorCode::VariableNotFound));
+}
+
+#[test]
+fn test_assert_29() {
+    // Missing variable reported against its use, rather than where
+    // its name first appears
+    let (err,start,text) = check_program_error_highlight("void g(i32 x) { skip; } i32 f() { return x; }");
+    assert!(matches!(err.errno,ErrorCode::VariableNotFound));
+    assert_eq!((start,text.as_str()),(41,"x"));
+}
+
+// ======================================================
+// Tests (Control Flow)
+// ======================================================
+
+#[test]
+fn test_control_01() {
+    let ast = check_parse("i32 f(bool b) { if (b) { return 1; } return 2; }");
+    assert_eq!(ast.get(5),&Node::VarExpr(Name(3)));
+    assert_eq!(ast.get(6),&Node::IntExpr(false,1));
+    assert_eq!(ast.get(7),&Node::ReturnStmt(Some(Expr(6))));
+    assert_eq!(ast.get(8),&Node::BlockStmt(vec![Stmt(7)]));
+    assert_eq!(ast.get(9),&Node::IfStmt(Expr(5),Stmt(8),None));
+}
+
+#[test]
+fn test_control_02() {
+    let ast = check_parse("void f(bool b) { while b { skip; } return; }");
+    assert_eq!(ast.get(8),&Node::WhileStmt(Expr(5),Stmt(7)));
+    assert_eq!(ast.get(9),&Node::ReturnStmt(None));
+}
+
+#[test]
+fn test_control_03() {
+    check_parse("i32 f(bool b) { if b { return 1; } else { { return 2; } } }");
+}
+
+#[test]
+fn test_control_04() {
+    check_parse_error("void f(bool b) { if b skip; }");
+}
+
+#[test]
+fn test_control_05() {
+    check_parse_error("void f(bool b) { while (b) { skip; }");
+}
+
+#[test]
+fn test_control_06() {
+    let err = check_type_error("i32 f(bool b) { if (b) { return true; } return 2; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_control_07() {
+    let err = check_type_error("bool f(bool b) { if b { return true; } else { return 1; } }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_control_08() {
+    let err = check_type_error("void f(bool b) { while b { if b { return 1; } } }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_control_09() {
+    let err = check_type_error("i32 f() { return; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_control_10() {
+    let err = check_type_error("void f(i32 x) { if x { skip; } }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_control_11() {
+    check_parse("i32 f(i32 x) { if x < 0 { return 0; } else if x < 10 { return 1; } else { return 2; } }");
+}
+
+#[test]
+fn test_control_12() {
+    let err = check_type_error("i32 f(i32 x) { if x < 0 { return 0; } else if x { return 1; } }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_control_13() {
+    check_parse_error("void f(bool b) { if b { skip; } else skip; }");
+}
+
+#[test]
+fn test_control_14() {
+    let err = check_parse_error("bool f(i32 a, i32 b, i32 c) { return a < b < c; }");
+    assert_eq!(err.message,"comparison operators cannot be chained; use parentheses");
+    // Points at the second operator
+    assert_eq!(err.start,43);
+}
+
+#[test]
+fn test_control_15() {
+    // Parenthesised comparisons are not chains
+    let mut ast = AbstractSyntaxTree::new();
+    let e = Parser::new("(a < b) < c",&mut ast,source_mapper).parse_expr();
+    assert!(e.is_ok());
+}
+
+#[test]
+fn test_control_16() {
+    // Relational operators bind more tightly than logical ones
+    let ast = check_parse("void f(i32 a, i32 b, bool c) { if (a < b && c) { skip; } }");
+    assert_eq!(ast.get(12),&Node::LessThanExpr(Expr(10),Expr(11)));
+    assert_eq!(ast.get(14),&Node::AndExpr(Expr(12),Expr(13)));
+    assert!(matches!(ast.get(17),Node::IfStmt(Expr(14),_,None)));
+
+}
+
+#[test]
+fn test_control_17() {
+    check_program("void f(i32 a, i32 b, bool c) { if (a < b && c) { skip; } }");
+    check_program("void f(i32 a, i32 b, bool c) { while a < b && !c || a == b { a++; } }");
+    check_execute("void f(i32 x) { i32 y = 0; while y < 3 && x < 2 { y++; } assert y == 3; }");
+}
+
+#[test]
+fn test_control_18() {
+    let err = check_program_error("void f(i32 a, i32 b, i32 c) { if (a < b && c) { skip; } }");
+    assert_eq!(err.errno.to_string(),"expected bool, found i32");
+    let err = check_program_error("void f(i32 a, bool c) { while c || a { skip; } }");
+    assert_eq!(err.errno.to_string(),"expected bool, found i32");
+}
+
+#[test]
+fn test_control_19() {
+    check_program("void f(bool b) { while b { break; } }");
+    check_program("void f(bool b) { while b { if b { continue; } b = false; } }");
+    check_program("void f(i32 n) { for (i32 i = 0; i < n; i++) { if i == 2 { break; } } }");
+    check_program("void f(bool b) { while b { while b { break; } continue; } }");
+}
+
+#[test]
+fn test_control_20() {
+    let err = check_type_error("void f() { break; }");
+    assert!(matches!(err.errno,ErrorCode::BreakOutsideLoop));
+    assert_eq!(err.node,2);
+    let err = check_type_error("void f(bool b) { if b { break; } }");
+    assert!(matches!(err.errno,ErrorCode::BreakOutsideLoop));
+    let err = check_type_error("void f(bool b) { while b { skip; } continue; }");
+    assert!(matches!(err.errno,ErrorCode::ContinueOutsideLoop));
+    assert_eq!(err.errno.to_string(),"continue outside of loop");
+}
+
+#[test]
+fn test_control_21() {
+    check_execute("void f(i32 x) { i32 i = 0; while i < 10 { if i == x { break; } i++; } assert i == 1; }");
+    check_execute("void f(i32 x) { i32 y = 0; for (i32 i = 0; i < 4; i++) { if i == x { continue; } y++; } assert y == 3; }");
+}
+
+#[test]
+fn test_control_22() {
+    check_format("void f(bool b) {\n    while b {\n        if b {\n            continue;\n        }\n        break;\n    }\n}\n");
+}
+
+// ======================================================
+// Tests (Switch)
+// ======================================================
+
+#[test]
+fn test_switch_01() {
+    check_parse("i32 f(bool b) { switch b { case true: { return 1; } case false: { return 0; } } return 2; }");
+}
+
+#[test]
+fn test_switch_02() {
+    check_parse("i32 f(bool b) { switch b { case true: { return 1; } default: { return 0; } } }");
+}
+
+#[test]
+fn test_switch_03() {
+    check_parse("void f(i32 x) { switch x { case 0: { skip; } case -1: { skip; } } }");
+}
+
+#[test]
+fn test_switch_04() {
+    let err = check_type_error("void f(bool b) { switch b { case true: { skip; } case true: { skip; } default: { skip; } } }");
+    assert!(matches!(err.errno,ErrorCode::DuplicateCase));
+}
+
+#[test]
+fn test_switch_05() {
+    let err = check_type_error("void f(bool b) { switch b { case true: { skip; } } }");
+    assert!(matches!(err.errno,ErrorCode::NonExhaustiveSwitch));
+}
+
+#[test]
+fn test_switch_06() {
+    let err = check_type_error("void f(bool b) { switch b { case 1: { skip; } default: { skip; } } }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_switch_07() {
+    let err = check_type_error("void f(i32 x, i32 y) { switch x { case y: { skip; } } }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedConstant));
+}
+
+#[test]
+fn test_switch_08() {
+    check_parse_error("void f(bool b) { switch b { case true: skip; } }");
+}
+
+#[test]
+fn test_switch_09() {
+    let ast = check_parse("i32 f(bool b) { switch b { case true: { return 1; } default: { return 0; } } }");
+    let r = Interpreter::new(&ast).invoke(Decl{index:15},&[Value::Bool(true)]);
+    assert!(matches!(r,Ok(Some(Value::Int(1)))));
+    let r = Interpreter::new(&ast).invoke(Decl{index:15},&[Value::Bool(false)]);
+    assert!(matches!(r,Ok(Some(Value::Int(0)))));
+}
+
+// ======================================================
+// Tests (Reachability)
+// ======================================================
+
+#[test]
+fn test_reach_01() {
+    check_reachable("i32 f(bool b) { if b { return 1; } else { return 2; } }");
+}
+
+#[test]
+fn test_reach_02() {
+    let err = check_unreachable("i32 f() { return 1; return 2; }");
+    assert!(matches!(err.errno,ErrorCode::UnreachableCode));
+    assert_eq!(err.node,5);
+}
+
+#[test]
+fn test_reach_03() {
+    let err = check_unreachable("i32 f(bool b) { if b { return 1; } }");
+    assert!(matches!(err.errno,ErrorCode::MissingReturn));
+}
+
+#[test]
+fn test_reach_04() {
+    check_reachable("void f(bool b) { if b { return; } }");
+}
+
+#[test]
+fn test_reach_05() {
+    let err = check_unreachable("i32 f(bool b) { while b { return 1; skip; } return 0; }");
+    assert!(matches!(err.errno,ErrorCode::UnreachableCode));
+}
+
+#[test]
+fn test_reach_06() {
+    check_reachable("i32 f(bool b) { switch b { case true: { return 1; } case false: { return 0; } } }");
+}
+
+#[test]
+fn test_reach_07() {
+    let r = lil::typecheck("i32 f() { return 1; skip; }");
+    assert!(matches!(r,Err(CompileError::Syntax(SyntaxError{errno:ErrorCode::UnreachableCode,..}))));
+}
+
+#[test]
+fn test_reach_08() {
+    // Nothing following a break or continue is reachable
+    let err = check_unreachable("void f(bool b) { while b { break; skip; } }");
+    assert!(matches!(err.errno,ErrorCode::UnreachableCode));
+    let err = check_unreachable("void f(bool b) { while b { continue; b = false; } }");
+    assert!(matches!(err.errno,ErrorCode::UnreachableCode));
+    check_reachable("void f(bool b) { while b { if b { break; } skip; } skip; }");
+}
+
+#[test]
+fn test_reach_09() {
+    // Loops which never exit need no return after them
+    check_reachable("i32 f() { while (true) { skip; } }");
+    check_reachable("i32 f(i32 x) { while true { if x < 0 { return x; } x++; } }");
+    check_reachable("i32 f(bool b) { while true { while b { break; } } }");
+    check_reachable("i32 f() { for (i32 i = 0; true; i++) { skip; } }");
+}
+
+#[test]
+fn test_reach_10() {
+    let err = check_unreachable("i32 f(bool cond) { while (cond) { skip; } }");
+    assert!(matches!(err.errno,ErrorCode::MissingReturn));
+    let err = check_unreachable("i32 f(bool b) { while true { if b { break; } } }");
+    assert!(matches!(err.errno,ErrorCode::MissingReturn));
+    let err = check_unreachable("i32 f() { while (false) { skip; } }");
+    assert!(matches!(err.errno,ErrorCode::MissingReturn));
+}
+
+#[test]
+fn test_reach_11() {
+    // Nothing following a loop which never exits is reachable
+    let err = check_unreachable("void f() { while true { skip; } skip; }");
+    assert!(matches!(err.errno,ErrorCode::UnreachableCode));
+    assert!(lil::typecheck("i32 f() { while (true) { skip; } }").is_ok());
+}
+
+// ======================================================
+// Tests (Increment / Decrement)
+// ======================================================
+
+#[test]
+fn test_incdec_01() {
+    let ast = check_parse("void f(i32 x) { x++; }");
+    check_name(ast.get(3),"x");
+    assert_eq!(ast.get(5),&Node::VarExpr(Name(3)));
+    assert_eq!(ast.get(6),&Node::VarExpr(Name(3)));
+    assert_eq!(ast.get(7),&Node::IntExpr(false,1));
+    assert_eq!(ast.get(8),&Node::AddExpr(Expr(6),Expr(7)));
+    assert_eq!(ast.get(9),&Node::AssignStmt(Expr(5),Expr(8)));
+}
+
+#[test]
+fn test_incdec_02() {
+    let ast = check_parse("void f(i32 x) { ++x; }");
+    check_name(ast.get(3),"x");
+    assert_eq!(ast.get(5),&Node::VarExpr(Name(3)));
+    assert_eq!(ast.get(6),&Node::VarExpr(Name(3)));
+    assert_eq!(ast.get(7),&Node::IntExpr(false,1));
+    assert_eq!(ast.get(8),&Node::AddExpr(Expr(6),Expr(7)));
+    assert_eq!(ast.get(9),&Node::AssignStmt(Expr(5),Expr(8)));
+}
+
+#[test]
+fn test_incdec_03() {
+    let ast = check_parse("void f(i32 x) { x--; }");
+    assert_eq!(ast.get(8),&Node::SubtractExpr(Expr(6),Expr(7)));
+    assert_eq!(ast.get(9),&Node::AssignStmt(Expr(5),Expr(8)));
+}
+
+#[test]
+fn test_incdec_04() {
+    let ast = check_parse("void f(i32 x) { --x; }");
+    assert_eq!(ast.get(8),&Node::SubtractExpr(Expr(6),Expr(7)));
+    assert_eq!(ast.get(9),&Node::AssignStmt(Expr(5),Expr(8)));
+}
+
+#[test]
+fn test_incdec_05() {
+    check_parse_error("void f(i32 x) { x; }");
+}
+
+#[test]
+fn test_incdec_06() {
+    check_parse_error("void f(i32 x) { ++1; }");
+}
+
+#[test]
+fn test_incdec_07() {
+    let err = check_type_error("void f(bool b) { b++; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_incdec_08() {
+    let err = check_type_error("void f(i32 x) { y--; }");
+    assert!(matches!(err.errno,ErrorCode::VariableNotFound));
+}
+
+// ======================================================
+// Tests (Sizeof)
+// ======================================================
+
+#[test]
+fn test_sizeof_01() {
+    check_parse_error("void f() { assert sizeof(i32; }");
+}
+
+#[test]
+fn test_sizeof_02() {
+    let ast = check_parse("void f() { assert sizeof(i32) < sizeof(i64); }");
+    assert_eq!(ast.get(2),&Node::IntType(true,32));
+    assert_eq!(ast.get(3),&Node::SizeofExpr(Type(2)));
+    assert_eq!(ast.get(4),&Node::IntType(true,64));
+    assert_eq!(ast.get(5),&Node::SizeofExpr(Type(4)));
+    assert_eq!(ast.get(6),&Node::LessThanExpr(Expr(3),Expr(5)));
+}
+
+#[test]
+fn test_sizeof_03() {
+    assert_eq!(check_expr_type("sizeof(i32)"),Node::IntType(false,64));
+}
+
+#[test]
+fn test_sizeof_04() {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("{i32 f, bool[] g, &u16 h}",&mut ast, source_mapper);
+    let t = parser.parse_type().unwrap();
+    let typer = TypeChecker::new(&mut ast, type_mapper);
+    assert_eq!(typer.size_of(&t),Some(4 + 8 + 8));
+}
+
+// ======================================================
+// Tests (Len)
+// ======================================================
+
+#[test]
+fn test_len_01() {
+    let ast = check_parse("void f(i32[] a, bool[4] b) { assert len(a) < len(b); }");
+    check_name(ast.get(4),"a");
+    assert_eq!(ast.get(11),&Node::VarExpr(Name(4)));
+    assert_eq!(ast.get(12),&Node::LenExpr(Expr(11)));
+    assert_eq!(ast.get(14),&Node::LenExpr(Expr(13)));
+    assert_eq!(ast.get(15),&Node::LessThanExpr(Expr(12),Expr(14)));
+
+}
+
+#[test]
+fn test_len_02() {
+    let err = check_type_error("void f() { assert len(1) < len(1); }");
+    assert_eq!(err.errno.to_string(),"expected array, found i32");
+}
+
+#[test]
+fn test_len_03() {
+    check_parse_error("void f(i32[] a) { assert len a; }");
+    check_parse_error("void f(i32[] a) { assert len(a; }");
+}
+
+#[test]
+fn test_len_04() {
+    check_parse("u64 f({i32[] xs} r) { return len(r.xs); }");
+}
+
+#[test]
+fn test_len_05() {
+    check_format("u64 f() {\n    return len(xs);\n}\n");
+}
+
+// ======================================================
+// Tests (Verifier)
+// ======================================================
+
+#[test]
+fn test_verify_01() {
+    let ast = check_parse("void f(i32 x, {bool b} r) { x++; assert sizeof(i32) < sizeof(bool); }");
+    assert!(verify(&ast,&[Decl{index:20}]).is_ok());
+
+}
+
+#[test]
+fn test_verify_02() {
+    let mut ast = AbstractSyntaxTree::new();
+    ast.push(Node::Utf8("t".to_string()));
+    ast.push(Node::BoolExpr(true));
+    ast.push(Node::ArrayType(Type(1)));
+    ast.push(Node::TypeDecl(Name(0),Type(2)));
+    let err = verify(&ast,&[Decl{index:3}]).err().unwrap();
+    assert_eq!(err.node,2);
+}
+
+#[test]
+fn test_verify_03() {
+    let mut ast = AbstractSyntaxTree::new();
+    ast.push(Node::Utf8("t".to_string()));
+    ast.push(Node::RecordType(vec![(Type(9),Name(0))]));
+    ast.push(Node::TypeDecl(Name(0),Type(1)));
+    let err = verify(&ast,&[Decl{index:2}]).err().unwrap();
+    assert_eq!(err.node,1);
+}
+
+#[test]
+fn test_verify_04() {
+    let mut ast = AbstractSyntaxTree::new();
+    ast.push(Node::VoidType);
+    ast.push(Node::Utf8("f".to_string()));
+    ast.push(Node::AssertStmt(Expr(7)));
+    ast.push(Node::BlockStmt(vec![Stmt(2)]));
+    ast.push(Node::MethodDecl(Name(1),Type(0),vec![],Stmt(3)));
+    let err = verify(&ast,&[Decl{index:4}]).err().unwrap();
+    assert_eq!(err.node,2);
+}
+
+#[test]
+fn test_verify_05() {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("void f(bool[4] xs, i32[2][3] ys) { assert sizeof(u8[8]) == 8; }",&mut ast, source_mapper);
+    let d = parser.parse_decl().unwrap();
+    assert!(verify(&ast,&[d]).is_ok());
+}
+
+// ======================================================
+// Tests (Interpreter)
+// ======================================================
+
+#[test]
+fn test_interpreter_01() {
+    check_execute("void f() { assert 1 < 2; }");
+}
+
+#[test]
+fn test_interpreter_02() {
+    check_execute("void f(i32 x) { x++; x--; assert sizeof(i32) < sizeof(i64); }");
+}
+
+#[test]
+fn test_interpreter_03() {
+    let input = "void f() { assert 2 < 1; }";
+    let mut map = SourceMap::new(input);
+    let mut ast = AbstractSyntaxTree::new();
+    let d = Parser::new(input,&mut ast,|i,s| map.map(i,s)).parse_decl().unwrap();
+    let err = Interpreter::new(&ast).invoke(d,&[]).err().unwrap();
+    assert_eq!(err.node,4);
+    let hl = map.get_highlight(err.node);
+    assert_eq!(hl.start,18);
+    assert_eq!(hl.end,23);
+    assert_eq!(err.report(&map),"error: assertion failed\nvoid f() { assert 2 < 1; }\n                  ^^^^^\n");
+}
+
+#[test]
+fn test_interpreter_04() {
+    let ast = check_parse("i32 f(bool b) { if (b) { return 1; } return 2; }");
+    let r = Interpreter::new(&ast).invoke(Decl{index:13},&[Value::Bool(true)]);
+    assert!(matches!(r,Ok(Some(Value::Int(1)))));
+    let r = Interpreter::new(&ast).invoke(Decl{index:13},&[Value::Bool(false)]);
+    assert!(matches!(r,Ok(Some(Value::Int(2)))));
+}
+
+#[test]
+fn test_interpreter_05() {
+    let ast = check_parse("i32 f(i32 x) { while 0 < x { x--; } return x; }");
+    let r = Interpreter::new(&ast).invoke(Decl{index:17},&[Value::Int(5)]);
+    assert!(matches!(r,Ok(Some(Value::Int(0)))));
+}
+
+#[test]
+fn test_interpreter_06() {
+    let input = "void f() {\n  // check\n  /* block */ assert 2 < 1;\n}";
+    let mut map = SourceMap::new(input);
+    let mut ast = AbstractSyntaxTree::new();
+    let d = Parser::new(input,&mut ast,|i,s| map.map(i,s)).parse_decl().unwrap();
+    assert!(matches!(ast.get(5),Node::AssertStmt(_)));
+    let hl = map.get_highlight(5);
+    assert_eq!(hl.line,"  /* block */ assert 2 < 1;");
+    assert_eq!(hl.start,14);
+    assert_eq!(hl.end,26);
+    let err = Interpreter::new(&ast).invoke(d,&[]).err().unwrap();
+    assert_eq!(err.report(&map),"error: assertion failed\n  /* block */ assert 2 < 1;\n                     ^^^^^\n");
+}
+
+#[test]
+fn test_interpreter_07() {
+    assert_eq!(check_evaluate("1 + 2").to_string(),"3");
+    assert_eq!(check_evaluate("1 - 2").to_string(),"-1");
+    assert_eq!(check_evaluate("1 < 2").to_string(),"true");
+    assert_eq!(check_evaluate("2 < 1").to_string(),"false");
+}
+
+#[test]
+fn test_interpreter_08() {
+    assert_eq!(check_evaluate("1 + 2"),Value::Int(3));
+    assert_eq!(check_evaluate("1 < 2"),Value::Bool(true));
+    assert!(check_evaluate("1 < 2") != Value::Int(1));
+}
+
+#[test]
+fn test_interpreter_09() {
+    // Literals beyond the range of i64 are not truncated
+    assert_eq!(check_evaluate("18446744073709551615"),Value::Int(18446744073709551615));
+    assert_eq!(check_evaluate("9223372036854775807 + 1").to_string(),"9223372036854775808");
+    assert_eq!(check_evaluate("-9223372036854775808 - 1").to_string(),"-9223372036854775809");
+}
+
+// ======================================================
+// Tests (Field Access)
+// ======================================================
+
+#[test]
+fn test_field_01() {
+    check_parse("i32 f({i32 x, bool y} r) { return r.x; }");
+}
+
+#[test]
+fn test_field_02() {
+    check_parse("bool f({i32 x, bool y} r) { return r.y; }");
+}
+
+#[test]
+fn test_field_03() {
+    let ast = check_parse("bool f({i32 x, {bool z} y} r) { return r.y.z; }");
+    check_name(ast.get(6),"y");
+    check_name(ast.get(4),"z");
+    assert_eq!(ast.get(11),&Node::FieldAccessExpr(Expr(10),Name(6)));
+    assert_eq!(ast.get(12),&Node::FieldAccessExpr(Expr(11),Name(4)));
+}
+
+#[test]
+fn test_field_04() {
+    let err = check_type_error("i32 f({i32 x, bool y} r) { return r.z; }");
+    assert!(matches!(err.errno,ErrorCode::FieldNotFound));
+}
+
+#[test]
+fn test_field_05() {
+    let err = check_type_error("i32 f(i32 r) { return r.x; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_field_06() {
+    let err = check_type_error("i32 f({i32 x, bool y} r) { return r.y; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_field_07() {
+    check_parse("void f({i32 x, bool y} r) { r.x = 1; r.y = true; }");
+}
+
+#[test]
+fn test_field_08() {
+    let err = check_type_error("void f({i32 x} r) { r.x = true; }");
+    assert_eq!(err.errno.to_string(),"expected i32, found bool");
+}
+
+#[test]
+fn test_field_09() {
+    let err = check_type_error("void f({i32 x} r) { r.z = 1; }");
+    assert!(matches!(err.errno,ErrorCode::FieldNotFound));
+}
+
+#[test]
+fn test_field_10() {
+    check_parse("void f({{i32 z} y} r) { r.y.z = 1; r.y.z++; --r.y.z; }");
+}
+
+#[test]
+fn test_field_11() {
+    // Array of records
+    let ast = check_parse("type t = {i32 f}[];");
+    check_name(ast.get(0),"t");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    check_name(ast.get(2),"f");
+    assert_eq!(ast.get(3),&Node::RecordType(vec![(Type(1),Name(2))]));
+    assert_eq!(ast.get(4),&Node::ArrayType(Type(3)));
+    assert_eq!(ast.get(5),&Node::TypeDecl(Name(0),Type(4)));
+}
+
+#[test]
+fn test_field_12() {
+    // Record with array field
+    let ast = check_parse("type t = {i32[] f};");
+    check_name(ast.get(0),"t");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(2),&Node::ArrayType(Type(1)));
+    check_name(ast.get(3),"f");
+    assert_eq!(ast.get(4),&Node::RecordType(vec![(Type(2),Name(3))]));
+    assert_eq!(ast.get(5),&Node::TypeDecl(Name(0),Type(4)));
+}
+
+#[test]
+fn test_field_13() {
+    // Indexing an array of records yields a record
+    check_program("{i32 f} g({i32 f}[] xs) { return xs[0]; }");
+    check_program("i32 g({i32 f}[2][] xs, i32 i) { return xs[i][1].f; }");
+    let err = check_program_error("bool g({i32 f}[] xs) { return xs[0]; }");
+    assert_eq!(err.errno.to_string(),"expected bool, found {i32 f}");
+}
+
+#[test]
+fn test_field_14() {
+    // Indexing an array field yields its element
+    check_program("i32 g({i32[] f} r) { return r.f[1]; }");
+    check_program("void g({bool[] f}[] rs) { rs[0].f[1] = true; }");
+}
+
+#[test]
+fn test_field_15() {
+    let err = check_program_error("i32 g({i32 f} r) { return r[0]; }");
+    assert_eq!(err.errno.to_string(),"expected array, found {i32 f}");
+    let err = check_program_error("i32 g(i32[] xs) { return xs[true]; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_field_16() {
+    check_format("i32 g({i32 f}[] xs, &{bool b} r) {\n    return xs[len(xs) - 1].f;\n}\n");
+}
+
+#[test]
+fn test_field_17() {
+    // Nested field accesses chain left-to-right
+    let ast = check_parse("i32 f({{i32 b} a} r) { return r.a.b; }");
+    assert_eq!(ast.get(3),&Node::RecordType(vec![(Type(0),Name(2))]));
+    assert_eq!(ast.get(5),&Node::RecordType(vec![(Type(3),Name(4))]));
+    assert_eq!(ast.get(8),&Node::VarExpr(Name(6)));
+    check_name(ast.get(4),"a");
+    assert_eq!(ast.get(9),&Node::FieldAccessExpr(Expr(8),Name(4)));
+    check_name(ast.get(2),"b");
+    assert_eq!(ast.get(10),&Node::FieldAccessExpr(Expr(9),Name(2)));
+    let params = vec![Parameter{declared:Type(5),name:Name(6),site:Name(7)}];
+    assert_eq!(ast.get(13),&Node::MethodDecl(Name(1),Type(0),params,Stmt(12)));
+
+}
+
+#[test]
+fn test_field_18() {
+    // Inner access yields the nested record, outer access its field
+    check_program("{i32 b} f({{i32 b} a} r) { return r.a; }");
+    check_program("i32 f({{i32 b} a} r) { return r.a.b; }");
+    let err = check_program_error("bool f({{i32 b} a} r) { return r.a.b; }");
+    assert_eq!(err.errno.to_string(),"expected bool, found i32");
+}
+
+#[test]
+fn test_field_19() {
+    let err = check_type_error("i32 f({{i32 b} a} r) { return r.a.c; }");
+    assert!(matches!(err.errno,ErrorCode::FieldNotFound));
+    let err = check_type_error("i32 f({{i32 b} a} r) { return r.b.a; }");
+    assert!(matches!(err.errno,ErrorCode::FieldNotFound));
+}
+
+#[test]
+fn test_field_20() {
+    // Records of identical shape can be compared
+    check_program("bool f({i32 f} r1, {i32 f} r2) { return r1 == r2; }");
+    check_program("bool f({i32 f, bool g} r1, {i32 f, bool g} r2) { return r1 != r2; }");
+    check_program("type t = {i32 f}; bool f(t r1, {i32 f} r2) { return r1 == r2; }");
+    check_program("bool f({{i32 b} a} r1, {i32 b} r2) { return r1.a == r2; }");
+}
+
+#[test]
+fn test_field_21() {
+    let err = check_program_error("bool f({i32 f} r1, {bool f} r2) { return r1 == r2; }");
+    assert_eq!(err.errno.to_string(),"expected {i32 f}, found {bool f}");
+    let err = check_program_error("bool f({i32 f} r1, {i32 g} r2) { return r1 != r2; }");
+    assert_eq!(err.errno.to_string(),"expected {i32 f}, found {i32 g}");
+}
+
+#[test]
+fn test_field_22() {
+    let err = check_program_error("bool f({i32 f} r1, {i32 f, i32 g} r2) { return r1 == r2; }");
+    assert_eq!(err.errno.to_string(),"expected {i32 f}, found {i32 f, i32 g}");
+    let err = check_program_error("bool f({i32 f} r, i32 x) { return r == x; }");
+    assert_eq!(err.errno.to_string(),"expected {i32 f}, found i32");
+}
+
+#[test]
+fn test_field_23() {
+    // Missing field reported against the access, even when the field
+    // name appears earlier in the file
+    let (err,start,text) = check_program_error_highlight("type t = {i32 f}; i32 g({i32 h} r) { return r.f; }");
+    assert!(matches!(err.errno,ErrorCode::FieldNotFound));
+    assert_eq!((start,text.as_str()),(44,"r.f"));
+}
+
+// ======================================================
+// Tests (Printer)
+// ======================================================
+
+#[test]
+fn test_printer_01() {
+    check_format("void f() {\n    skip;\n}\n");
+}
+
+#[test]
+fn test_printer_02() {
+    check_format("type t = {(&i32)[] f, &(bool[]) g};\n");
+}
+
+#[test]
+fn test_printer_03() {
+    check_format("i32 f(i32 x) {\n    if x < 0 {\n        return 0;\n    } else if x < 10 {\n        return 1;\n    } else {\n        return 2;\n    }\n}\n");
+}
+
+#[test]
+fn test_printer_04() {
+    check_format("bool f({i32 x, {bool z} y} r) {\n    return r.y.z;\n}\n");
+}
+
+#[test]
+fn test_printer_05() {
+    check_format("type t = (&i32)[4][];\n");
+}
+
+#[test]
+fn test_printer_06() {
+    let s = "i32 f(i32 x) { if (x < 0) { return 0; } else if (x < 10) { return 1; } else { return 2; } }";
+    let mut ast = AbstractSyntaxTree::new();
+    let d = Parser::new(s,&mut ast,source_mapper).parse_decl().unwrap();
+    let f = format(&ast,d);
+    assert_eq!(f,"i32 f(i32 x) {\n    if x < 0 {\n        return 0;\n    } else if x < 10 {\n        return 1;\n    } else {\n        return 2;\n    }\n}\n");
+}
+
+#[test]
+fn test_printer_07() {
+    assert_eq!(check_display_type("i32[]"),"i32[]");
+    assert_eq!(check_display_type("(&u8)[][]"),"(&u8)[][]");
+}
+
+#[test]
+fn test_printer_08() {
+    assert_eq!(check_display_type("&(i32[])"),"&(i32[])");
+    assert_eq!(check_display_type("&&bool"),"&&bool");
+}
+
+#[test]
+fn test_printer_09() {
+    assert_eq!(check_display_type("{i32 f, bool g}"),"{i32 f, bool g}");
+    assert_eq!(check_display_type("{ u16[2] f , {null n} g, }"),"{u16[2] f, {null n} g}");
+}
+
+// ======================================================
+// Tests (For)
+// ======================================================
+
+#[test]
+fn test_for_01() {
+    check_parse("void f() { for (i32 i=0; i<10; i=i+1) { skip; } }");
+}
+
+#[test]
+fn test_for_02() {
+    check_parse("void f(i32 i) { for (i=0; i<10; i++) { skip; } }");
+}
+
+#[test]
+fn test_for_03() {
+    check_parse("i32 f() { i32 x = 1; x = x - 1; return x; }");
+}
+
+#[test]
+fn test_for_04() {
+    let err = check_type_error("void f() { i32 x = true; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_for_05() {
+    // Loop variable is not in scope after the loop
+    let err = check_type_error("void f() { for (i32 i=0; i<1; i++) { skip; } assert i < 1; }");
+    assert!(matches!(err.errno,ErrorCode::VariableNotFound));
+}
+
+#[test]
+fn test_for_06() {
+    check_parse_error("void f() { for (i32 i=0; i<10; i++;) { skip; } }");
+    check_parse_error("void f() { for (assert true; true; i++) { skip; } }");
+}
+
+#[test]
+fn test_for_07() {
+    check_execute("i32 f() { i32 s = 0; for (i32 i=0; i<4; i=i+1) { s = s + i; } assert 5 < s; assert s < 7; return s; }");
+}
+
+#[test]
+fn test_for_08() {
+    check_format("void f() {\n    for (i32 i = 0; i < 10; i = i + 1) {\n        skip;\n    }\n}\n");
+}
+
+#[test]
+fn test_for_09() {
+    let out = lil::format_desugared("void f() { for (i32 i=0; i<10; i++) { skip; } }").unwrap();
+    assert_eq!(out,"void f() {\n    {\n        i32 i = 0;\n        while i < 10 {\n            skip;\n            i = i + 1;\n        }\n    }\n}\n");
+}
+
+#[test]
+fn test_for_10() {
+    // Nested loops are lowered, whilst other statements are unchanged
+    let out = lil::format_desugared("i32 f(i32 n) { i32 x = 0; while x < n { for (i32 i = 0; i < n; i++) { x++; } } return x; }");
+    assert_eq!(out.unwrap(),"i32 f(i32 n) {\n    i32 x = 0;\n    while x < n {\n        {\n            i32 i = 0;\n            while i < n {\n                x = x + 1;\n                i = i + 1;\n            }\n        }\n    }\n    return x;\n}\n");
+}
+
+#[test]
+fn test_for_11() {
+    // Loops which continue are not lowered, since this would skip the update
+    let out = lil::format_desugared("void f() { for (i32 i=0; i<10; i++) { continue; } }").unwrap();
+    assert_eq!(out,"void f() {\n    for (i32 i = 0; i < 10; i = i + 1) {\n        continue;\n    }\n}\n");
+}
+
+// ======================================================
+// Tests (Lint)
+// ======================================================
+
+#[test]
+fn test_lint_01() {
+    assert!(check_lint("void f(i32 x) { i32 y = x; skip; }").is_empty());
+}
+
+#[test]
+fn test_lint_02() {
+    let ws = check_lint("void f(i32 x) { i32 x = 0; skip; }");
+    assert_eq!(ws.len(),1);
+    assert!(matches!(ws[0].errno,ErrorCode::ShadowedVariable));
+    assert_eq!(ws[0].errno.severity(),Severity::Warning);
+}
+
+#[test]
+fn test_lint_03() {
+    // Shadowing an enclosing local
+    let ws = check_lint("void f() { i32 y = 0; for (i32 y = 0; y < 1; y++) { i32 z = y; } }");
+    assert_eq!(ws.len(),1);
+}
+
+#[test]
+fn test_lint_04() {
+    // Sibling scopes do not shadow each other
+    let ws = check_lint("void f(bool b) { if b { i32 y = 0; } else { i32 y = 1; } }");
+    assert!(ws.is_empty());
+}
+
+// ======================================================
+// Tests (Print)
+// ======================================================
+
+#[test]
+fn test_print_01() {
+    check_parse("void f() { print 1 + 2; }");
+}
+
+#[test]
+fn test_print_02() {
+    check_parse("void f(bool b) { print b; print 1 < 2; }");
+}
+
+#[test]
+fn test_print_03() {
+    check_parse_error("void f() { print; }");
+}
+
+#[test]
+fn test_print_04() {
+    // NOTE: no expression can currently have type void, hence the
+    // check for this cannot be exercised from source.
+    let err = check_type_error("void f() { print x; }");
+    assert!(matches!(err.errno,ErrorCode::VariableNotFound));
+}
+
+#[test]
+fn test_print_05() {
+    let (ast,ds) = lil::parse("void f(i32 x) { print x + 2; print x < 0; }").unwrap();
+    let mut buf = Vec::new();
+    {
+	let interpreter = Interpreter::new(&ast).with_output(&mut buf);
+	assert!(interpreter.invoke(ds[0],&[Value::Int(1)]).is_ok());
+    }
+    assert_eq!(String::from_utf8(buf).unwrap(),"3\nfalse\n");
+}
+
+#[test]
+fn test_print_06() {
+    check_format("void f() {\n    print 1 + 2;\n}\n");
+}
+
+// ======================================================
+// Tests (Type Aliases)
+// ======================================================
+
+#[test]
+fn test_alias_01() {
+    check_parse("void f() { type t = i32; skip; }");
+}
+
+#[test]
+fn test_alias_02() {
+    check_parse("i32 f() { type t = i32; t x = 1; x = x + 1; return x; }");
+}
+
+#[test]
+fn test_alias_03() {
+    check_program("type t = bool; bool f(t x) { type u = t; u y = x; return y; }");
+}
+
+#[test]
+fn test_alias_04() {
+    // Local alias is not in scope outside its method
+    let err = check_program_parse_error("void f() { type t = i32; skip; } void g(t x) { skip; }");
+    assert_eq!(err.message,"unknown type 't'");
+}
+
+#[test]
+fn test_alias_05() {
+    // Local alias is not in scope outside its block
+    check_parse_error("void f() { { type t = i32; skip; } t x = 1; }");
+}
+
+#[test]
+fn test_alias_06() {
+    let err = check_type_error("void f() { type t = void[]; skip; }");
+    assert!(matches!(err.errno,ErrorCode::InvalidType{..}));
+}
+
+#[test]
+fn test_alias_07() {
+    check_format("void f() {\n    type t = i32;\n    skip;\n}\n");
+}
+
+#[test]
+fn test_alias_08() {
+    // Shared record type used in several places
+    check_program("type r = {i32 f, bool g}; void f(r x, r y) { r z = x; assert z.g; z = y; } bool g(r x) { return x.g; }");
+}
+
+#[test]
+fn test_alias_09() {
+    // Invalid shared type is reported at every use
+    let input = "type r = {i32 f, void g}; void f(r x) {} void g(r x) {}";
+    let mut ast = AbstractSyntaxTree::new();
+    let ds = Parser::new(input,&mut ast,source_mapper).parse_program().unwrap();
+    let errs = TypeChecker::new(&mut ast, type_mapper).check_program_all(&ds);
+    assert_eq!(errs.len(),3);
+    assert!(errs.iter().all(|e| matches!(e.errno,ErrorCode::InvalidType{..})));
+}
+
+// ======================================================
+// Tests (Nesting)
+// ======================================================
+
+#[test]
+fn test_nesting_01() {
+    let input = format!("void f() {{ assert {}true{}; }}","(".repeat(100),")".repeat(100));
+    check_parse(&input);
+}
+
+#[test]
+fn test_nesting_02() {
+    let input = format!("void f() {{ assert {}true{}; }}","(".repeat(100000),")".repeat(100000));
+    let err = check_parse_error(&input);
+    assert_eq!(err.message,"input nested too deeply");
+}
+
+#[test]
+fn test_nesting_03() {
+    let input = format!("type t = {}i32{};","(".repeat(100000),")".repeat(100000));
+    let err = check_parse_error(&input);
+    assert_eq!(err.message,"input nested too deeply");
+}
+
+#[test]
+fn test_nesting_04() {
+    let input = format!("void f() {}{}","{".repeat(100000),"}".repeat(100000));
+    let err = check_parse_error(&input);
+    assert_eq!(err.message,"input nested too deeply");
+}
+
+#[test]
+fn test_nesting_05() {
+    let input = format!("void f() {{ assert {}true{}; }}","(".repeat(10),")".repeat(10));
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(&input,&mut ast,source_mapper);
+    parser.set_recursion_limit(5);
+    let err = parser.parse_decl().err().unwrap();
+    assert_eq!(err.message,"input nested too deeply");
+}
+
+#[test]
+fn test_nesting_06() {
+    let input = format!("type t = {}i32[];","&".repeat(1000));
+    check_parse(&input);
+}
+
+// ======================================================
+// Tests (Precedence)
+// ======================================================
+
+#[test]
+fn test_precedence_01() {
+    assert_eq!(check_shape("a || b && c"),"(|| a (&& b c))");
+    assert_eq!(check_shape("a && b || c"),"(|| (&& a b) c)");
+}
+
+#[test]
+fn test_precedence_02() {
+    assert_eq!(check_shape("x - y + z"),"(+ (- x y) z)");
+    assert_eq!(check_shape("x - (y + z)"),"(- x (+ y z))");
+}
+
+#[test]
+fn test_precedence_03() {
+    assert_eq!(check_shape("x + 1 < y - 2"),"(< (+ x 1) (- y 2))");
+    assert_eq!(check_shape("x < y && y == z + 1"),"(&& (< x y) (== y (+ z 1)))");
+}
+
+#[test]
+fn test_precedence_04() {
+    assert_eq!(check_shape("a || b && c == d || e"),"(|| (|| a (&& b (== c d))) e)");
+    assert_eq!(check_shape("!a && b"),"(&& (! a) b)");
+}
+
+#[test]
+fn test_precedence_05() {
+    // Relational operators are non-associative
+    let mut ast = AbstractSyntaxTree::new();
+    assert!(Parser::new("x < y == z",&mut ast,source_mapper).parse_expr().is_err());
+    assert_eq!(check_shape("(x < y) == z"),"(== (< x y) z)");
+}
+
+#[test]
+fn test_precedence_06() {
+    assert!(matches!(binary_operator(TokenType::Plus),Some((_,Assoc::Left,_))));
+    assert!(matches!(binary_operator(TokenType::LeftAngle),Some((_,Assoc::None,_))));
+    assert!(binary_operator(TokenType::Dot).is_none());
+}
+
+// ======================================================
+// Tests (Logical Operators)
+// ======================================================
+
+#[test]
+fn test_logical_01() {
+    assert_eq!(check_expr_type("true && false"),Node::BoolType);
+    assert_eq!(check_expr_type("true || false"),Node::BoolType);
+    assert_eq!(check_expr_type("1 < 2 && 2 < 3"),Node::BoolType);
+}
+
+#[test]
+fn test_logical_02() {
+    // Conjunction binds more tightly than disjunction
+    let mut ast = AbstractSyntaxTree::new();
+    let e = Parser::new("a || b && c",&mut ast,source_mapper).parse_expr().unwrap();
+    match ast.get(e.0) {
+	Node::OrExpr(_,r) => assert!(matches!(ast.get(r.0),Node::AndExpr(..))),
+	n => panic!("unexpected node {:?}",n)
+    }
+}
+
+#[test]
+fn test_logical_03() {
+    check_parse("void f(bool a, &&i32 p) { assert a && (p == null || a); }");
+}
+
+#[test]
+fn test_logical_04() {
+    let err = check_type_error("void f(i32 x) { assert x && true; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_logical_05() {
+    let err = check_type_error("void f() { assert true || 1; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_logical_06() {
+    assert_eq!(check_evaluate("true && false"),Value::Bool(false));
+    assert_eq!(check_evaluate("false || true"),Value::Bool(true));
+    assert_eq!(check_evaluate("1 < 2 && 2 < 1"),Value::Bool(false));
+}
+
+#[test]
+fn test_logical_07() {
+    // The rhs is not evaluated when short circuited.  Since `x` is
+    // unbound, evaluating it would fail.
+    assert_eq!(check_evaluate("false && x"),Value::Bool(false));
+    assert_eq!(check_evaluate("true || x"),Value::Bool(true));
+}
+
+#[test]
+fn test_logical_08() {
+    assert_eq!(check_expr_type("!true"),Node::BoolType);
+    assert_eq!(check_evaluate("!true"),Value::Bool(false));
+    assert_eq!(check_evaluate("!(1 < 2) || !!true"),Value::Bool(true));
+}
+
+#[test]
+fn test_logical_09() {
+    let err = check_type_error("void f(i32 x) { assert !x; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_logical_10() {
+    check_format("bool f(bool a, bool b) {\n    return !a && !(a || b);\n}\n");
+}
+
+// ======================================================
+// Tests (Optimizer)
+// ======================================================
+
+#[test]
+fn test_optimizer_01() {
+    assert_eq!(check_fold("true && false"),Node::BoolExpr(false));
+    assert_eq!(check_fold("true || false"),Node::BoolExpr(true));
+}
+
+#[test]
+fn test_optimizer_02() {
+    assert_eq!(check_fold("!false"),Node::BoolExpr(true));
+    assert_eq!(check_fold("!(true && !false)"),Node::BoolExpr(false));
+}
+
+#[test]
+fn test_optimizer_03() {
+    assert_eq!(check_fold("true == false"),Node::BoolExpr(false));
+    assert_eq!(check_fold("(true && true) != false"),Node::BoolExpr(true));
+}
+
+#[test]
+fn test_optimizer_04() {
+    // Operands which are not literals are not folded
+    let mut ast = AbstractSyntaxTree::new();
+    let e = Parser::new("false && x",&mut ast,source_mapper).parse_expr().unwrap();
+    assert_eq!(optimizer::fold(&mut ast,e),e);
+}
+
+#[test]
+fn test_optimizer_05() {
+    // Only the literal part is folded
+    let mut ast = AbstractSyntaxTree::new();
+    let e = Parser::new("x || !true",&mut ast,source_mapper).parse_expr().unwrap();
+    let e = optimizer::fold(&mut ast,e);
+    match ast.get(e.0) {
+	Node::OrExpr(_,r) => assert_eq!(ast.get(r.0),&Node::BoolExpr(false)),
+	n => panic!("unexpected node {:?}",n)
+    }
+}
+
+#[test]
+fn test_optimizer_06() {
+    let mut ast = AbstractSyntaxTree::new();
+    let input = "bool f(i32 x) {\n    while !false && x < 1 {\n        x++;\n    }\n    return !(true || false);\n}\n";
+    let d = Parser::new(input,&mut ast,source_mapper).parse_decl().unwrap();
+    let d = optimizer::optimize(&mut ast,d);
+    let expected = "bool f(i32 x) {\n    while true && (x < 1) {\n        x = x + 1;\n    }\n    return false;\n}\n";
+    assert_eq!(format(&ast,d),expected);
+}
+
+// ======================================================
+// Tests (Calls)
+// ======================================================
+
+#[test]
+fn test_call_01() {
+    check_program("i32 f(i32 x) { return x; } i32 g() { return f(1); }");
+}
+
+#[test]
+fn test_call_02() {
+    let err = check_program_error("i32 f(i32 x, bool b) { return x; } i32 g() { return f(1); }");
+    assert!(matches!(err.errno,ErrorCode::ArityMismatch{expected:2,actual:1}));
+    assert_eq!(err.errno.to_string(),"expected 2 arguments, found 1");
+}
+
+#[test]
+fn test_call_03() {
+    let err = check_program_error("i32 f(i32 x) { return x; } i32 g() { return f(); }");
+    assert_eq!(err.errno.to_string(),"expected 1 argument, found 0");
+}
+
+#[test]
+fn test_call_04() {
+    let err = check_program_error("i32 f(i32 x) { return x; } i32 g() { return f(true); }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_call_05() {
+    let err = check_program_error("i32 g() { return f(1); }");
+    assert!(matches!(err.errno,ErrorCode::MethodNotFound));
+}
+
+#[test]
+fn test_call_06() {
+    // Methods can be called before they are declared, and recursively
+    check_program("i32 g() { return f(1); } i32 f(i32 x) { return f(x); }");
+}
+
+#[test]
+fn test_call_07() {
+    let (ast,ds) = lil::parse("i32 f(i32 x) { return x + 1; } i32 g(i32 y) { return f(f(y)); }").unwrap();
+    let r = Interpreter::new(&ast).invoke(ds[1],&[Value::Int(1)]);
+    assert_eq!(r,Ok(Some(Value::Int(3))));
+}
+
+#[test]
+fn test_call_08() {
+    check_format("i32 f() {\n    return g(1, x) + h();\n}\n");
+}
+
+#[test]
+fn test_call_09() {
+    // Methods can be overloaded by arity
+    check_program("void f() {} void f(i32 x) {}");
+}
+
+#[test]
+fn test_call_10() {
+    check_program("i32 f() { return 0; } bool f(i32 x) { return true; } bool g() { return f(f()); }");
+}
+
+#[test]
+fn test_call_11() {
+    // Overloads returning different types resolve separately
+    let err = check_program_error("i32 f() { return 0; } bool f(i32 x) { return true; } i32 g() { return f(1); }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_call_12() {
+    let err = check_program_error("i32 f() { return 0; } i32 f(i32 x) { return x; } i32 g() { return f(1,2); }");
+    assert!(matches!(err.errno,ErrorCode::NoMatchingMethod{actual:2}));
+    assert_eq!(err.errno.to_string(),"no method accepts 2 arguments");
+}
+
+#[test]
+fn test_call_13() {
+    let err = check_program_error("void f(i32 x) {} void f(bool y) {}");
+    assert!(matches!(err.errno,ErrorCode::DuplicateDeclaration));
+}
+
+#[test]
+fn test_call_14() {
+    let src = "i32 f() { return 1; } i32 f(i32 x) { return x + 2; } i32 g(i32 y) { return f() + f(y); }";
+    let (ast,ds) = lil::parse(src).unwrap();
+    let r = Interpreter::new(&ast).invoke(ds[2],&[Value::Int(3)]);
+    assert_eq!(r,Ok(Some(Value::Int(6))));
+}
+
+#[test]
+fn test_call_15() {
+    // Zero-argument declarations and calls
+    let (ast,_) = lil::parse("i32 g() { return 1; } i32 f() { return g(); }").unwrap();
+    assert_eq!(ast.get(5),&Node::MethodDecl(Name(1),Type(0),vec![],Stmt(4)));
+    check_name(ast.get(1),"g");
+    assert_eq!(ast.get(7),&Node::CallExpr(Name(1),vec![]));
+}
+
+#[test]
+fn test_call_16() {
+    check_program("void g() {} void f() { return g(); }");
+    check_program("i32 g() { return 1; } i32 f() { return g() + g(); }");
+    let err = check_program_error("bool g() { return true; } i32 f() { return g(); }");
+    assert_eq!(err.errno.to_string(),"expected i32, found bool");
+}
+
+#[test]
+fn test_call_17() {
+    check_parse_error("i32 f() { return g(,); }");
+    check_parse_error("i32 f() { return g(; }");
+    check_parse_error("void g(,) {}");
+}
+
+#[test]
+fn test_call_18() {
+    // Calls can be given as arguments to other calls
+    let (ast,_) = lil::parse("i32 g(i32 x) { return x; } i32 f(i32 y) { return f(g(1)); }").unwrap();
+    check_name(ast.get(1),"g");
+    check_name(ast.get(8),"f");
+    assert_eq!(ast.get(11),&Node::IntExpr(false,1));
+    assert_eq!(ast.get(12),&Node::CallExpr(Name(1),vec![Expr(11)]));
+    assert_eq!(ast.get(13),&Node::CallExpr(Name(8),vec![Expr(12)]));
+}
+
+#[test]
+fn test_call_19() {
+    check_program("i32 g(i32 x) { return x; } i32 f(i32 y) { return f(g(1)); }");
+    check_program("bool g() { return true; } i32 f(bool b, i32 x) { return f(g(),f(g(),x)); }");
+    let src = "i32 g(i32 x) { return x + 1; } i32 f(i32 y) { return g(g(y)); }";
+    let (ast,ds) = lil::parse(src).unwrap();
+    let r = Interpreter::new(&ast).invoke(ds[1],&[Value::Int(1)]);
+    assert_eq!(r,Ok(Some(Value::Int(3))));
+}
+
+#[test]
+fn test_call_20() {
+    // Argument type comes from the inner method's return type
+    let src = "bool g(i32 x) { return x < 1; } i32 f(i32 y) { return f(g(1)); }";
+    let err = check_program_error(src);
+    assert_eq!(err.errno.to_string(),"expected i32, found bool");
+    let err = check_program_error("i32 g(bool b) { return 1; } i32 f(i32 y) { return f(g(y)); }");
+    assert_eq!(err.errno.to_string(),"expected bool, found i32");
+}
+
+#[test]
+fn test_call_21() {
+    // Missing method reported against the call, even when its name
+    // appears earlier in the file
+    let (err,start,text) = check_program_error_highlight("void f(i32 g) { skip; } i32 h() { return g(1); }");
+    assert!(matches!(err.errno,ErrorCode::MethodNotFound));
+    assert_eq!((start,text.as_str()),(41,"g(1)"));
+}
+
+// ======================================================
+// Tests (Integer Literals)
+// ======================================================
+
+#[test]
+fn test_literal_01() {
+    // Literals take on the integer type expected of them
+    check_program("void f() { u8 x = 255; i8 y = -128; i64 z = 0; }");
+    check_program("u16 f(u16 x) { x = 65535; return 0; }");
+    check_program("i8 g(i8 x) { return x; } i8 f() { return g(127); }");
+    check_program("const u8 c = 0;");
+}
+
+#[test]
+fn test_literal_02() {
+    let err = check_program_error("void f() { u8 x = 256; }");
+    assert!(matches!(err.errno,ErrorCode::LiteralOutOfRange{negative:false,magnitude:256,signed:false,width:8}));
+    assert_eq!(err.errno.to_string(),"256 is out of range for u8 (0..=255)");
+    assert_eq!(err.node,4);
+}
+
+#[test]
+fn test_literal_03() {
+    let err = check_program_error("i8 f() { return -129; }");
+    assert_eq!(err.errno.to_string(),"-129 is out of range for i8 (-128..=127)");
+    let err = check_program_error("void f(u32 x) { x = -1; }");
+    assert_eq!(err.errno.to_string(),"-1 is out of range for u32 (0..=4294967295)");
+    assert_eq!(err.errno.code(),"E0021");
+}
+
+#[test]
+fn test_literal_04() {
+    assert_eq!(Type::int_range(false,8),(0,255));
+    assert_eq!(Type::int_range(true,16),(-32768,32767));
+    assert_eq!(Type::int_range(true,128),(i128::MIN,i128::MAX as u128));
+    assert_eq!(Type::int_range(false,128),(0,u128::MAX));
+}
+
+#[test]
+fn test_literal_05() {
+    // Literals wider than 32 bits
+    check_program("void f() { i64 x = 3000000000; }");
+    check_program("void f() { u128 x = 4294967296; }");
+    check_program("void f() { i32 x = -2147483648; }");
+    let err = check_program_error("void f() { i32 x = 3000000000; }");
+    assert_eq!(err.errno.to_string(),"3000000000 is out of range for i32 (-2147483648..=2147483647)");
+}
+
+#[test]
+fn test_literal_06() {
+    // Literals outside the range of every integer type
+    let mut ast = AbstractSyntaxTree::new();
+    let input = "void f() { i32 x = -170141183460469231731687303715884105729; }";
+    let err = Parser::new(input,&mut ast,source_mapper).parse_program().err().unwrap();
+    assert_eq!(err.to_string(),"integer literal out of range (at 19..59)");
+    assert!(lil::parse("void f() { u8 x = 999999999999999999999999999999999999999999; }").is_err());
+    assert!(lil::parse("void f() { i128 x = -170141183460469231731687303715884105728; }").is_ok());
+}
+
+#[test]
+fn test_literal_07() {
+    // Literals in the upper half of u128 are left to the type checker
+    check_program("void f() { u128 x = 340282366920938463463374607431768211455; }");
+    check_program("u128 f(u128 x) { x = 170141183460469231731687303715884105728; return x; }");
+    let err = check_program_error("void f() { i128 x = 170141183460469231731687303715884105728; }");
+    assert_eq!(err.errno.to_string(),"170141183460469231731687303715884105728 is out of range for i128 (-170141183460469231731687303715884105728..=170141183460469231731687303715884105727)");
+    let err = check_program_error("void f() { u8 x = 340282366920938463463374607431768211455; }");
+    assert!(matches!(err.errno,ErrorCode::LiteralOutOfRange{negative:false,magnitude:u128::MAX,signed:false,width:8}));
+    assert!(lil::parse("void f() { u8 x = 340282366920938463463374607431768211456; }").is_err());
+}
+
+// ======================================================
+// Tests (Widening)
+// ======================================================
+
+#[test]
+fn test_widening_01() {
+    check_program("void f(i32 y) { i64 x = 0; x = y; }");
+    check_program("void f(i32 y) { { i64 x = y; x = y; } }");
+    check_program("i64 f(i8 x) { return x; }");
+    check_program("i32 g(i128 x) { return 0; } i32 f(u8 x) { return g(x); }");
+}
+
+#[test]
+fn test_widening_02() {
+    // Narrowing is never permitted
+    let err = check_program_error("void f(i64 y) { i32 x = 0; x = y; }");
+    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+    assert_eq!(err.errno.to_string(),"expected i32, found i64");
+    let err = check_program_error("void f(i64 y) { i32 x = y; }");
+    assert_eq!(err.errno.to_string(),"expected i32, found i64");
+}
+
+#[test]
+fn test_widening_03() {
+    // Signedness must be preserved (unless widened)
+    check_program("void f(u16 y) { i32 x = y; }");
+    let err = check_program_error("void f(u8 y) { i8 x = y; }");
+    assert_eq!(err.errno.to_string(),"expected i8, found u8");
+    let err = check_program_error("void f(i8 y) { u64 x = y; }");
+    assert_eq!(err.errno.to_string(),"expected u64, found i8");
+}
+
+#[test]
+fn test_widening_04() {
+    // Widening does not apply to compound types
+    let err = check_program_error("void f(i32[] ys) { i64[] xs = ys; }");
+    assert_eq!(err.errno.to_string(),"expected i64[], found i32[]");
+    let err = check_program_error("void f(&i32 p) { &i64 q = p; }");
+    assert_eq!(err.errno.to_string(),"expected &i64, found &i32");
+}
+
+// ======================================================
+// Tests (Equality)
+// ======================================================
+
+#[test]
+fn test_equality_01() {
+    // Equality chains are rejected, rather than silently associated
+    for (input,op) in [("a == b == c",7),("a != b != c",7),("a == b != c",7),("a < b == c",6)] {
+	let mut ast = AbstractSyntaxTree::new();
+	let err = Parser::new(input,&mut ast,source_mapper).parse_expr().err().unwrap();
+	assert_eq!(err.message,"comparison operators cannot be chained; use parentheses");
+	assert_eq!((err.start,err.end),(op,op+2));
+    }
+    assert_eq!(check_shape("(a == b) != c"),"(!= (== a b) c)");
+    assert_eq!(check_shape("a == (b != c)"),"(== a (!= b c))");
+}
+
+#[test]
+fn test_equality_02() {
+    // Mismatched operands are reported, rather than panicking
+    let r = lil::typecheck("bool f() { return 1 == true; }");
+    match r {
+	Err(CompileError::Syntax(err)) => {
+	    assert!(matches!(err.errno,ErrorCode::ExpectedSubtype{..}));
+	    assert_eq!(err.errno.to_string(),"expected i32, found bool");
+	}
+	_ => panic!("expected type error")
+    }
+    let err = check_program_error("bool f(bool b) { return b != 1; }");
+    assert_eq!(err.errno.to_string(),"expected bool, found i32");
+}
+
+#[test]
+fn test_equality_03() {
+    check_execute("void f(i32 x) { assert x == 1; assert x != 2; assert (x == 1) == true; }");
+    check_execute("void f(i32 x) { bool b = x != 1; assert b == false; assert (b != true) == (x == 1); }");
+    assert_eq!(check_evaluate("(1 == 2) != (3 == 3)"),Value::Bool(true));
+}
+
+// ======================================================
+// Tests (Recovery)
+// ======================================================
+
+#[test]
+fn test_recovery_01() {
+    let (ast,err) = check_recovery("void f() { skip; x = ; skip; }");
+    assert_eq!(err.start,21);
+    let body = check_recovered_body(&ast);
+    assert_eq!(body.len(),3);
+    assert_eq!(ast.get(body[0].0),&Node::SkipStmt);
+    assert_eq!(ast.get(body[1].0),&Node::Error);
+    assert_eq!(ast.get(body[2].0),&Node::SkipStmt);
+}
+
+#[test]
+fn test_recovery_02() {
+    // Only the first error is reported
+    let (ast,err) = check_recovery("void f() { x = ; y = ; }");
+    assert_eq!(err.start,15);
+    let body = check_recovered_body(&ast);
+    assert_eq!(ast.get(body[0].0),&Node::Error);
+    assert_eq!(ast.get(body[1].0),&Node::Error);
+}
+
+#[test]
+fn test_recovery_03() {
+    // Recovery stops at the end of the enclosing block
+    let (ast,_) = check_recovery("void f() { if true { assert } skip; }");
+    let body = check_recovered_body(&ast);
+    assert_eq!(body.len(),2);
+    assert_eq!(ast.get(body[1].0),&Node::SkipStmt);
+    match ast.get(body[0].0) {
+	Node::IfStmt(_,tt,_) => {
+	    assert_eq!(ast.get(tt.0 - 1),&Node::Error);
+	    assert_eq!(ast.get(tt.0),&Node::BlockStmt(vec![Stmt(tt.0 - 1)]));
+	}
+	n => panic!("unexpected node {:?}",n)
+    }
+}
+
+#[test]
+fn test_recovery_04() {
+    // Later passes skip placeholders
+    let (mut ast,_) = check_recovery("void f() { skip; x = ; assert true; }");
+    let d = Decl{index: ast.len() - 1};
+    assert!(verify(&ast,&[d]).is_ok());
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    assert!(typer.check(d).is_ok());
+}
+
+#[test]
+fn test_recovery_05() {
+    // Broken declarations are skipped, whilst their errors are retained
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("void f(,) { skip; } type t = i32;",&mut ast,source_mapper);
+    let ds = parser.parse_program_partial();
+    assert_eq!(parser.errors().len(),1);
+    assert_eq!(parser.errors()[0].start,7);
+    assert_eq!(ds.len(),1);
+    assert!(matches!(ast.get(ds[0].index),Node::TypeDecl(_,_)));
+    assert_eq!(ast.roots(),&[ds[0].index]);
+}
+
+#[test]
+fn test_recovery_06() {
+    // Every error recovered from is retained
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("void f() { x = ; { y = ; } } void g() { skip; }",&mut ast,source_mapper);
+    let ds = parser.parse_program_partial();
+    let starts : Vec<usize> = parser.errors().iter().map(|e| e.start).collect();
+    assert_eq!(starts,[15,23]);
+    assert_eq!(ds.len(),1);
+}
+
+#[test]
+fn test_recovery_07() {
+    // Errors within nested blocks skip the entire declaration
+    let mut ast = AbstractSyntaxTree::new();
+    let input = "void f(bool b) { if b { while } } } type t = bool; i32 g() { return 1; }";
+    let mut parser = Parser::new(input,&mut ast,source_mapper);
+    let ds = parser.parse_program_partial();
+    assert_eq!(ds.len(),2);
+    assert!(!parser.errors().is_empty());
+    // Well-formed input produces no errors
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("type t = bool; void f() { }",&mut ast,source_mapper);
+    assert_eq!(parser.parse_program_partial().len(),2);
+    assert!(parser.errors().is_empty());
+}
+
+#[test]
+fn test_recovery_08() {
+    // Parsing stops once the maximum number of errors is reached
+    let mut ast = AbstractSyntaxTree::new();
+    let input = "void f() { x = ; y = ; } type t = ; type u = i32;";
+    let mut parser = Parser::new(input,&mut ast,source_mapper);
+    parser.set_max_errors(1);
+    assert!(parser.parse_program_partial().is_empty());
+    assert_eq!(parser.errors().len(),2);
+    assert_eq!(parser.errors()[1].message,"too many errors; stopping");
+    // Checking reports at most the maximum number of errors
+    let errs = lil::typecheck_all("bool f() { return 1; } bool g() { return 1; }",1).err().unwrap();
+    assert_eq!(errs.len(),2);
+    assert!(matches!(&errs[1],CompileError::Syntax(SyntaxError{errno: ErrorCode::TooManyErrors,..})));
+    assert!(lil::typecheck_all("i32 f() { return 1; }",1).is_ok());
+}
+
+#[test]
+fn test_recovery_09() {
+    // Exactly the maximum number of errors is not capped
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("type t = ; type u = ;",&mut ast,source_mapper);
+    parser.set_max_errors(2);
+    parser.parse_program_partial();
+    assert_eq!(parser.errors().len(),2);
+    // Beyond that, the note identifies the first error not reported
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("type t = ; type u = ; type v = ;",&mut ast,source_mapper);
+    parser.set_max_errors(2);
+    parser.parse_program_partial();
+    let errs = parser.errors();
+    assert_eq!(errs.len(),3);
+    assert_eq!((errs[2].start,errs[2].message.as_str()),(31,"too many errors; stopping"));
+    // A maximum of zero reports only the note
+    let errs = lil::typecheck_all("type t = ;",0).err().unwrap();
+    assert_eq!(errs.len(),1);
+    assert_eq!(errs[0].to_string(),"too many errors; stopping (at 9..10)");
+}
+
+// ======================================================
+// Tests (API)
+// ======================================================
+
+#[test]
+fn test_api_01() {
+    let (ast,ds) = lil::parse("type t = i32;").unwrap();
+    assert_eq!(ds.len(),1);
+    check_name(ast.get(0),"t");
+    assert_eq!(ast.get(1),&Node::IntType(true,32));
+    assert_eq!(ast.get(ds[0].index),&Node::TypeDecl(Name(0),Type(1)));
+}
+
+#[test]
+fn test_api_02() {
+    let (_,ds) = lil::parse("type t = i32; void f() { }").unwrap();
+    assert_eq!(ds.len(),2);
+}
+
+#[test]
+fn test_api_03() {
+    assert!(lil::parse("type t = ;").is_err());
+}
+
+#[test]
+fn test_api_04() {
+    assert!(lil::typecheck("type t = i32; i32 f(i32 x) { return x; }").is_ok());
+}
+
+#[test]
+fn test_api_05() {
+    let r = lil::typecheck("i32 f() { return true; }");
+    assert!(matches!(r,Err(CompileError::Syntax(_))));
+}
+
+#[test]
+fn test_api_06() {
+    let r = lil::typecheck("i32 f() { return }");
+    assert!(matches!(r,Err(CompileError::Parse(_))));
+}
+
+#[test]
+fn test_api_07() {
+    let (ast,ds) = lil::parse("").unwrap();
+    assert!(ds.is_empty());
+    assert_eq!(ast.len(),0);
+}
+
+#[test]
+fn test_api_08() {
+    let (_,ds) = lil::parse("   \n\t").unwrap();
+    assert!(ds.is_empty());
+}
+
+#[test]
+fn test_api_09() {
+    let (_,ds) = lil::parse("// just a comment").unwrap();
+    assert!(ds.is_empty());
+    let (_,ds) = lil::parse("/* just a comment */\n").unwrap();
+    assert!(ds.is_empty());
+}
+
+#[test]
+fn test_api_10() {
+    let (ast,ds) = lil::parse("type t = i32; i32 f() { return 0; } const i32 N = 1; @inline void g() { skip; }").unwrap();
+    assert_eq!(Decl::methods(&ast,&ds).count(),2);
+    assert_eq!(Decl::types(&ast,&ds).count(),1);
+    assert_eq!(Decl::of_kind(&ast,&ds,|n| matches!(n,Node::ConstDecl(..))).count(),1);
+    // Annotations are stripped
+    let g = Decl::methods(&ast,&ds).last().unwrap();
+    assert!(matches!(ast.get(g.index),Node::MethodDecl(..)));
+}
+
+#[test]
+fn test_api_11() {
+    // One error per method
+    let input : String = (0..25).map(|i| format!("void f{}() {{ assert {}; }}",i,i)).collect();
+    let (mut ast,ds) = lil::parse(&input).unwrap();
+    let errs = TypeChecker::new(&mut ast, type_mapper).check_program_all(&ds);
+    assert_eq!(errs.len(),DEFAULT_MAX_ERRORS + 1);
+    assert!(matches!(errs[DEFAULT_MAX_ERRORS].errno,ErrorCode::TooManyErrors));
+    // Smaller cap
+    let errs = TypeChecker::new(&mut ast, type_mapper).with_max_errors(3).check_program_all(&ds);
+    assert_eq!(errs.len(),4);
+    assert!(matches!(errs[3].errno,ErrorCode::TooManyErrors));
+}
+
+#[test]
+fn test_api_12() {
+    // Below the cap, no note is added
+    let (mut ast,ds) = lil::parse("void f() { assert 1; } void g() { skip; } void h() { assert 2; }").unwrap();
+    let errs = TypeChecker::new(&mut ast, type_mapper).with_max_errors(2).check_program_all(&ds);
+    assert_eq!(errs.len(),2);
+    assert!(matches!(errs[1].errno,ErrorCode::ExpectedSubtype{..}));
+}
+
+#[test]
+fn test_api_13() {
+    assert_eq!(Node::Utf8("x".to_string()).kind_name(),"name");
+    assert_eq!(Node::SkipStmt.kind_name(),"statement");
+    assert_eq!(Node::BoolExpr(true).kind_name(),"expression");
+    assert_eq!(Node::VoidType.kind_name(),"type");
+    assert_eq!(Node::TypeDecl(Name(0),Type(1)).kind_name(),"declaration");
+}
+
+#[test]
+fn test_api_14() {
+    // Checking a type as a statement is an internal failure
+    let mut ast = AbstractSyntaxTree::new();
+    let t = Parser::new("i32",&mut ast,source_mapper).parse_type().unwrap();
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    let err = typer.check_stmt(&Env::new(),t,Stmt(t.0)).err().unwrap();
+    assert_eq!(err.node,t.0);
+    assert_eq!(err.errno.to_string(),"internal failure (expected statement, found type)");
+}
+
+#[test]
+fn test_api_15() {
+    assert_eq!(lil::type_of("1 < 2").unwrap(),"bool");
+    assert_eq!(lil::type_of("1").unwrap(),"i32");
+    assert_eq!(lil::type_of("sizeof(u8[4])").unwrap(),"u64");
+}
+
+#[test]
+fn test_api_16() {
+    assert!(matches!(lil::type_of("1 < 2 )"),Err(CompileError::Parse(_))));
+    assert!(matches!(lil::type_of("x < 2"),Err(CompileError::Syntax(_))));
+}
+
+#[test]
+fn test_api_17() {
+    let mut ast = Ast::new();
+    assert!(ast.is_empty());
+    let r : AstRef = ast.push(Node::BoolType);
+    assert_eq!(r.raw_index(),0);
+    assert_eq!(r.get(),&Node::BoolType);
+    let t = Type::from(ast.push(Node::ArrayType(Type(0))));
+    assert_eq!(ast.len(),2);
+    assert_eq!(ast.get(t.0),&Node::ArrayType(Type(0)));
+}
+
+#[test]
+fn test_api_18() {
+    let inputs = ["i32 f(i32 x) { return x; }", "void f() { assert 1; }", "void f( {", "i32 f() { skip; }"];
+    for input in &inputs {
+	let (r1,_) = lil::typecheck_timed(input);
+	let r2 = lil::typecheck(input);
+	match (r1,r2) {
+	    (Ok(a1),Ok(a2)) => assert_eq!(a1.len(),a2.len()),
+	    (Err(e1),Err(e2)) => assert_eq!(format!("{:?}",e1),format!("{:?}",e2)),
+	    _ => panic!("timed and untimed results differ for {}",input)
+	}
+    }
+}
+
+#[test]
+fn test_api_19() {
+    // Parse methods can be called repeatedly on the same parser
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new("type t = i32; type u = t;",&mut ast,source_mapper);
+    let d1 = parser.parse_decl().unwrap();
+    let d2 = parser.parse_decl().unwrap();
+    let t = parser.parse_type();
+    assert!(t.is_err());
+    assert!(matches!(ast.get(d1.index),Node::TypeDecl(..)));
+    assert!(matches!(ast.get(d2.index),Node::TypeDecl(..)));
+}
+
+#[test]
+fn test_api_20() {
+    let text = lil::explain("E0002");
+    assert!(!text.is_empty());
+    assert!(text.contains("return true"));
+}
+
+#[test]
+fn test_api_21() {
+    assert_eq!(lil::explain("E9999"),"unknown error code 'E9999'");
+    assert_eq!(lil::explain("e0002"),"unknown error code 'e0002'");
+}
+
+#[test]
+fn test_api_22() {
+    // Errors report codes which can be explained
+    let err = check_program_error("i32 f() { return x; }");
+    assert_eq!(err.errno.code(),"E0003");
+    assert!(lil::error::explain(err.errno.code()).is_some());
+}
+
+#[test]
+fn test_api_23() {
+    let (ast,ds) = lil::parse("type t = i32; const t c = 1; void f() { skip; }").unwrap();
+    let roots : Vec<usize> = ds.iter().map(|d| d.index).collect();
+    assert_eq!(ast.roots(),&roots[..]);
+}
+
+#[test]
+fn test_api_24() {
+    // Roots are recorded only for top-level declarations
+    let mut ast = AbstractSyntaxTree::new();
+    assert!(ast.roots().is_empty());
+    Parser::new("void f() { }",&mut ast,source_mapper).parse_decl().unwrap();
+    assert!(ast.roots().is_empty());
+    Parser::new("",&mut ast,source_mapper).parse_program().unwrap();
+    assert!(ast.roots().is_empty());
+}
+
+#[test]
+fn test_api_25() {
+    // Deserialised trees report the same roots
+    let (ast,_) = lil::parse("type t = i32; void f(t x) { skip; }").unwrap();
+    let copy = AbstractSyntaxTree::from_json(&ast.to_json()).unwrap();
+    assert_eq!(copy.roots(),ast.roots());
+    assert_eq!(copy.len(),ast.len());
+    assert_eq!(copy.get(ast.roots()[1]),ast.get(ast.roots()[1]));
+}
+
+#[test]
+fn test_api_26() {
+    assert!(AbstractSyntaxTree::from_json("{\"nodes\":[{\"Foo\":1}],\"roots\":[]}").is_err());
+}
+
+#[test]
+fn test_api_27() {
+    // Reserve a parent, build its child, then fill the parent
+    let mut ast = AbstractSyntaxTree::new();
+    let parent = ast.reserve();
+    let child = ast.push(Node::SkipStmt).raw_index();
+    ast.fill(parent,Node::BlockStmt(vec![Stmt(child)]));
+    assert_eq!(ast.len(),2);
+    assert_eq!(ast.get(parent),&Node::BlockStmt(vec![Stmt(1)]));
+    assert_eq!(ast.get(child),&Node::SkipStmt);
+    // Filled slots are printed as such
+    let mut expected = AbstractSyntaxTree::new();
+    expected.push(Node::BlockStmt(vec![Stmt(1)]));
+    expected.push(Node::SkipStmt);
+    assert_eq!(ast.to_string(),expected.to_string());
+}
+
+#[test]
+#[should_panic]
+#[cfg(debug_assertions)]
+fn test_api_28() {
+    let mut ast = AbstractSyntaxTree::new();
+    let index = ast.reserve();
+    ast.get(index);
+}
+
+#[test]
+#[should_panic]
+fn test_api_29() {
+    let mut ast = AbstractSyntaxTree::new();
+    let index = ast.reserve();
+    ast.fill(index,Node::SkipStmt);
+    ast.fill(index,Node::SkipStmt);
+}
+
+#[test]
+fn test_api_30() {
+    // Generated (benchmark) programs are well-typed
+    let src = generator::generate_program(5);
+    let ast = lil::typecheck(&src).unwrap();
+    assert_eq!(ast.roots().len(),5);
+    let (ast,ds) = lil::parse(&src).unwrap();
+    let r = Interpreter::new(&ast).invoke(ds[4],&[Value::Int(1),Value::Bool(true)]);
+    assert!(r.is_ok());
+}
+
+#[test]
+fn test_api_31() {
+    // Parse errors can be boxed and printed
+    fn parse(src: &str) -> Result<(),Box<dyn std::error::Error>> {
+	lil::parse(src)?;
+	Ok(())
+    }
+    let err = parse("type t = ;").err().unwrap();
+    assert_eq!(err.to_string(),"unknown token encountered (at 9..10)");
+}
+
+#[test]
+fn test_api_32() {
+    let err = check_program_error("i32 f() { return x; }");
+    assert_eq!(err.to_string(),"error[E0003]: variable not found");
+    let err : Box<dyn std::error::Error> = Box::new(err);
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn test_api_33() {
+    // Compile errors print (and expose) the underlying error
+    let err = lil::typecheck("bool f() { return 1; }").err().unwrap();
+    assert_eq!(err.to_string(),"error[E0002]: expected bool, found i32");
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(source.to_string(),err.to_string());
+}
+
+#[test]
+fn test_api_34() {
+    // Distinct uses of a name key to the same symbol table entry
+    let (ast,ds) = lil::parse("i32 f(i32 x) { return x + x; }").unwrap();
+    let param = match ast.get(ds[0].index) {
+	Node::MethodDecl(_,_,ps,_) => ps[0].name,
+	n => panic!("unexpected node {:?}",n)
+    };
+    let uses = check_var_names(&ast);
+    assert_eq!(uses.len(),2);
+    let mut symbols = std::collections::HashMap::new();
+    symbols.insert(param,1);
+    *symbols.get_mut(&uses[0]).unwrap() += 1;
+    *symbols.get_mut(&uses[1]).unwrap() += 1;
+    assert_eq!(symbols.len(),1);
+    assert_eq!(symbols.get(&param),Some(&3));
+    // Likewise, when keyed by string
+    let mut symbols = std::collections::HashMap::new();
+    symbols.insert(param.key(&ast),1);
+    *symbols.get_mut(&uses[0].key(&ast)).unwrap() += 1;
+    assert_eq!(symbols.get("x"),Some(&2));
+}
+
+#[test]
+fn test_api_35() {
+    // Names parsed separately are distinct, but key the same
+    let mut ast = AbstractSyntaxTree::new();
+    Parser::new("x < y",&mut ast,source_mapper).parse_expr().unwrap();
+    Parser::new("x",&mut ast,source_mapper).parse_expr().unwrap();
+    let names = check_var_names(&ast);
+    let (x,y) = (names[0].key(&ast),names[1].key(&ast));
+    assert_eq!(x.as_str(),"x");
+    assert_eq!(x.name(),names[0]);
+    assert_ne!(x,y);
+    assert_ne!(names[0],names[2]);
+    assert_eq!(x,names[2].key(&ast));
+}
+
+#[test]
+fn test_api_36() {
+    // Pre-sizing a heap has no effect on the tree parsed into it
+    let input = "type T = {i32 f};\ni32 f(T t) { return t.f + 1; }";
+    let mut a1 = AbstractSyntaxTree::new();
+    Parser::new(input,&mut a1,source_mapper).parse_program().unwrap();
+    for n in [0,1,1024] {
+	let mut a2 = AbstractSyntaxTree::with_capacity(n);
+	Parser::new(input,&mut a2,source_mapper).parse_program().unwrap();
+	assert_eq!(a1.to_json(),a2.to_json());
+    }
+    assert_eq!(lil::parse(input).unwrap().0.to_json(),a1.to_json());
+}
+
+#[test]
+fn test_api_37() {
+    // Duplicate declarations count towards the cap
+    let input = "void f() { assert 1; } void f() { skip; } void g() { assert 2; }";
+    let (mut ast,ds) = lil::parse(input).unwrap();
+    let errs = TypeChecker::new(&mut ast, type_mapper).with_max_errors(1).check_program_all(&ds);
+    assert_eq!(errs.len(),2);
+    assert!(matches!(errs[0].errno,ErrorCode::DuplicateDeclaration));
+    assert!(matches!(errs[1].errno,ErrorCode::TooManyErrors));
+    let errs = TypeChecker::new(&mut ast, type_mapper).with_max_errors(0).check_program_all(&ds);
+    assert_eq!(errs.len(),1);
+    assert!(matches!(errs[0].errno,ErrorCode::TooManyErrors));
+    assert_eq!(errs[0].node,ds[1].index);
+}
+
+// ======================================================
+// Helpers
+// ======================================================
+
+/// Parse a declaration which contains a broken statement, returning
+/// the (partial) tree and the error reported.
+#[cfg(test)]
+fn check_recovery(input: &str) -> (AbstractSyntaxTree,Error) {
+    let mut ast = AbstractSyntaxTree::new();
+    let d = Parser::new(input,&mut ast, source_mapper).parse_decl();
+    assert!(d.is_err());
+    (ast,d.err().unwrap())
+}
+
+/// Extract the statements in the body of the method most recently
+/// added to a given tree.
+#[cfg(test)]
+fn check_recovered_body(ast: &AbstractSyntaxTree) -> Vec<Stmt> {
+    match ast.get(ast.len() - 1) {
+	Node::MethodDecl(_,_,_,body) => match ast.get(body.0) {
+	    Node::BlockStmt(stmts) => stmts.clone(),
+	    n => panic!("unexpected body {:?}",n)
+	},
+	n => panic!("unexpected declaration {:?}",n)
+    }
+}
+
+/// A dummy source mapper which does nothing.
+fn source_mapper<'a>(_: usize, _: &'a str) { }
+
+/// A dummy type mapper which does nothing.
+fn type_mapper<'a>(_: usize, _: Type) { }
+
+#[cfg(test)]
+fn check_parse(input: &str) -> Box<AbstractSyntaxTree> {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(input,&mut ast, source_mapper);
+    // Parse input
+    let d = parser.parse_decl();
+    println!("PARSED {:?}",d);
+    assert!(!d.is_err());
+    // Type input
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    let r = typer.check(d.unwrap());
+    assert!(!r.is_err());
+    // Done
+    Box::new(ast)
+}
+
+#[cfg(test)]
+fn check_parse_error(input: &str) -> Error {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut p = Parser::new(input,&mut ast, source_mapper);
+    let d = p.parse_decl();
+    assert!(d.is_err());
+    d.err().unwrap()
+}
+
+#[cfg(test)]
+fn check_program(input: &str) -> Box<AbstractSyntaxTree> {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(input,&mut ast, source_mapper);
+    // Parse input
+    let ds = parser.parse_program();
+    assert!(!ds.is_err());
+    // Type input
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    let r = typer.check_program(&ds.unwrap());
+    assert!(!r.is_err());
+    // Done
+    Box::new(ast)
+}
+
+#[cfg(test)]
+fn check_program_parse_error(input: &str) -> Error {
+    let mut ast = AbstractSyntaxTree::new();
+    let ds = Parser::new(input,&mut ast, source_mapper).parse_program();
+    assert!(ds.is_err());
+    ds.err().unwrap()
+}
+
+#[cfg(test)]
+fn check_program_error(input: &str) -> SyntaxError {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(input,&mut ast, source_mapper);
+    // Parse input
+    let ds = parser.parse_program();
+    assert!(!ds.is_err());
+    // Type input
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    let r = typer.check_program(&ds.unwrap());
+    assert!(r.is_err());
+    r.err().unwrap()
+}
+
+/// Parse and type check a given program which is expected to fail,
+/// returning the error along with where the text it highlights
+/// starts (on its line) and that text.
+#[cfg(test)]
+fn check_program_error_highlight(input: &str) -> (SyntaxError,usize,String) {
+    let mut map = SourceMap::new(input);
+    let mut ast = AbstractSyntaxTree::new();
+    let ds = Parser::new(input,&mut ast,|i,s| map.map(i,s)).parse_program().unwrap();
+    let err = TypeChecker::new(&mut ast, type_mapper).check_program(&ds).err().unwrap();
+    let hl = map.get_highlight(err.node);
+    (err,hl.start,hl.line[hl.start..hl.end].to_string())
+}
+
+#[cfg(test)]
+fn check_type_error(input: &str) -> SyntaxError {
+
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(input,&mut ast, source_mapper);
+    // Parse input
+    let d = parser.parse_decl();
+    println!("PARSED {:?}",d);
+    assert!(!d.is_err());
+    // Type input
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    let r = typer.check(d.unwrap());
+    assert!(r.is_err());
+    r.err().unwrap()
+}
+
+/// Parse and type check a given expression in an empty environment,
+/// returning the node representing its type.
+#[cfg(test)]
+fn check_expr_type(input: &str) -> Node {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(input,&mut ast, source_mapper);
+    let e = parser.parse_expr().unwrap();
+    let mut typer = TypeChecker::new(&mut ast, type_mapper);
+    let t = typer.check_expr(&Env::new(),e).unwrap();
+    ast.get(t.0).clone()
+}
+
+/// Parse, type check and then execute a method which accepts a
+/// single (optional) integer argument.  Execution should succeed.
+#[cfg(test)]
+fn check_execute(input: &str) {
+    let ast = check_program(input);
+    // Find method declaration
+    let mut index = 0;
+    while !matches!(ast.get(index),Node::MethodDecl(..)) { index += 1; }
+    let args = match ast.get(index) {
+	Node::MethodDecl(_,_,ps,_) if ps.is_empty() => vec![],
+	_ => vec![Value::Int(1)]
+    };
+    let r = Interpreter::new(&ast).invoke(Decl{index},&args);
+    assert!(r.is_ok());
+}
+
+/// Parse and fold a given expression, returning the resulting node.
+#[cfg(test)]
+fn check_fold(input: &str) -> Node {
+    let mut ast = AbstractSyntaxTree::new();
+    let e = Parser::new(input,&mut ast, source_mapper).parse_expr().unwrap();
+    let e = optimizer::fold(&mut ast,e);
+    ast.get(e.0).clone()
+}
+
+/// Parse and evaluate a given (closed) expression.
+#[cfg(test)]
+fn check_evaluate(input: &str) -> Value {
+    let mut ast = AbstractSyntaxTree::new();
+    let e = Parser::new(input,&mut ast, source_mapper).parse_expr().unwrap();
+    let v = Interpreter::new(&ast).evaluate(&Frame::new(),e);
+    v.unwrap()
+}
+
+/// Check that a given input is printed exactly as it was given.
+#[cfg(test)]
+fn check_format(input: &str) {
+    let mut ast = AbstractSyntaxTree::new();
+    let mut parser = Parser::new(input,&mut ast, source_mapper);
+    let d = parser.parse_decl().unwrap();
+    assert_eq!(format(&ast,d),input);
+}
+
+fn check_reachable(input: &str) {
+    let (ast,ds) = lil::parse(input).unwrap();
+    assert!(lil::reachability::check(&ast,&ds).is_ok());
+}
+
+fn check_unreachable(input: &str) -> SyntaxError {
+    let (ast,ds) = lil::parse(input).unwrap();
+    lil::reachability::check(&ast,&ds).err().unwrap()
+}
+
+fn check_lint(input: &str) -> Vec<SyntaxError> {
+    let (ast,ds) = lil::parse(input).unwrap();
+    lil::lint::check(&ast,&ds)
+}
+
+fn check_display_type(input: &str) -> String {
+    let mut ast = AbstractSyntaxTree::new();
+    let t = Parser::new(input,&mut ast, source_mapper).parse_type().unwrap();
+    display_type(&ast,t)
+}
+
+/// Parse two types and check whether or not they match structurally.
+#[cfg(test)]
+fn check_types_match(t1: &str, t2: &str) -> bool {
+    let mut ast = AbstractSyntaxTree::new();
+    let t1 = Parser::new(t1,&mut ast, source_mapper).parse_type().unwrap();
+    let t2 = Parser::new(t2,&mut ast, source_mapper).parse_type().unwrap();
+    let typer = TypeChecker::new(&mut ast, type_mapper);
+    typer.check_matching_types(&t1,&t2).is_ok()
+}
+
+fn check_structurally_eq(t1: &str, t2: &str) -> bool {
+    let mut ast = AbstractSyntaxTree::new();
+    let t1 = Parser::new(t1,&mut ast, source_mapper).parse_type().unwrap();
+    let t2 = Parser::new(t2,&mut ast, source_mapper).parse_type().unwrap();
+    Type::structurally_eq(&ast,t1,t2)
+}
+
+/// Check that a given node is an instance of Node::Utf8 and matches
+/// the corresponding string.
 #[cfg(test)]
 fn check_name(n: &Node, s: &str) {
     let r = match n {
@@ -359,3 +3382,38 @@ fn check_name(n: &Node, s: &str) {
     };
     assert_eq!(r.unwrap(),s);
 }
+
+/// Get the names of all variable accesses in a given tree, in the
+/// order they appear on the heap.
+#[cfg(test)]
+fn check_var_names(ast: &AbstractSyntaxTree) -> Vec<Name> {
+    (0..ast.len()).filter_map(|i| match ast.get(i) {
+	Node::VarExpr(n) => Some(*n),
+	_ => None
+    }).collect()
+}
+
+/// Parse a given expression and render the shape of its tree, where
+/// each operator is written prefix and bracketed (e.g. `(+ x 1)`).
+#[cfg(test)]
+fn check_shape(input: &str) -> String {
+    fn shape(ast: &AbstractSyntaxTree, e: Expr) -> String {
+	let (op,l,r) = match ast.get(e.0) {
+	    Node::AddExpr(l,r) => ("+",l,r),
+	    Node::AndExpr(l,r) => ("&&",l,r),
+	    Node::EqualsExpr(l,r) => ("==",l,r),
+	    Node::LessThanExpr(l,r) => ("<",l,r),
+	    Node::NotEqualsExpr(l,r) => ("!=",l,r),
+	    Node::OrExpr(l,r) => ("||",l,r),
+	    Node::SubtractExpr(l,r) => ("-",l,r),
+	    Node::NotExpr(e) => { return format!("(! {})",shape(ast,*e)); }
+	    Node::VarExpr(n) => { return n.as_str(ast).to_string(); }
+	    Node::IntExpr(n,i) => { return format!("{}{}",if *n { "-" } else { "" },i); }
+	    n => panic!("unexpected node {:?}",n)
+	};
+	format!("({} {} {})",op,shape(ast,*l),shape(ast,*r))
+    }
+    let mut ast = AbstractSyntaxTree::new();
+    let e = Parser::new(input,&mut ast,source_mapper).parse_expr().unwrap();
+    shape(&ast,e)
+}